@@ -0,0 +1,240 @@
+use std::f32::consts::TAU;
+
+use thiserror::Error;
+
+use crate::{BIT_RATE, CARRIER_FREQ, Hz, SAMPLE_RATE};
+
+/// longest carrier table accepted, keeps it within a few cache lines
+pub const MAX_CARRIER_SAMPLES: u32 = 1024;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("{freq} Hz is not below nyquist at {sample_rate} Hz")]
+    AboveNyquist { freq: Hz, sample_rate: Hz },
+
+    #[error("{bit_rate} bit/s doesn't divide {sample_rate} Hz into whole samples per bit")]
+    FractionalBit { bit_rate: Hz, sample_rate: Hz },
+
+    #[error("carrier step {steps} shares a factor with the {samples} sample table")]
+    SharedFactor { steps: u32, samples: u32 },
+
+    #[error("carrier needs a {samples} sample table, at most {MAX_CARRIER_SAMPLES} allowed")]
+    TableTooLong { samples: u32 },
+
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+}
+
+pub type ConfigResult<T> = Result<T, ConfigError>;
+
+/// sample rate, carrier and bit timing of the amplitude keyed modem
+///
+/// The carrier is played from a single-period lookup table of
+/// `carrier_samples` entries, advancing `carrier_steps` entries per sample, so
+/// the carrier sits at `sample_rate * carrier_steps / carrier_samples`. With
+/// the two coprime every entry of the table gets used and the phase never
+/// repeats early.
+///
+/// | sample rate | table | step | samples per bit | bit rate   |
+/// |-------------|-------|------|-----------------|------------|
+/// | 44.1 kHz    | 147   | 65   | 15              | 2940 bit/s |
+/// | 48 kHz      | 32    | 13   | 16              | 3000 bit/s |
+/// | 96 kHz      | 64    | 13   | 32              | 3000 bit/s |
+/// | 192 kHz     | 128   | 13   | 64              | 3000 bit/s |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModemConfig {
+    sample_rate: Hz,
+    carrier_steps: u32,   // table entries advanced per sample
+    carrier_samples: u32, // table entries in one carrier period
+    samples_per_bit: u16,
+}
+
+impl ModemConfig {
+    /// carrier at `carrier_freq`, `bit_rate` has to divide `sample_rate`
+    pub fn new(sample_rate: Hz, carrier_freq: Hz, bit_rate: Hz) -> ConfigResult<Self> {
+        if sample_rate == 0 || carrier_freq == 0 || bit_rate == 0 {
+            return Err(ConfigError::InvalidParameter(
+                "sample rate, carrier and bit rate must be positive".into(),
+            ));
+        }
+        if 2 * carrier_freq >= sample_rate {
+            return Err(ConfigError::AboveNyquist {
+                freq: carrier_freq,
+                sample_rate,
+            });
+        }
+        if !sample_rate.is_multiple_of(bit_rate) {
+            return Err(ConfigError::FractionalBit {
+                bit_rate,
+                sample_rate,
+            });
+        }
+        let samples_per_bit = u16::try_from(sample_rate / bit_rate)
+            .map_err(|_| ConfigError::InvalidParameter(format!("{bit_rate} bit/s is too slow")))?;
+        // smallest table holding a whole number of carrier periods
+        let common = gcd(carrier_freq, sample_rate);
+        Self::from_table(
+            sample_rate,
+            carrier_freq / common,
+            sample_rate / common,
+            samples_per_bit,
+        )
+    }
+
+    /// carrier given as a phase step through a table of `carrier_samples`
+    pub fn from_table(
+        sample_rate: Hz,
+        carrier_steps: u32,
+        carrier_samples: u32,
+        samples_per_bit: u16,
+    ) -> ConfigResult<Self> {
+        if carrier_steps == 0 || samples_per_bit == 0 {
+            return Err(ConfigError::InvalidParameter(
+                "carrier step and samples per bit must be positive".into(),
+            ));
+        }
+        if carrier_samples > MAX_CARRIER_SAMPLES {
+            return Err(ConfigError::TableTooLong {
+                samples: carrier_samples,
+            });
+        }
+        if 2 * carrier_steps >= carrier_samples {
+            return Err(ConfigError::AboveNyquist {
+                freq: (sample_rate as u64 * carrier_steps as u64 / carrier_samples as u64) as Hz,
+                sample_rate,
+            });
+        }
+        if gcd(carrier_steps, carrier_samples) != 1 {
+            return Err(ConfigError::SharedFactor {
+                steps: carrier_steps,
+                samples: carrier_samples,
+            });
+        }
+        Ok(Self {
+            sample_rate,
+            carrier_steps,
+            carrier_samples,
+            samples_per_bit,
+        })
+    }
+
+    /// same carrier at another sample rate, the bit rate moves to the nearest
+    /// one giving whole samples per bit
+    pub fn with_sample_rate(&self, sample_rate: Hz) -> ConfigResult<Self> {
+        let carrier = self.carrier_freq().round() as Hz;
+        let mut samples_per_bit = rescale(self.samples_per_bit, self.sample_rate, sample_rate);
+        while !sample_rate.is_multiple_of(samples_per_bit as Hz) && samples_per_bit < u16::MAX {
+            samples_per_bit += 1;
+        }
+        Self::new(sample_rate, carrier, sample_rate / samples_per_bit as Hz)
+    }
+
+    pub fn sample_rate(&self) -> Hz {
+        self.sample_rate
+    }
+
+    pub fn carrier_freq(&self) -> f32 {
+        self.sample_rate as f32 * self.carrier_steps as f32 / self.carrier_samples as f32
+    }
+
+    pub fn carrier_steps(&self) -> u32 {
+        self.carrier_steps
+    }
+
+    pub fn carrier_samples(&self) -> u32 {
+        self.carrier_samples
+    }
+
+    pub fn samples_per_bit(&self) -> u16 {
+        self.samples_per_bit
+    }
+
+    pub fn bit_rate(&self) -> f32 {
+        self.sample_rate as f32 / self.samples_per_bit as f32
+    }
+
+    /// one carrier period at unit amplitude, gain is applied per bit
+    pub fn carrier_table(&self) -> Vec<f32> {
+        let n = self.carrier_samples;
+        (0..n).map(|i| (TAU * i as f32 / n as f32).sin()).collect()
+    }
+}
+
+impl Default for ModemConfig {
+    fn default() -> Self {
+        Self::new(SAMPLE_RATE, CARRIER_FREQ, BIT_RATE).expect("default modem config is valid")
+    }
+}
+
+/// `samples` at `from` Hz as a duration in samples at `to` Hz, at least one
+pub(crate) fn rescale(samples: u16, from: Hz, to: Hz) -> u16 {
+    let scaled = (samples as u64 * to as u64 + from as u64 / 2) / from as u64;
+    scaled.clamp(1, u16::MAX as u64) as u16
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_matches_stock_table() {
+        let config = ModemConfig::default();
+        assert_eq!((config.carrier_steps(), config.carrier_samples()), (13, 32));
+        assert_eq!(config.samples_per_bit(), 16);
+        assert_eq!(config.carrier_freq(), 19_500.0);
+    }
+
+    #[test]
+    fn test_common_sample_rates() {
+        for (sample_rate, samples, steps) in
+            [(44_100, 147, 65), (96_000, 64, 13), (192_000, 128, 13)]
+        {
+            let config = ModemConfig::default()
+                .with_sample_rate(sample_rate)
+                .unwrap();
+            assert_eq!(
+                (config.carrier_samples(), config.carrier_steps()),
+                (samples, steps)
+            );
+            assert_eq!(config.carrier_freq().round(), 19_500.0);
+            assert_eq!(sample_rate % config.samples_per_bit() as Hz, 0);
+            assert!((config.bit_rate() - 3000.0).abs() < 100.0);
+        }
+    }
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(
+            ModemConfig::new(32_000, CARRIER_FREQ, BIT_RATE),
+            Err(ConfigError::AboveNyquist {
+                freq: CARRIER_FREQ,
+                sample_rate: 32_000
+            })
+        );
+        assert_eq!(
+            ModemConfig::new(44_100, CARRIER_FREQ, BIT_RATE),
+            Err(ConfigError::FractionalBit {
+                bit_rate: BIT_RATE,
+                sample_rate: 44_100
+            })
+        );
+        assert_eq!(
+            ModemConfig::from_table(SAMPLE_RATE, 12, 32, 16),
+            Err(ConfigError::SharedFactor {
+                steps: 12,
+                samples: 32
+            })
+        );
+        assert!(matches!(
+            ModemConfig::new(48_000, 19_501, BIT_RATE),
+            Err(ConfigError::TableTooLong { .. })
+        ));
+    }
+}
@@ -0,0 +1,233 @@
+use bitvec::{order::Lsb0, vec::BitVec};
+
+use crate::{ONE_GAIN, ZERO_GAIN, config::ModemConfig};
+
+/// streaming sample-to-bit interface shared by every keying scheme
+///
+/// Implementors decide the bits of a symbol window in `push_sample`, appending
+/// them to their bit buffer; byte packing and the iterator adapters come for
+/// free.
+pub trait BitDemodulator {
+    /// accumulate one sample, buffering bits once a window completes, returns bits decided
+    fn push_sample(&mut self, sample: f32) -> usize;
+
+    /// discard the partially accumulated window
+    fn reset_window(&mut self);
+
+    /// decided bits not yet taken as bytes
+    fn bit_buffer(&self) -> &BitVec<u8, Lsb0>;
+
+    fn bit_buffer_mut(&mut self) -> &mut BitVec<u8, Lsb0>;
+
+    /// streaming push, decided bits are buffered until taken, returns bits decided
+    fn push(&mut self, samples: &[f32]) -> usize {
+        samples.iter().map(|&sample| self.push_sample(sample)).sum()
+    }
+
+    /// number of buffered bits not yet taken
+    fn pending_bits(&self) -> usize {
+        self.bit_buffer().len()
+    }
+
+    /// drain every complete byte, leftover bits stay buffered
+    fn take_bytes(&mut self) -> Vec<u8> {
+        let buffer = self.bit_buffer_mut();
+        let whole = buffer.len() / 8;
+        let rest = buffer.split_off(whole * 8);
+        std::mem::replace(buffer, rest).into_vec()
+    }
+
+    /// drop partial window and buffered bits, e.g. after losing sync
+    fn reset(&mut self) {
+        self.reset_window();
+        self.bit_buffer_mut().clear();
+    }
+
+    /// lazily demodulate an iterator of samples into bits
+    fn bits<I>(self, samples: I) -> Bits<Self, I::IntoIter>
+    where
+        Self: Sized,
+        I: IntoIterator<Item = f32>,
+    {
+        Bits {
+            demod: self,
+            samples: samples.into_iter(),
+        }
+    }
+}
+
+/// non-coherent signal demodulator, inverse of `WaveGenerator`
+///
+/// Integrates carrier energy over each bit window and compares the resulting
+/// amplitude against a threshold. At the stock rate a bit spans exactly 6.5
+/// carrier periods, a whole number of half periods, so the energy of a window
+/// does not depend on where the carrier table cursor started. Other rates may
+/// leave a fraction of a half period over, which moves the estimate by a few
+/// percent, far from the threshold.
+pub struct WaveDemodulator {
+    threshold: f32, // amplitude separating one from zero
+    energy: f32,    // accumulated energy of current bit
+    samples_per_bit: u16,
+    count: u16,             // samples accumulated in current bit
+    bits: BitVec<u8, Lsb0>, // decided bits not yet taken as bytes
+}
+
+impl WaveDemodulator {
+    pub fn new(config: ModemConfig) -> Self {
+        // geometric mean sits halfway between the gains on a log scale
+        Self::with_threshold(config, (ONE_GAIN * ZERO_GAIN).sqrt())
+    }
+
+    /// threshold is a carrier amplitude, expects input normalized to the generator gains
+    pub fn with_threshold(config: ModemConfig, threshold: f32) -> Self {
+        Self {
+            threshold,
+            energy: 0.0,
+            samples_per_bit: config.samples_per_bit(),
+            count: 0,
+            bits: BitVec::new(),
+        }
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    /// demodulate a whole buffer, trailing partial byte is dropped
+    pub fn decode(config: ModemConfig, samples: &[f32]) -> Vec<u8> {
+        let mut demod = Self::new(config);
+        demod.push(samples);
+        demod.take_bytes()
+    }
+}
+
+impl BitDemodulator for WaveDemodulator {
+    fn push_sample(&mut self, sample: f32) -> usize {
+        self.energy += sample * sample;
+        self.count += 1;
+        if self.count < self.samples_per_bit {
+            return 0;
+        }
+        // mean power of a sine is amplitude^2 / 2
+        let amplitude = (2.0 * self.energy / self.samples_per_bit as f32).sqrt();
+        self.energy = 0.0;
+        self.count = 0;
+        self.bits.push(amplitude >= self.threshold);
+        1
+    }
+
+    fn reset_window(&mut self) {
+        self.energy = 0.0;
+        self.count = 0;
+    }
+
+    fn bit_buffer(&self) -> &BitVec<u8, Lsb0> {
+        &self.bits
+    }
+
+    fn bit_buffer_mut(&mut self) -> &mut BitVec<u8, Lsb0> {
+        &mut self.bits
+    }
+}
+
+impl Default for WaveDemodulator {
+    fn default() -> Self {
+        Self::new(ModemConfig::default())
+    }
+}
+
+/// lazy bit iterator over a sample source
+pub struct Bits<D, I> {
+    demod: D,
+    samples: I,
+}
+
+impl<D: BitDemodulator, I: Iterator<Item = f32>> Bits<D, I> {
+    /// regroup bits into bytes, least significant bit first like `WaveGenerator`
+    pub fn bytes(self) -> Bytes<D, I> {
+        Bytes { bits: self }
+    }
+}
+
+impl<D: BitDemodulator, I: Iterator<Item = f32>> Iterator for Bits<D, I> {
+    type Item = bool;
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.demod.pending_bits() == 0 {
+            let sample = self.samples.next()?;
+            self.demod.push_sample(sample);
+        }
+        // at most one symbol worth of bits is ever buffered here
+        Some(self.demod.bit_buffer_mut().remove(0))
+    }
+}
+
+/// lazy byte iterator over a sample source
+pub struct Bytes<D, I> {
+    bits: Bits<D, I>,
+}
+
+impl<D: BitDemodulator, I: Iterator<Item = f32>> Iterator for Bytes<D, I> {
+    type Item = u8;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut byte = 0u8;
+        for i in 0..8 {
+            byte |= (self.bits.next()? as u8) << i;
+        }
+        Some(byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WaveGenerator;
+
+    const MESSAGE: &[u8] = b"chirp chirp \x00\xff\x5a";
+
+    #[test]
+    fn test_round_trip_slice() {
+        let config = ModemConfig::default();
+        let samples: Vec<f32> = WaveGenerator::new(config, MESSAGE).collect();
+        assert_eq!(WaveDemodulator::decode(config, &samples), MESSAGE);
+    }
+
+    #[test]
+    fn test_round_trip_other_sample_rates() {
+        for sample_rate in [44_100, 96_000, 192_000] {
+            let config = ModemConfig::default()
+                .with_sample_rate(sample_rate)
+                .unwrap();
+            let samples: Vec<f32> = WaveGenerator::new(config, MESSAGE).collect();
+            assert_eq!(
+                samples.len(),
+                MESSAGE.len() * 8 * config.samples_per_bit() as usize
+            );
+            assert_eq!(WaveDemodulator::decode(config, &samples), MESSAGE);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_iterator() {
+        let config = ModemConfig::default();
+        let bytes: Vec<u8> = WaveDemodulator::new(config)
+            .bits(WaveGenerator::new(config, MESSAGE))
+            .bytes()
+            .collect();
+        assert_eq!(bytes, MESSAGE);
+    }
+
+    #[test]
+    fn test_round_trip_streaming() {
+        let config = ModemConfig::default();
+        let samples: Vec<f32> = WaveGenerator::new(config, MESSAGE).collect();
+        let mut demod = WaveDemodulator::new(config);
+        let mut bytes = Vec::new();
+        // odd chunk size so windows straddle pushes
+        for chunk in samples.chunks(37) {
+            demod.push(chunk);
+            bytes.extend(demod.take_bytes());
+        }
+        assert_eq!(bytes, MESSAGE);
+        assert_eq!(demod.pending_bits(), 0);
+    }
+}
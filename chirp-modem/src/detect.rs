@@ -0,0 +1,221 @@
+use std::f32::consts::TAU;
+
+use crate::Hz;
+
+/// single-bin DFT over a block of samples using the Goertzel recurrence
+#[derive(Debug, Clone, Copy)]
+pub struct Goertzel {
+    coeff: f32,
+}
+
+impl Goertzel {
+    pub fn new(freq: Hz, sample_rate: Hz) -> Self {
+        let omega = TAU * freq as f32 / sample_rate as f32;
+        Self {
+            coeff: 2.0 * omega.cos(),
+        }
+    }
+
+    /// bin `bin` of a `len`-sample DFT, exact where the frequency isn't a
+    /// whole number of Hz
+    pub fn at_bin(bin: u16, len: usize) -> Self {
+        let omega = TAU * bin as f32 / len as f32;
+        Self {
+            coeff: 2.0 * omega.cos(),
+        }
+    }
+
+    /// power of the bin scaled so a sinusoid of amplitude `a` centered on it reads `a^2`
+    pub fn power(&self, samples: impl IntoIterator<Item = f32>) -> f32 {
+        let (mut s1, mut s2, mut n) = (0.0f32, 0.0f32, 0usize);
+        for x in samples {
+            let s0 = x + self.coeff * s1 - s2;
+            s2 = s1;
+            s1 = s0;
+            n += 1;
+        }
+        if n == 0 {
+            return 0.0;
+        }
+        let magnitude = s1 * s1 + s2 * s2 - self.coeff * s1 * s2;
+        4.0 * magnitude / (n * n) as f32
+    }
+}
+
+/// per-tone outcome of the latest analysis window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub freq: Hz,
+    pub power: f32,
+    pub on: bool,
+}
+
+/// streaming bank of Goertzel filters over sliding windows
+///
+/// Every `hop` samples the last `window` samples are analysed at each tone.
+/// A tone turns on once its power reaches the on threshold and only turns off
+/// again when it drops below the off threshold, so a carrier hovering around a
+/// single threshold doesn't chatter.
+pub struct ToneDetector {
+    filters: Vec<Goertzel>,
+    detections: Vec<Detection>,
+    window: usize,     // samples per analysis window
+    hop: usize,        // samples between analyses
+    on_power: f32,     // power to switch a tone on
+    off_power: f32,    // power to switch a tone off
+    history: Vec<f32>, // ring buffer of the last `window` samples
+    head: usize,       // next write index in history
+    filled: usize,     // valid samples in history
+    since: usize,      // samples since last analysis
+    position: u64,     // samples consumed in total
+}
+
+impl ToneDetector {
+    /// non-overlapping windows, default thresholds suit input normalized to full scale
+    pub fn new(sample_rate: Hz, freqs: &[Hz], window: usize) -> Self {
+        assert!(window > 0, "window must hold at least one sample");
+        Self {
+            filters: freqs
+                .iter()
+                .map(|&freq| Goertzel::new(freq, sample_rate))
+                .collect(),
+            detections: freqs
+                .iter()
+                .map(|&freq| Detection {
+                    freq,
+                    power: 0.0,
+                    on: false,
+                })
+                .collect(),
+            window,
+            hop: window,
+            on_power: 0.01,   // amplitude 0.1
+            off_power: 0.004, // amplitude ~0.063
+            history: vec![0.0; window],
+            head: 0,
+            filled: 0,
+            since: 0,
+            position: 0,
+        }
+    }
+
+    /// analyse every `hop` samples, hops shorter than the window overlap
+    pub fn with_hop(mut self, hop: usize) -> Self {
+        assert!(hop > 0, "hop must advance at least one sample");
+        self.hop = hop;
+        self
+    }
+
+    /// on/off power thresholds, on must not be below off
+    pub fn with_hysteresis(mut self, on_power: f32, off_power: f32) -> Self {
+        assert!(on_power >= off_power, "on threshold below off threshold");
+        self.on_power = on_power;
+        self.off_power = off_power;
+        self
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// results of the most recent window, in configured tone order
+    pub fn detections(&self) -> &[Detection] {
+        &self.detections
+    }
+
+    /// true if any configured tone is currently on
+    pub fn any_on(&self) -> bool {
+        self.detections.iter().any(|d| d.on)
+    }
+
+    /// total samples consumed
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// consume one sample, returns true when a new window was analysed
+    pub fn push_sample(&mut self, sample: f32) -> bool {
+        self.history[self.head] = sample;
+        self.head = (self.head + 1) % self.window;
+        self.filled = (self.filled + 1).min(self.window);
+        self.since += 1;
+        self.position += 1;
+        if self.filled < self.window || self.since < self.hop {
+            return false;
+        }
+        self.since = 0;
+        self.analyse();
+        true
+    }
+
+    /// consume a block, calling `on_window` with the end position of every analysed window
+    pub fn push(&mut self, samples: &[f32], mut on_window: impl FnMut(u64, &[Detection])) {
+        for &sample in samples {
+            if self.push_sample(sample) {
+                on_window(self.position, &self.detections);
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.head = 0;
+        self.filled = 0;
+        self.since = 0;
+        for detection in self.detections.iter_mut() {
+            detection.power = 0.0;
+            detection.on = false;
+        }
+    }
+
+    fn analyse(&mut self) {
+        // oldest sample sits at head once the ring is full
+        let (newer, older) = self.history.split_at(self.head);
+        for (filter, detection) in self.filters.iter().zip(self.detections.iter_mut()) {
+            let power = filter.power(older.iter().chain(newer).copied());
+            detection.power = power;
+            if detection.on {
+                detection.on = power >= self.off_power;
+            } else {
+                detection.on = power >= self.on_power;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CARRIER_FREQ, SAMPLE_RATE};
+
+    fn tone(freq: Hz, amplitude: f32, len: usize) -> impl Iterator<Item = f32> {
+        (0..len).map(move |n| amplitude * (TAU * freq as f32 * n as f32 / SAMPLE_RATE as f32).sin())
+    }
+
+    #[test]
+    fn test_goertzel_power_matches_amplitude() {
+        let goertzel = Goertzel::new(CARRIER_FREQ, SAMPLE_RATE);
+        let power = goertzel.power(tone(CARRIER_FREQ, 0.5, 480));
+        assert!((power - 0.25).abs() < 0.01, "power {power}");
+        let off_bin = goertzel.power(tone(18_000, 0.5, 480));
+        assert!(off_bin < 0.001, "leakage {off_bin}");
+    }
+
+    #[test]
+    fn test_detector_hysteresis() {
+        let mut detector = ToneDetector::new(SAMPLE_RATE, &[CARRIER_FREQ], 96);
+        let mut states = Vec::new();
+        // loud, between thresholds, then quiet
+        let samples: Vec<f32> = tone(CARRIER_FREQ, 0.5, 960)
+            .chain(tone(CARRIER_FREQ, 0.08, 960))
+            .chain(tone(CARRIER_FREQ, 0.01, 960))
+            .collect();
+        detector.push(&samples, |_, detections| states.push(detections[0].on));
+        assert_eq!(states.len(), 30);
+        assert!(states[..20].iter().all(|&on| on));
+        assert!(states[21..].iter().all(|&on| !on));
+    }
+}
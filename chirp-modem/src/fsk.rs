@@ -0,0 +1,218 @@
+use std::f32::consts::TAU;
+
+use bitvec::{
+    order::Lsb0,
+    slice::{BitSlice, Iter as BitIter},
+    vec::BitVec,
+    view::BitView,
+};
+
+use crate::{
+    Hz, SAMPLE_RATE,
+    config::{ConfigError, ConfigResult, rescale},
+    demodulator::BitDemodulator,
+    detect::Goertzel,
+};
+
+/// tone pair and bit duration of a binary FSK link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FskTones {
+    pub sample_rate: Hz,
+    pub mark: Hz,             // tone keyed for set bits
+    pub space: Hz,            // tone keyed for clear bits
+    pub samples_per_bit: u16, // bit duration at `sample_rate`
+}
+
+impl FskTones {
+    pub fn new(sample_rate: Hz, mark: Hz, space: Hz, samples_per_bit: u16) -> ConfigResult<Self> {
+        if mark == space {
+            return Err(ConfigError::InvalidParameter(
+                "mark and space tones must differ".into(),
+            ));
+        }
+        if 2 * mark.max(space) >= sample_rate {
+            return Err(ConfigError::AboveNyquist {
+                freq: mark.max(space),
+                sample_rate,
+            });
+        }
+        if samples_per_bit == 0 {
+            return Err(ConfigError::InvalidParameter(
+                "bits must last at least one sample".into(),
+            ));
+        }
+        Ok(Self {
+            sample_rate,
+            mark,
+            space,
+            samples_per_bit,
+        })
+    }
+
+    /// same tones at another sample rate, bits keep their duration as close
+    /// as whole samples allow
+    pub fn with_sample_rate(&self, sample_rate: Hz) -> ConfigResult<Self> {
+        Self::new(
+            sample_rate,
+            self.mark,
+            self.space,
+            rescale(self.samples_per_bit, self.sample_rate, sample_rate),
+        )
+    }
+
+    /// tone spacing at which the pair is orthogonal over one bit
+    pub fn orthogonal_spacing(sample_rate: Hz, samples_per_bit: u16) -> Hz {
+        sample_rate / samples_per_bit as Hz
+    }
+}
+
+impl Default for FskTones {
+    fn default() -> Self {
+        // 1 kHz apart over 1 ms bits, the minimum orthogonal spacing
+        Self::new(SAMPLE_RATE, 19_500, 18_500, 48).expect("default tones are valid")
+    }
+}
+
+/// zero-copy iterator continuous-phase binary FSK modulator
+///
+/// The oscillator phase carries over between bits so switching tones never
+/// causes a discontinuity, keeping the spectrum tight around the two tones.
+pub struct FskGenerator<'a> {
+    mark_step: f32,  // radians per sample for set bits
+    space_step: f32, // radians per sample for clear bits
+    phase: f32,
+    samples_per_bit: u16,
+    count: u16,
+    hold: bool,
+    bits: BitIter<'a, u8, Lsb0>,
+}
+
+impl<'a> FskGenerator<'a> {
+    pub fn new(tones: FskTones, data: &'a [u8]) -> Self {
+        let bits: &'a BitSlice<u8, Lsb0> = data.view_bits::<Lsb0>();
+        Self {
+            mark_step: TAU * tones.mark as f32 / tones.sample_rate as f32,
+            space_step: TAU * tones.space as f32 / tones.sample_rate as f32,
+            phase: 0.0,
+            samples_per_bit: tones.samples_per_bit,
+            count: 0,
+            hold: false,
+            bits: bits.iter(),
+        }
+    }
+}
+
+impl<'a> Iterator for FskGenerator<'a> {
+    type Item = f32;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.count == 0 {
+            self.hold = *self.bits.next()?;
+        }
+        let value = self.phase.sin();
+        self.count = (self.count + 1) % self.samples_per_bit;
+        let step = if self.hold {
+            self.mark_step
+        } else {
+            self.space_step
+        };
+        self.phase = (self.phase + step) % TAU;
+        Some(value)
+    }
+}
+
+/// non-coherent binary FSK demodulator
+///
+/// Compares Goertzel power at the mark and space tones over each bit window.
+/// Only the ratio between the tones matters, so the decision holds regardless
+/// of speaker volume or microphone gain.
+pub struct FskDemodulator {
+    mark: Goertzel,
+    space: Goertzel,
+    window: Vec<f32>, // samples of the current bit
+    samples_per_bit: usize,
+    bits: BitVec<u8, Lsb0>,
+}
+
+impl FskDemodulator {
+    pub fn new(tones: FskTones) -> Self {
+        let samples_per_bit = tones.samples_per_bit as usize;
+        Self {
+            mark: Goertzel::new(tones.mark, tones.sample_rate),
+            space: Goertzel::new(tones.space, tones.sample_rate),
+            window: Vec::with_capacity(samples_per_bit),
+            samples_per_bit,
+            bits: BitVec::new(),
+        }
+    }
+
+    /// demodulate a whole buffer, trailing partial byte is dropped
+    pub fn decode(tones: FskTones, samples: &[f32]) -> Vec<u8> {
+        let mut demod = Self::new(tones);
+        demod.push(samples);
+        demod.take_bytes()
+    }
+}
+
+impl BitDemodulator for FskDemodulator {
+    fn push_sample(&mut self, sample: f32) -> usize {
+        self.window.push(sample);
+        if self.window.len() < self.samples_per_bit {
+            return 0;
+        }
+        let mark = self.mark.power(self.window.iter().copied());
+        let space = self.space.power(self.window.iter().copied());
+        self.window.clear();
+        self.bits.push(mark > space);
+        1
+    }
+
+    fn reset_window(&mut self) {
+        self.window.clear();
+    }
+
+    fn bit_buffer(&self) -> &BitVec<u8, Lsb0> {
+        &self.bits
+    }
+
+    fn bit_buffer_mut(&mut self) -> &mut BitVec<u8, Lsb0> {
+        &mut self.bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &[u8] = b"bfsk \x00\xff\xa5";
+
+    #[test]
+    fn test_round_trip_independent_of_gain() {
+        let tones = FskTones::default();
+        for gain in [1.0, 0.05, 0.001] {
+            let samples: Vec<f32> = FskGenerator::new(tones, MESSAGE)
+                .map(|s| s * gain)
+                .collect();
+            assert_eq!(FskDemodulator::decode(tones, &samples), MESSAGE);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_other_sample_rates() {
+        for sample_rate in [44_100, 96_000, 192_000] {
+            let tones = FskTones::default().with_sample_rate(sample_rate).unwrap();
+            let samples: Vec<f32> = FskGenerator::new(tones, MESSAGE).collect();
+            assert_eq!(FskDemodulator::decode(tones, &samples), MESSAGE);
+        }
+        assert!(FskTones::default().with_sample_rate(32_000).is_err());
+    }
+
+    #[test]
+    fn test_phase_is_continuous() {
+        let samples: Vec<f32> = FskGenerator::new(FskTones::default(), MESSAGE).collect();
+        // largest possible step between samples of the faster tone
+        let max_step = TAU * 19_500.0 / SAMPLE_RATE as f32;
+        for pair in samples.windows(2) {
+            assert!((pair[1] - pair[0]).abs() <= max_step + 1e-3);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::{fmt, str::FromStr};
+
+use bitvec::{order::Lsb0, slice::BitSlice, vec::BitVec};
+
+use crate::config::ConfigError;
+
+/// reorders bits before modulation so a burst of lost bits on the air lands
+/// in many different codewords after deinterleaving
+///
+/// Both variants are plain permutations of the bits they are given, so the
+/// length never changes and a whole number of bytes stays a whole number of
+/// bytes, ready for `WaveGenerator` and friends. Sender and receiver have to
+/// agree on the interleaver up front, the same way they agree on keying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interleaver {
+    /// bits go out in the order they came in
+    #[default]
+    None,
+    /// written row by row into a `rows` x `cols` matrix and read column by
+    /// column, a trailing partial block is read the same way skipping the
+    /// cells it doesn't fill
+    Block { rows: usize, cols: usize },
+    /// tail-biting convolutional interleaver, bit `n` goes through branch
+    /// `n % branches` which delays it by `branch * depth` rounds of
+    /// `branches` bits, wrapping around the end of the input
+    Convolutional { branches: usize, depth: usize },
+}
+
+impl Interleaver {
+    /// adjacent input bits end up `rows` bits apart
+    pub fn block(rows: usize, cols: usize) -> Self {
+        assert!(
+            rows > 0 && cols > 0,
+            "block needs at least one row and column"
+        );
+        Interleaver::Block { rows, cols }
+    }
+
+    /// adjacent input bits end up about `branches * depth` bits apart
+    pub fn convolutional(branches: usize, depth: usize) -> Self {
+        assert!(
+            branches > 0 && depth > 0,
+            "need at least one branch and delay"
+        );
+        Interleaver::Convolutional { branches, depth }
+    }
+
+    pub fn interleave(&self, bits: &BitSlice<u8, Lsb0>) -> BitVec<u8, Lsb0> {
+        self.permutation(bits.len())
+            .into_iter()
+            .map(|source| bits[source])
+            .collect()
+    }
+
+    pub fn deinterleave(&self, bits: &BitSlice<u8, Lsb0>) -> BitVec<u8, Lsb0> {
+        let mut out = BitVec::repeat(false, bits.len());
+        for (index, source) in self.permutation(bits.len()).into_iter().enumerate() {
+            out.set(source, bits[index]);
+        }
+        out
+    }
+
+    // input position of every output bit
+    fn permutation(&self, len: usize) -> Vec<usize> {
+        match *self {
+            Interleaver::None => (0..len).collect(),
+            Interleaver::Block { rows, cols } => {
+                let block = rows * cols;
+                let mut order = Vec::with_capacity(len);
+                for start in (0..len).step_by(block) {
+                    let filled = block.min(len - start);
+                    for col in 0..cols {
+                        order.extend(
+                            (0..rows)
+                                .map(|row| row * cols + col)
+                                .filter(|&cell| cell < filled)
+                                .map(|cell| start + cell),
+                        );
+                    }
+                }
+                order
+            }
+            Interleaver::Convolutional { branches, depth } => {
+                // bits past the last whole round of branches pass straight through
+                let wrap = len - len % branches;
+                let mut order: Vec<usize> = (0..len).collect();
+                for source in 0..wrap {
+                    let delay = (source % branches) * depth * branches;
+                    order[(source + delay) % wrap] = source;
+                }
+                order
+            }
+        }
+    }
+}
+
+/// `none`, `block:<rows>x<cols>` or `conv:<branches>x<depth>`
+impl fmt::Display for Interleaver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Interleaver::None => write!(f, "none"),
+            Interleaver::Block { rows, cols } => write!(f, "block:{rows}x{cols}"),
+            Interleaver::Convolutional { branches, depth } => {
+                write!(f, "conv:{branches}x{depth}")
+            }
+        }
+    }
+}
+
+impl FromStr for Interleaver {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, ConfigError> {
+        let invalid = || {
+            ConfigError::InvalidParameter(format!(
+                "interleaver {s} isn't none, block:<rows>x<cols> or conv:<branches>x<depth>"
+            ))
+        };
+        if s == "none" {
+            return Ok(Interleaver::None);
+        }
+        let (kind, shape) = s.split_once(':').ok_or_else(invalid)?;
+        let (a, b) = shape.split_once('x').ok_or_else(invalid)?;
+        let (a, b): (usize, usize) = match (a.parse(), b.parse()) {
+            (Ok(a), Ok(b)) if a > 0 && b > 0 => (a, b),
+            _ => return Err(invalid()),
+        };
+        match kind {
+            "block" => Ok(Interleaver::block(a, b)),
+            "conv" => Ok(Interleaver::convolutional(a, b)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitvec::view::BitView;
+
+    #[test]
+    fn test_round_trip_any_length() {
+        let data = *b"interleaved, then put back in order";
+        for interleaver in [
+            Interleaver::None,
+            Interleaver::block(8, 12),
+            Interleaver::block(5, 7),
+            Interleaver::convolutional(6, 3),
+            Interleaver::convolutional(7, 2),
+        ] {
+            for len in [0, 1, 13, 96, 100, data.len() * 8] {
+                let bits = &data.view_bits::<Lsb0>()[..len];
+                let interleaved = interleaver.interleave(bits);
+                assert_eq!(interleaved.len(), len);
+                assert_eq!(interleaver.deinterleave(&interleaved), bits);
+            }
+            assert_eq!(interleaver.to_string().parse(), Ok(interleaver));
+        }
+        assert!("block:0x4".parse::<Interleaver>().is_err());
+        assert!("zigzag:2x2".parse::<Interleaver>().is_err());
+    }
+
+    #[test]
+    fn test_spreads_burst() {
+        let len = 480;
+        for (interleaver, burst) in [
+            (Interleaver::block(16, 30), 16),
+            (Interleaver::convolutional(8, 4), 8),
+        ] {
+            // wipe out a burst on the air, then see where it lands
+            let mut received = interleaver.interleave(&BitVec::<u8, Lsb0>::repeat(false, len));
+            received[200..200 + burst].fill(true);
+            let errors: Vec<usize> = interleaver.deinterleave(&received).iter_ones().collect();
+            assert_eq!(errors.len(), burst);
+            assert!(errors.windows(2).all(|pair| pair[1] - pair[0] > 8));
+        }
+    }
+}
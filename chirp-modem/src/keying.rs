@@ -0,0 +1,171 @@
+use bitvec::{order::Lsb0, vec::BitVec};
+
+use crate::{
+    Hz, WaveGenerator,
+    config::{ConfigResult, ModemConfig},
+    demodulator::{BitDemodulator, WaveDemodulator},
+    fsk::{FskDemodulator, FskGenerator, FskTones},
+    mfsk::{MfskConfig, MfskDemodulator, MfskGenerator},
+};
+
+/// how bits are keyed onto the ultrasonic carrier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keying {
+    /// on-off style amplitude keying of a single carrier
+    Amplitude(ModemConfig),
+    /// continuous-phase binary frequency keying
+    Frequency(FskTones),
+    /// M-ary frequency keying, several bits per symbol
+    MultiFrequency(MfskConfig),
+}
+
+impl Keying {
+    /// short name, one of `ook`, `fsk` or `mfsk`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Keying::Amplitude(_) => "ook",
+            Keying::Frequency(_) => "fsk",
+            Keying::MultiFrequency(_) => "mfsk",
+        }
+    }
+
+    /// keying with default parameters by short name
+    pub fn from_name(name: &str) -> Option<Keying> {
+        match name.to_ascii_lowercase().as_str() {
+            "ook" => Some(Keying::Amplitude(ModemConfig::default())),
+            "fsk" => Some(Keying::Frequency(FskTones::default())),
+            "mfsk" => Some(Keying::MultiFrequency(MfskConfig::default())),
+            _ => None,
+        }
+    }
+
+    pub fn sample_rate(&self) -> Hz {
+        match self {
+            Keying::Amplitude(config) => config.sample_rate(),
+            Keying::Frequency(tones) => tones.sample_rate,
+            Keying::MultiFrequency(config) => config.sample_rate,
+        }
+    }
+
+    /// the same keying for a device running at `sample_rate`, tones stay put
+    /// and symbols last about as long
+    pub fn with_sample_rate(&self, sample_rate: Hz) -> ConfigResult<Keying> {
+        Ok(match self {
+            Keying::Amplitude(config) => Keying::Amplitude(config.with_sample_rate(sample_rate)?),
+            Keying::Frequency(tones) => Keying::Frequency(tones.with_sample_rate(sample_rate)?),
+            Keying::MultiFrequency(config) => {
+                Keying::MultiFrequency(config.with_sample_rate(sample_rate)?)
+            }
+        })
+    }
+
+    /// samples spent on every symbol
+    pub fn samples_per_symbol(&self) -> usize {
+        match self {
+            Keying::Amplitude(config) => config.samples_per_bit() as usize,
+            Keying::Frequency(tones) => tones.samples_per_bit as usize,
+            Keying::MultiFrequency(config) => config.symbol_samples as usize,
+        }
+    }
+
+    pub fn bits_per_symbol(&self) -> usize {
+        match self {
+            Keying::Amplitude(_) | Keying::Frequency(_) => 1,
+            Keying::MultiFrequency(config) => config.bits_per_symbol(),
+        }
+    }
+
+    pub fn bit_rate(&self) -> f32 {
+        (self.bits_per_symbol() as Hz * self.sample_rate()) as f32
+            / self.samples_per_symbol() as f32
+    }
+}
+
+impl Default for Keying {
+    fn default() -> Self {
+        Keying::Amplitude(ModemConfig::default())
+    }
+}
+
+/// sample iterator for any keying
+pub enum Modulator<'a> {
+    Amplitude(WaveGenerator<'a>),
+    Frequency(FskGenerator<'a>),
+    MultiFrequency(MfskGenerator<'a>),
+}
+
+impl<'a> Modulator<'a> {
+    pub fn new(keying: Keying, data: &'a [u8]) -> Self {
+        match keying {
+            Keying::Amplitude(config) => Modulator::Amplitude(WaveGenerator::new(config, data)),
+            Keying::Frequency(tones) => Modulator::Frequency(FskGenerator::new(tones, data)),
+            Keying::MultiFrequency(config) => {
+                Modulator::MultiFrequency(MfskGenerator::new(config, data))
+            }
+        }
+    }
+}
+
+impl Iterator for Modulator<'_> {
+    type Item = f32;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Modulator::Amplitude(generator) => generator.next(),
+            Modulator::Frequency(generator) => generator.next(),
+            Modulator::MultiFrequency(generator) => generator.next(),
+        }
+    }
+}
+
+/// bit demodulator for any keying
+pub enum Demodulator {
+    Amplitude(WaveDemodulator),
+    Frequency(FskDemodulator),
+    MultiFrequency(MfskDemodulator),
+}
+
+impl Demodulator {
+    pub fn new(keying: Keying) -> Self {
+        match keying {
+            Keying::Amplitude(config) => Demodulator::Amplitude(WaveDemodulator::new(config)),
+            Keying::Frequency(tones) => Demodulator::Frequency(FskDemodulator::new(tones)),
+            Keying::MultiFrequency(config) => {
+                Demodulator::MultiFrequency(MfskDemodulator::new(config))
+            }
+        }
+    }
+}
+
+impl BitDemodulator for Demodulator {
+    fn push_sample(&mut self, sample: f32) -> usize {
+        match self {
+            Demodulator::Amplitude(demod) => demod.push_sample(sample),
+            Demodulator::Frequency(demod) => demod.push_sample(sample),
+            Demodulator::MultiFrequency(demod) => demod.push_sample(sample),
+        }
+    }
+
+    fn reset_window(&mut self) {
+        match self {
+            Demodulator::Amplitude(demod) => demod.reset_window(),
+            Demodulator::Frequency(demod) => demod.reset_window(),
+            Demodulator::MultiFrequency(demod) => demod.reset_window(),
+        }
+    }
+
+    fn bit_buffer(&self) -> &BitVec<u8, Lsb0> {
+        match self {
+            Demodulator::Amplitude(demod) => demod.bit_buffer(),
+            Demodulator::Frequency(demod) => demod.bit_buffer(),
+            Demodulator::MultiFrequency(demod) => demod.bit_buffer(),
+        }
+    }
+
+    fn bit_buffer_mut(&mut self) -> &mut BitVec<u8, Lsb0> {
+        match self {
+            Demodulator::Amplitude(demod) => demod.bit_buffer_mut(),
+            Demodulator::Frequency(demod) => demod.bit_buffer_mut(),
+            Demodulator::MultiFrequency(demod) => demod.bit_buffer_mut(),
+        }
+    }
+}
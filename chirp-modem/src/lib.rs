@@ -1,50 +1,51 @@
+pub mod config;
+pub mod demodulator;
+pub mod detect;
+pub mod fsk;
+pub mod interleave;
+pub mod keying;
+pub mod mfsk;
 pub mod modulator;
 
-use std::f32::consts::TAU;
-use std::sync::LazyLock;
-
 use bitvec::{
     order::Lsb0,
     slice::{BitSlice, Iter as BitIter},
     view::BitView,
 };
 
+use crate::config::ModemConfig;
+
 pub type Hz = u32;
 
-pub const BIT_REPEATS: u8 = 16; // lines up w/ zero crossing of sine wave
-pub const CARRIER_STEPS: u8 = 13; // prime ensures every element using mod
-pub const CARRIER_SAMPLES: u8 = 32; // rational divisor based on prime and sample_rate/carrier freq ratio
 pub const CARRIER_FREQ: Hz = 19_500; // low-end ultrasonic, meets nyquist criteria with sample rate
-pub const SAMPLE_RATE: Hz = 48_000; // average stock sound card sampling rate:w
-
-// look-up table to avoid sine computation in-the-loop
-pub static CARRIER_SIGNAL: LazyLock<Vec<f32>> = LazyLock::new(|| {
-    let mut carrier = Vec::with_capacity(CARRIER_SAMPLES as usize);
-    for i in 0..CARRIER_SAMPLES {
-        let radian = ((i as f32) / (CARRIER_SAMPLES as f32)) * TAU;
-        let scaled = radian.sin() * f32::MAX;
-        carrier.push(scaled);
-    }
-    carrier
-});
+pub const SAMPLE_RATE: Hz = 48_000; // average stock sound card sampling rate
+pub const BIT_RATE: Hz = 3_000; // 16 samples per bit at the stock rate, 6.5 carrier periods
+pub const ONE_GAIN: f32 = 1.0; // full-scale carrier for set bits
+pub const ZERO_GAIN: f32 = 0.1; // attenuated carrier for clear bits
 
 /// zero-copy iterator signal modulator
 pub struct WaveGenerator<'a> {
     one: f32,
     zero: f32,
-    cursor: u8,
-    count: u8,
+    carrier: Vec<f32>, // look-up table to avoid sine computation in-the-loop
+    steps: u32,
+    cursor: u32,
+    samples_per_bit: u16,
+    count: u16,
     hold: bool,
     bits: BitIter<'a, u8, Lsb0>,
 }
 
 impl<'a> WaveGenerator<'a> {
-    pub fn new(data: &'a [u8]) -> Self {
+    pub fn new(config: ModemConfig, data: &'a [u8]) -> Self {
         let bits: &'a BitSlice<u8, Lsb0> = data.view_bits::<Lsb0>();
         Self {
-            one: f32::MAX,         // place-holder, will be dynamic
-            zero: f32::MAX / 10.0, // place-holder, will be dynamic
+            one: ONE_GAIN,   // place-holder, will be dynamic
+            zero: ZERO_GAIN, // place-holder, will be dynamic
+            carrier: config.carrier_table(),
+            steps: config.carrier_steps(),
             cursor: 0,
+            samples_per_bit: config.samples_per_bit(),
             count: 0,
             hold: false,
             bits: bits.iter(),
@@ -58,9 +59,9 @@ impl<'a> Iterator for WaveGenerator<'a> {
         if self.count == 0 {
             self.hold = *self.bits.next()?;
         }
-        let value = CARRIER_SIGNAL[self.cursor as usize];
-        self.count = (self.count + 1) % BIT_REPEATS;
-        self.cursor = (self.cursor + CARRIER_STEPS) % CARRIER_SAMPLES;
+        let value = self.carrier[self.cursor as usize];
+        self.count = (self.count + 1) % self.samples_per_bit;
+        self.cursor = (self.cursor + self.steps) % self.carrier.len() as u32;
         if self.hold {
             Some(self.one * value)
         } else {
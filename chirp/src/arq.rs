@@ -0,0 +1,1232 @@
+//! Reliable delivery on top of frames: numbered data segments, ACK and NACK
+//! segments, retransmission and duplicate suppression.
+//!
+//! Segments travel as frame payloads, the frame header says who sent them
+//! and to whom. Every station numbers its data segments in a sequence space
+//! per destination, receivers keep a window per station and destination they
+//! hear and acknowledge selectively:
+//!
+//! ```text
+//! data | kind | sequence | base | data     |
+//!      | 1    | 2        | 2    | the rest |
+//!
+//! ack  | kind | stream | next | received |
+//!      | 1    | 2      | 2    | 4        |
+//! ```
+//!
+//! `base` is the oldest segment its sender still waits on, so a receiver can
+//! join mid-stream and skips whatever the sender gave up on. An ACK goes
+//! back to the sender alone, `stream` is the destination of the segments it
+//! acknowledges, the receiver itself or `BROADCAST`. `next` is the first
+//! segment not received yet, bit `i` of `received` stands for
+//! `next + 1 + i`. A NACK has the ACK layout and asks for the gaps right
+//! away instead of at their timeout. Broadcast messages count as delivered
+//! once anyone acknowledges them.
+//!
+//! Messages longer than `ArqConfig::fragment_size` are split, one segment
+//! per fragment with its index and count in the frame header. Fragments
+//! are acknowledged and resent like any other segment, so a NACK asks for
+//! exactly the missing ones, and the receiver joins them once all came in.
+//! A message fails as a whole when any of its fragments does, receivers
+//! drop what they have of it, and of anything a sender left unfinished for
+//! `reassembly_timeout`.
+//!
+//! The channel is half-duplex, nobody can answer while the sender still
+//! talks. Timers run from play, not from `poll_transmit`: a caller holding
+//! segments back for a quiet channel reports when one starts with
+//! `Arq::started`. Each segment played pushes the timers of those in flight
+//! back behind its own end, and receivers answer `ack_delay` after the last
+//! segment they heard, a whole burst at once, once their own carrier sense
+//! lets them, within `access_delay`.
+//!
+//! `Arq` does no I/O and reads no clock, callers play what `poll_transmit`
+//! hands out and pass the time, e.g. as samples captured so far.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    time::Duration,
+};
+
+use thiserror::Error;
+
+use crate::{
+    frame::{Fragment, Header, MAX_PAYLOAD},
+    link::LinkConfig,
+    mac::MacConfig,
+    station::{BROADCAST, Station},
+};
+
+/// local handle of a message passed to `Arq::send`, counting up from 0
+pub type MessageId = u64;
+
+/// most segments in flight, one per bit of an ACK's `received` field plus
+/// the one at `next`
+pub const MAX_WINDOW: u16 = 32;
+
+pub const DATA_HEADER_LEN: usize = 5;
+pub const ACK_LEN: usize = 9;
+
+/// most data a segment carries
+pub const MAX_DATA: usize = MAX_PAYLOAD - DATA_HEADER_LEN;
+
+/// most fragments a message is split into, the frame header counts them in
+/// a byte
+pub const MAX_FRAGMENTS: usize = u8::MAX as usize;
+
+const DATA: u8 = 0x01;
+const ACK: u8 = 0x02;
+const NACK: u8 = 0x03;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ArqError {
+    #[error("Segment truncated: needed {needed} bytes, got {available}.")]
+    Truncated { needed: usize, available: usize },
+
+    #[error("Unknown segment kind: {0:#04x}")]
+    UnknownKind(u8),
+
+    #[error("Message too long: {len} bytes, at most {max} fit {MAX_FRAGMENTS} fragments")]
+    MessageTooLong { len: usize, max: usize },
+}
+
+pub type ArqResult<T> = Result<T, ArqError>;
+
+/// one frame payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Data {
+        sequence: u16,
+        base: u16, // oldest segment still awaiting an ACK
+        data: Vec<u8>,
+    },
+    Ack {
+        stream: Station, // destination of the segments acknowledged
+        next: u16,       // everything before was received
+        received: u32,   // bit `i` for `next + 1 + i`
+        nack: bool,      // resend the gaps now
+    },
+}
+
+impl Segment {
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Segment::Data {
+                sequence,
+                base,
+                data,
+            } => {
+                let mut bytes = Vec::with_capacity(DATA_HEADER_LEN + data.len());
+                bytes.push(DATA);
+                bytes.extend_from_slice(&sequence.to_be_bytes());
+                bytes.extend_from_slice(&base.to_be_bytes());
+                bytes.extend_from_slice(data);
+                bytes
+            }
+            Segment::Ack {
+                stream,
+                next,
+                received,
+                nack,
+            } => {
+                let mut bytes = Vec::with_capacity(ACK_LEN);
+                bytes.push(if *nack { NACK } else { ACK });
+                bytes.extend_from_slice(&stream.to_be_bytes());
+                bytes.extend_from_slice(&next.to_be_bytes());
+                bytes.extend_from_slice(&received.to_be_bytes());
+                bytes
+            }
+        }
+    }
+
+    pub fn decode(payload: &[u8]) -> ArqResult<Self> {
+        let &kind = payload.first().ok_or(ArqError::Truncated {
+            needed: 1,
+            available: 0,
+        })?;
+        let needed = match kind {
+            DATA => DATA_HEADER_LEN,
+            ACK | NACK => ACK_LEN,
+            _ => return Err(ArqError::UnknownKind(kind)),
+        };
+        if payload.len() < needed {
+            return Err(ArqError::Truncated {
+                needed,
+                available: payload.len(),
+            });
+        }
+        let u16_at = |i: usize| u16::from_be_bytes([payload[i], payload[i + 1]]);
+        Ok(match kind {
+            DATA => Segment::Data {
+                sequence: u16_at(1),
+                base: u16_at(3),
+                data: payload[DATA_HEADER_LEN..].to_vec(),
+            },
+            _ => Segment::Ack {
+                stream: u16_at(1),
+                next: u16_at(3),
+                received: u32::from_be_bytes([payload[5], payload[6], payload[7], payload[8]]),
+                nack: kind == NACK,
+            },
+        })
+    }
+}
+
+/// how hard to try
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArqConfig {
+    pub window: u16,                  // segments in flight, 1 is stop-and-wait
+    pub max_attempts: u32,            // transmissions before a segment fails
+    pub ack_delay: Duration,          // quiet after the last segment before answering
+    pub turnaround: Duration,         // capture muting, device latency and margin
+    pub access_delay: Duration,       // longest the answer waits for a quiet channel
+    pub fragment_size: usize,         // data bytes per segment, at most `MAX_DATA`
+    pub reassembly_timeout: Duration, // silence after which incomplete messages are dropped
+}
+
+impl ArqConfig {
+    /// one segment at a time, each acknowledged before the next goes out
+    pub fn stop_and_wait() -> Self {
+        Self::sliding_window(1)
+    }
+
+    /// up to `window` segments in flight, at most `MAX_WINDOW`
+    pub fn sliding_window(window: u16) -> Self {
+        Self {
+            window: window.clamp(1, MAX_WINDOW),
+            ..Self::default()
+        }
+    }
+
+    /// longest message `Arq::send` takes
+    pub fn max_message(&self) -> usize {
+        MAX_FRAGMENTS * self.fragment_len()
+    }
+
+    fn fragment_len(&self) -> usize {
+        self.fragment_size.clamp(1, MAX_DATA)
+    }
+}
+
+impl Default for ArqConfig {
+    fn default() -> Self {
+        Self {
+            window: 4,
+            max_attempts: 4,
+            ack_delay: Duration::from_millis(250),
+            turnaround: Duration::from_millis(400),
+            access_delay: MacConfig::default().max_wait(),
+            fragment_size: 128,
+            reassembly_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// where a message stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    /// first fragment handed out for the first time
+    Sent,
+    /// every fragment, `by` answered for the last of them
+    Acknowledged { by: Station },
+    /// `max_attempts` of a fragment went unacknowledged, the rest isn't sent
+    Failed,
+}
+
+/// reported through the callback of `Arq::with_delivery_callback`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delivery {
+    pub id: MessageId,
+    pub status: DeliveryStatus,
+    pub attempts: u32, // transmissions so far of its most repeated fragment
+}
+
+/// message received from another station, in the order it was sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Received {
+    pub from: Station,
+    pub to: Station, // us or `BROADCAST`
+    pub data: Vec<u8>,
+}
+
+/// payload for `poll_transmit` to put in a frame for `to`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outgoing {
+    pub to: Station,
+    pub fragment: Fragment, // for the frame header
+    pub payload: Vec<u8>,
+}
+
+/// sending and receiving end of the reliability layer for one station
+pub struct Arq {
+    station: Station,
+    link: LinkConfig,
+    config: ArqConfig,
+    next_id: MessageId,
+    first_sequence: u16,                       // of every new stream
+    streams: BTreeMap<Station, Stream>,        // by destination
+    progress: BTreeMap<MessageId, u32>,        // attempts of unfinished messages
+    peers: BTreeMap<(Station, Station), Peer>, // by source and destination
+    unplayed: Option<(Station, u16, usize)>,   // last data handed out: to, sequence, length
+    on_delivery: Box<dyn FnMut(Delivery) + Send>,
+}
+
+// messages to one destination
+struct Stream {
+    next_sequence: u16,
+    waiting: VecDeque<(MessageId, Fragment, Vec<u8>)>, // not sent yet
+    in_flight: VecDeque<InFlight>,                     // oldest first
+}
+
+// sent, not acknowledged yet
+struct InFlight {
+    id: MessageId,
+    fragment: Fragment,
+    sequence: u16,
+    data: Vec<u8>,
+    attempts: u32,
+    deadline: Duration, // resend or give up from here on
+}
+
+// receiving window for one station and destination
+struct Peer {
+    next: u16,                                   // first sequence not delivered yet
+    buffered: HashMap<u16, (Fragment, Vec<u8>)>, // received past a gap
+    partial: Option<(u16, Fragment, Vec<u8>)>,   // message so far, up to this sequence
+    answer: Option<(bool, Duration)>,            // nack or ack, due at
+    heard: Duration,                             // last segment
+}
+
+impl Peer {
+    fn received(&self) -> u32 {
+        (0..32)
+            .filter(|&i| {
+                let sequence = self.next.wrapping_add(1 + i as u16);
+                self.buffered.contains_key(&sequence)
+            })
+            .fold(0, |bits, i| bits | (1 << i))
+    }
+
+    fn deliver(&mut self, from: Station, to: Station, delivered: &mut Vec<Received>) {
+        while let Some((fragment, data)) = self.buffered.remove(&self.next) {
+            if let Some(data) = self.reassemble(self.next, fragment, data) {
+                delivered.push(Received { from, to, data });
+            }
+            self.next = self.next.wrapping_add(1);
+        }
+    }
+
+    // add a segment taken in order, returns the message it completes
+    //
+    // Fragments only join the message when nothing in between is missing,
+    // those after a gap the sender gave up on are dropped.
+    fn reassemble(&mut self, sequence: u16, fragment: Fragment, data: Vec<u8>) -> Option<Vec<u8>> {
+        let follows = self.partial.as_ref().is_some_and(|(last, partial, _)| {
+            sequence == last.wrapping_add(1)
+                && fragment.index == partial.index + 1
+                && fragment.count == partial.count
+        });
+        if fragment.index == 0 {
+            self.partial = Some((sequence, fragment, data));
+        } else if follows {
+            let (last, partial, bytes) = self.partial.as_mut()?;
+            *last = sequence;
+            *partial = fragment;
+            bytes.extend(data);
+        } else {
+            self.partial = None;
+            return None;
+        }
+        if !fragment.is_last() {
+            return None;
+        }
+        self.partial.take().map(|(_, _, bytes)| bytes)
+    }
+}
+
+// how far `sequence` is past `from`, negative if behind
+fn offset(sequence: u16, from: u16) -> i16 {
+    sequence.wrapping_sub(from) as i16
+}
+
+impl Arq {
+    /// timers are sized for frames of `link`
+    pub fn new(station: Station, link: LinkConfig, config: ArqConfig) -> Self {
+        Self {
+            station,
+            link,
+            config,
+            next_id: 0,
+            first_sequence: 0,
+            streams: BTreeMap::new(),
+            progress: BTreeMap::new(),
+            peers: BTreeMap::new(),
+            unplayed: None,
+            on_delivery: Box::new(|_| {}),
+        }
+    }
+
+    /// called on every change of a message's `DeliveryStatus`
+    pub fn with_delivery_callback(
+        mut self,
+        callback: impl FnMut(Delivery) + Send + 'static,
+    ) -> Self {
+        self.on_delivery = Box::new(callback);
+        self
+    }
+
+    /// start numbering at `sequence`, e.g. a random one so a restart isn't
+    /// taken for duplicates
+    pub fn with_sequence(mut self, sequence: u16) -> Self {
+        self.first_sequence = sequence;
+        self
+    }
+
+    pub fn station(&self) -> Station {
+        self.station
+    }
+
+    pub fn config(&self) -> &ArqConfig {
+        &self.config
+    }
+
+    /// queue a message for `to`, a station or `BROADCAST`, its id comes back
+    /// in every `Delivery`
+    ///
+    /// Messages longer than `ArqConfig::fragment_size` go out in fragments,
+    /// up to `ArqConfig::max_message` bytes.
+    pub fn send(&mut self, to: Station, data: Vec<u8>) -> ArqResult<MessageId> {
+        let max = self.config.max_message();
+        if data.len() > max {
+            return Err(ArqError::MessageTooLong {
+                len: data.len(),
+                max,
+            });
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        let first_sequence = self.first_sequence;
+        let stream = self.streams.entry(to).or_insert_with(|| Stream {
+            next_sequence: first_sequence,
+            waiting: VecDeque::new(),
+            in_flight: VecDeque::new(),
+        });
+        let chunks: Vec<&[u8]> = match data.len() {
+            0 => vec![&[]],
+            _ => data.chunks(self.config.fragment_len()).collect(),
+        };
+        let count = chunks.len() as u8;
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            let fragment = Fragment {
+                index: index as u8,
+                count,
+            };
+            stream.waiting.push_back((id, fragment, chunk.to_vec()));
+        }
+        self.progress.insert(id, 0);
+        Ok(id)
+    }
+
+    /// messages not acknowledged or failed yet
+    pub fn queued(&self) -> usize {
+        self.progress.len()
+    }
+
+    /// nothing left to send or answer
+    pub fn is_idle(&self) -> bool {
+        self.queued() == 0 && self.peers.values().all(|peer| peer.answer.is_none())
+    }
+
+    /// take a received frame, returns messages now complete
+    ///
+    /// Our own segments, heard back through the microphone, and those for
+    /// other stations are ignored.
+    pub fn receive(
+        &mut self,
+        header: &Header,
+        payload: &[u8],
+        now: Duration,
+    ) -> ArqResult<Vec<Received>> {
+        let (from, to) = (header.source, header.destination);
+        if from == self.station || !header.is_for(self.station) {
+            return Ok(Vec::new());
+        }
+        match Segment::decode(payload)? {
+            Segment::Data {
+                sequence,
+                base,
+                data,
+            } => Ok(self.receive_data(header, sequence, base, data, now)),
+            Segment::Ack {
+                stream,
+                next,
+                received,
+                nack,
+            } => {
+                if to == self.station {
+                    self.acknowledged(from, stream, next, received, nack, now);
+                }
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// next payload to play: an answer that's due, a retransmission or a new
+    /// segment, in that order
+    pub fn poll_transmit(&mut self, now: Duration) -> Option<Outgoing> {
+        // senders gone quiet won't complete what they started
+        for peer in self.peers.values_mut() {
+            if now.saturating_sub(peer.heard) >= self.config.reassembly_timeout {
+                peer.partial = None;
+                peer.buffered.clear();
+            }
+        }
+
+        // answers first, someone is waiting on them
+        let due = self
+            .peers
+            .iter_mut()
+            .find(|(_, peer)| peer.answer.is_some_and(|(_, due)| due <= now));
+        if let Some((&(from, stream), peer)) = due {
+            let (nack, _) = peer.answer.take()?;
+            self.unplayed = None;
+            let segment = Segment::Ack {
+                stream,
+                next: peer.next,
+                received: peer.received(),
+                nack,
+            };
+            return Some(Outgoing {
+                to: from,
+                fragment: Fragment::WHOLE,
+                payload: segment.encode(),
+            });
+        }
+
+        while let Some((to, index)) = self.streams.iter().find_map(|(&to, stream)| {
+            let index = stream.in_flight.iter().position(|s| s.deadline <= now)?;
+            Some((to, index))
+        }) {
+            let stream = self.streams.get_mut(&to)?;
+            if stream.in_flight[index].attempts >= self.config.max_attempts {
+                // the whole message fails with it
+                let id = stream.in_flight[index].id;
+                stream.in_flight.retain(|segment| segment.id != id);
+                stream.waiting.retain(|&(other, ..)| other != id);
+                (self.on_delivery)(Delivery {
+                    id,
+                    status: DeliveryStatus::Failed,
+                    attempts: self.progress.remove(&id).unwrap_or_default(),
+                });
+                continue;
+            }
+            return self.hand_out(to, index, now);
+        }
+
+        // the oldest message of any stream with room in its window
+        let window = self.config.window as usize;
+        let (&to, stream) = self
+            .streams
+            .iter_mut()
+            .filter(|(_, stream)| stream.in_flight.len() < window)
+            .filter_map(|(to, stream)| Some((stream.waiting.front()?.0, to, stream)))
+            .min_by_key(|&(id, ..)| id)
+            .map(|(_, to, stream)| (to, stream))?;
+        let (id, fragment, data) = stream.waiting.pop_front()?;
+        stream.in_flight.push_back(InFlight {
+            id,
+            fragment,
+            sequence: stream.next_sequence,
+            data,
+            attempts: 0,
+            deadline: Duration::ZERO,
+        });
+        stream.next_sequence = stream.next_sequence.wrapping_add(1);
+        let index = stream.in_flight.len() - 1;
+        self.hand_out(to, index, now)
+    }
+
+    /// what `poll_transmit` handed out last starts playing at `now`
+    ///
+    /// Its timers are restarted from here, they'd run out early for a frame
+    /// that waited for the channel. Nothing to do for answers or for a
+    /// segment acknowledged in the meantime.
+    pub fn started(&mut self, now: Duration) {
+        let Some((to, sequence, len)) = self.unplayed.take() else {
+            return;
+        };
+        let Some(stream) = self.streams.get(&to) else {
+            return;
+        };
+        if let Some(index) = stream.in_flight.iter().position(|s| s.sequence == sequence) {
+            self.restart_timers(to, index, len, now);
+        }
+    }
+
+    // encode `in_flight[index]` of the stream to `to`, its timers run from
+    // `now` until `started` says otherwise
+    fn hand_out(&mut self, to: Station, index: usize, now: Duration) -> Option<Outgoing> {
+        let stream = self.streams.get_mut(&to)?;
+        let base = stream.in_flight.front()?.sequence;
+        let segment = stream.in_flight.get_mut(index)?;
+        segment.attempts += 1;
+        let (id, fragment, attempts) = (segment.id, segment.fragment, segment.attempts);
+        let sequence = segment.sequence;
+        let payload = Segment::Data {
+            sequence,
+            base,
+            data: segment.data.clone(),
+        }
+        .encode();
+
+        if let Some(most) = self.progress.get_mut(&id) {
+            if *most == 0 {
+                (self.on_delivery)(Delivery {
+                    id,
+                    status: DeliveryStatus::Sent,
+                    attempts,
+                });
+            }
+            *most = (*most).max(attempts);
+        }
+
+        self.unplayed = Some((to, sequence, payload.len()));
+        self.restart_timers(to, index, payload.len(), now);
+        Some(Outgoing {
+            to,
+            fragment,
+            payload,
+        })
+    }
+
+    // `in_flight[index]` of the stream to `to`, `len` bytes, plays from
+    // `now`: nothing can be acknowledged before it finished
+    fn restart_timers(&mut self, to: Station, index: usize, len: usize, now: Duration) {
+        let end = now + self.airtime(len);
+        let answer = self.config.ack_delay
+            + self.config.access_delay
+            + self.airtime(ACK_LEN)
+            + 2 * self.config.turnaround;
+        for (&destination, stream) in &mut self.streams {
+            for (i, segment) in stream.in_flight.iter_mut().enumerate() {
+                let this = destination == to && i == index;
+                // later attempts wait longer, the channel may just be busy
+                let deadline = end + answer * segment.attempts.max(1);
+                // those already due stay due
+                if this {
+                    segment.deadline = deadline;
+                } else if segment.deadline > now {
+                    segment.deadline = segment.deadline.max(deadline);
+                }
+            }
+        }
+    }
+
+    fn airtime(&self, payload_len: usize) -> Duration {
+        let samples = self.link.frame_samples(payload_len);
+        Duration::from_secs_f64(samples as f64 / self.link.keying.sample_rate() as f64)
+    }
+
+    fn receive_data(
+        &mut self,
+        header: &Header,
+        sequence: u16,
+        base: u16,
+        data: Vec<u8>,
+        now: Duration,
+    ) -> Vec<Received> {
+        let (from, to, fragment) = (header.source, header.destination, header.fragment);
+        let peer = self.peers.entry((from, to)).or_insert_with(|| Peer {
+            next: base,
+            buffered: HashMap::new(),
+            partial: None,
+            answer: None,
+            heard: now,
+        });
+        peer.heard = now;
+        let mut delivered = Vec::new();
+
+        let moved = offset(base, peer.next);
+        if moved > 0 {
+            // the sender gave up on everything before `base`, pass on what
+            // we have of it and move on
+            let mut skipped: Vec<(u16, (Fragment, Vec<u8>))> = peer
+                .buffered
+                .extract_if(|&s, _| offset(s, base) < 0)
+                .collect();
+            skipped.sort_by_key(|&(s, _)| offset(s, peer.next));
+            // the message in progress lost a fragment
+            peer.partial = None;
+            for (sequence, (fragment, data)) in skipped {
+                if let Some(data) = peer.reassemble(sequence, fragment, data) {
+                    delivered.push(Received { from, to, data });
+                }
+            }
+            peer.partial = peer
+                .partial
+                .take()
+                .filter(|&(last, ..)| last.wrapping_add(1) == base);
+            peer.next = base;
+        } else if moved < -(MAX_WINDOW as i16) {
+            // further back than any segment still in flight, the sender
+            // started over
+            peer.buffered.clear();
+            peer.partial = None;
+            peer.next = base;
+        }
+
+        let ahead = offset(sequence, peer.next);
+        if ahead >= MAX_WINDOW as i16 {
+            return delivered;
+        }
+        // anything behind `next` is a duplicate, its ACK got lost
+        if ahead >= 0 {
+            peer.buffered.entry(sequence).or_insert((fragment, data));
+            peer.deliver(from, to, &mut delivered);
+        }
+        let nack = !peer.buffered.is_empty();
+        peer.answer = Some((nack, now + self.config.ack_delay));
+        delivered
+    }
+
+    fn acknowledged(
+        &mut self,
+        by: Station,
+        stream: Station,
+        next: u16,
+        received: u32,
+        nack: bool,
+        now: Duration,
+    ) {
+        // only the station itself answers for its stream
+        if stream != by && stream != BROADCAST {
+            return;
+        }
+        let Some(stream) = self.streams.get_mut(&stream) else {
+            return;
+        };
+        let mut acked = Vec::new();
+        stream.in_flight.retain(|segment| {
+            let ahead = offset(segment.sequence, next);
+            let done =
+                ahead < 0 || ((1..=32).contains(&ahead) && received & (1 << (ahead - 1)) != 0);
+            if done {
+                acked.push(segment.id);
+            }
+            !done
+        });
+        acked.dedup();
+        for id in acked {
+            let pending = stream.in_flight.iter().any(|segment| segment.id == id)
+                || stream.waiting.iter().any(|&(other, ..)| other == id);
+            if pending {
+                continue;
+            }
+            if let Some(attempts) = self.progress.remove(&id) {
+                (self.on_delivery)(Delivery {
+                    id,
+                    status: DeliveryStatus::Acknowledged { by },
+                    attempts,
+                });
+            }
+        }
+        if nack {
+            // the gaps below the newest segment received
+            let newest = 32 - received.leading_zeros() as i16;
+            for segment in &mut stream.in_flight {
+                if offset(segment.sequence, next) < newest {
+                    segment.deadline = now;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        f32::consts::TAU,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use crate::{
+        frame::{Coding, Flags},
+        mac::{Mac, tones},
+        sim::XorShift,
+    };
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn station(id: Station, config: ArqConfig) -> (Arq, Arc<Mutex<Vec<Delivery>>>) {
+        let deliveries = Arc::new(Mutex::new(Vec::new()));
+        let log = deliveries.clone();
+        let arq = Arq::new(id, LinkConfig::default(), config)
+            .with_delivery_callback(move |delivery| log.lock().unwrap().push(delivery));
+        (arq, deliveries)
+    }
+
+    // everything `from` wants to send at `now`
+    fn drain(from: &mut Arq, now: Duration) -> Vec<Outgoing> {
+        std::iter::from_fn(|| from.poll_transmit(now)).collect()
+    }
+
+    // `to` hears what `from` sent
+    fn hear(to: &mut Arq, from: Station, sent: &Outgoing, now: Duration) -> Vec<Received> {
+        let header = Header {
+            flags: Flags::EMPTY,
+            coding: Coding::NONE,
+            source: from,
+            destination: sent.to,
+            sequence: 0,
+            length: sent.payload.len() as u16,
+            fragment: sent.fragment,
+        };
+        to.receive(&header, &sent.payload, now).unwrap()
+    }
+
+    fn texts(received: &[Received]) -> Vec<&[u8]> {
+        received.iter().map(|r| &r.data[..]).collect()
+    }
+
+    #[test]
+    fn test_segment_round_trip() {
+        for segment in [
+            Segment::Data {
+                sequence: 65535,
+                base: 65530,
+                data: b"hello".to_vec(),
+            },
+            Segment::Ack {
+                stream: BROADCAST,
+                next: 7,
+                received: 0x8000_0001,
+                nack: true,
+            },
+        ] {
+            assert_eq!(Segment::decode(&segment.encode()), Ok(segment));
+        }
+        assert_eq!(Segment::decode(&[0x7f]), Err(ArqError::UnknownKind(0x7f)));
+        assert_eq!(
+            Segment::decode(&[DATA, 0, 1]),
+            Err(ArqError::Truncated {
+                needed: DATA_HEADER_LEN,
+                available: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_stop_and_wait_retransmits_lost_frame() {
+        let (mut a, deliveries) = station(1, ArqConfig::stop_and_wait());
+        let (mut b, _) = station(2, ArqConfig::stop_and_wait());
+        a.send(2, b"first".to_vec()).unwrap();
+        a.send(2, b"second".to_vec()).unwrap();
+
+        // only one in flight, and it's lost
+        let lost = drain(&mut a, Duration::ZERO);
+        assert_eq!(lost.len(), 1);
+        assert!(drain(&mut a, SECOND).is_empty());
+
+        let now = 10 * SECOND;
+        let [again] = &drain(&mut a, now)[..] else {
+            panic!("expected one retransmission")
+        };
+        assert_eq!(again, &lost[0]);
+        assert_eq!(texts(&hear(&mut b, 1, again, now)), [b"first"]);
+        // a duplicate is acknowledged but not delivered twice
+        assert!(hear(&mut b, 1, again, now).is_empty());
+
+        let [ack] = &drain(&mut b, now + SECOND)[..] else {
+            panic!("expected one ack")
+        };
+        assert_eq!(ack.to, 1);
+        hear(&mut a, 2, ack, now + SECOND);
+        assert_eq!(
+            deliveries.lock().unwrap()[..],
+            [
+                Delivery {
+                    id: 0,
+                    status: DeliveryStatus::Sent,
+                    attempts: 1
+                },
+                Delivery {
+                    id: 0,
+                    status: DeliveryStatus::Acknowledged { by: 2 },
+                    attempts: 2
+                },
+            ]
+        );
+        assert_eq!(drain(&mut a, now + SECOND).len(), 1);
+    }
+
+    #[test]
+    fn test_sliding_window_nack_fills_gap_in_order() {
+        let (mut a, deliveries) = station(1, ArqConfig::sliding_window(4));
+        let (mut b, _) = station(2, ArqConfig::default());
+        for text in ["zero", "one", "two", "three", "four"] {
+            a.send(2, text.as_bytes().to_vec()).unwrap();
+        }
+        let burst = drain(&mut a, Duration::ZERO);
+        assert_eq!(burst.len(), 4);
+
+        // the second one is lost, the rest waits for it
+        let mut received = Vec::new();
+        for sent in [&burst[0], &burst[2], &burst[3]] {
+            received.extend(hear(&mut b, 1, sent, Duration::ZERO));
+        }
+        assert_eq!(texts(&received), [b"zero"]);
+        assert!(
+            drain(&mut b, Duration::ZERO).is_empty(),
+            "answer is held back"
+        );
+
+        let now = SECOND;
+        let [nack] = &drain(&mut b, now)[..] else {
+            panic!("expected one nack")
+        };
+        assert!(matches!(
+            Segment::decode(&nack.payload),
+            Ok(Segment::Ack {
+                stream: 2,
+                nack: true,
+                next: 1,
+                received: 0b11,
+            })
+        ));
+        hear(&mut a, 2, nack, now);
+        assert_eq!(a.queued(), 2);
+
+        // the gap goes out at once, then the fifth fits the window
+        let resent = drain(&mut a, now);
+        assert_eq!(resent.len(), 2);
+        assert!(matches!(
+            Segment::decode(&resent[0].payload),
+            Ok(Segment::Data {
+                sequence: 1,
+                base: 1,
+                ..
+            })
+        ));
+        for sent in &resent {
+            received.extend(hear(&mut b, 1, sent, now));
+        }
+        assert_eq!(
+            texts(&received),
+            [&b"zero"[..], b"one", b"two", b"three", b"four"]
+        );
+
+        for ack in drain(&mut b, 2 * SECOND) {
+            hear(&mut a, 2, &ack, 2 * SECOND);
+        }
+        assert!(a.is_idle());
+        let acked = deliveries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|d| d.status == DeliveryStatus::Acknowledged { by: 2 })
+            .count();
+        assert_eq!(acked, 5);
+    }
+
+    #[test]
+    fn test_gives_up_and_receiver_moves_on() {
+        let config = ArqConfig {
+            max_attempts: 2,
+            ..ArqConfig::stop_and_wait()
+        };
+        let (mut a, deliveries) = station(1, config);
+        let (mut b, _) = station(2, config);
+        a.send(2, b"first".to_vec()).unwrap();
+        for sent in drain(&mut a, Duration::ZERO) {
+            hear(&mut b, 1, &sent, Duration::ZERO);
+        }
+        for ack in drain(&mut b, SECOND) {
+            hear(&mut a, 2, &ack, SECOND);
+        }
+
+        // every attempt at the second is lost, the third goes out instead
+        a.send(2, b"never heard".to_vec()).unwrap();
+        a.send(2, b"third".to_vec()).unwrap();
+        let mut now = SECOND;
+        let mut sent = Vec::new();
+        while a.queued() == 2 {
+            sent = drain(&mut a, now);
+            now += SECOND;
+        }
+        assert!(deliveries.lock().unwrap().contains(&Delivery {
+            id: 1,
+            status: DeliveryStatus::Failed,
+            attempts: 2
+        }));
+        let [third] = &sent[..] else {
+            panic!("expected the third message")
+        };
+        assert_eq!(texts(&hear(&mut b, 1, third, now)), [b"third"]);
+
+        // after a restart numbering from elsewhere nothing is taken for a
+        // duplicate
+        let (a, _) = station(1, config);
+        let mut a = a.with_sequence(60_000);
+        a.send(2, b"again".to_vec()).unwrap();
+        let [fresh] = &drain(&mut a, now)[..] else {
+            panic!("expected one segment")
+        };
+        assert_eq!(texts(&hear(&mut b, 1, fresh, now)), [b"again"]);
+    }
+
+    #[test]
+    fn test_unicast_and_broadcast_streams() {
+        let (mut a, deliveries) = station(1, ArqConfig::default());
+        let (mut b, _) = station(2, ArqConfig::default());
+        let (mut c, _) = station(3, ArqConfig::default());
+        let direct = a.send(2, b"for b".to_vec()).unwrap();
+        let everyone = a.send(BROADCAST, b"for all".to_vec()).unwrap();
+        let sent = drain(&mut a, Duration::ZERO);
+        assert_eq!(
+            sent.iter().map(|s| s.to).collect::<Vec<_>>(),
+            [2, BROADCAST]
+        );
+
+        let mut at_b = Vec::new();
+        let mut at_c = Vec::new();
+        for s in &sent {
+            at_b.extend(hear(&mut b, 1, s, Duration::ZERO));
+            at_c.extend(hear(&mut c, 1, s, Duration::ZERO));
+            // our own echo
+            assert!(hear(&mut a, 1, s, Duration::ZERO).is_empty());
+        }
+        assert_eq!(texts(&at_b), [&b"for b"[..], b"for all"]);
+        assert_eq!(at_b[0].to, 2);
+        assert_eq!(texts(&at_c), [b"for all"]);
+        assert_eq!(at_c[0].to, BROADCAST);
+
+        // c can't acknowledge what was meant for b, b's answers are for a only
+        let forged = Outgoing {
+            to: 1,
+            fragment: Fragment::WHOLE,
+            payload: Segment::Ack {
+                stream: 2,
+                next: 1,
+                received: 0,
+                nack: false,
+            }
+            .encode(),
+        };
+        hear(&mut a, 3, &forged, SECOND);
+        for ack in drain(&mut b, SECOND) {
+            assert!(hear(&mut c, 2, &ack, SECOND).is_empty());
+            hear(&mut a, 2, &ack, SECOND);
+        }
+        assert!(a.is_idle());
+        let deliveries = deliveries.lock().unwrap();
+        for id in [direct, everyone] {
+            assert!(deliveries.contains(&Delivery {
+                id,
+                status: DeliveryStatus::Acknowledged { by: 2 },
+                attempts: 1
+            }));
+        }
+    }
+
+    #[test]
+    fn test_fragments_reassemble_out_of_order() {
+        let config = ArqConfig {
+            fragment_size: 16,
+            ..ArqConfig::sliding_window(8)
+        };
+        let (mut a, deliveries) = station(1, config);
+        let (mut b, _) = station(2, config);
+        let long: Vec<u8> = (0..100).collect();
+        let id = a.send(2, long.clone()).unwrap();
+        assert_eq!(
+            a.send(2, vec![0; config.max_message() + 1]),
+            Err(ArqError::MessageTooLong {
+                len: 255 * 16 + 1,
+                max: 255 * 16
+            })
+        );
+
+        let sent = drain(&mut a, Duration::ZERO);
+        assert_eq!(sent.len(), 7);
+        assert!(sent.iter().all(|s| s.fragment.count == 7));
+        // backwards, and the fourth is lost
+        for (i, s) in sent.iter().enumerate().rev() {
+            if i != 3 {
+                assert!(hear(&mut b, 1, s, Duration::ZERO).is_empty());
+            }
+        }
+        let [nack] = &drain(&mut b, SECOND)[..] else {
+            panic!("expected one nack")
+        };
+        hear(&mut a, 2, nack, SECOND);
+        let [resent] = &drain(&mut a, SECOND)[..] else {
+            panic!("expected only the missing fragment")
+        };
+        assert_eq!(resent.fragment, Fragment { index: 3, count: 7 });
+        let received = hear(&mut b, 1, resent, SECOND);
+        assert_eq!(texts(&received), [&long[..]]);
+
+        for ack in drain(&mut b, 2 * SECOND) {
+            hear(&mut a, 2, &ack, 2 * SECOND);
+        }
+        assert!(a.is_idle());
+        assert_eq!(
+            deliveries.lock().unwrap()[..],
+            [
+                Delivery {
+                    id,
+                    status: DeliveryStatus::Sent,
+                    attempts: 1
+                },
+                Delivery {
+                    id,
+                    status: DeliveryStatus::Acknowledged { by: 2 },
+                    attempts: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_incomplete_messages_are_dropped() {
+        let config = ArqConfig {
+            fragment_size: 4,
+            max_attempts: 1,
+            ..ArqConfig::sliding_window(8)
+        };
+        let (mut a, deliveries) = station(1, config);
+        let (mut b, _) = station(2, config);
+        a.send(2, b"lost in the middle".to_vec()).unwrap();
+        a.send(2, b"short".to_vec()).unwrap();
+        let sent = drain(&mut a, Duration::ZERO);
+        assert_eq!(sent.len(), 7);
+        // the long one only gets its first fragment through
+        hear(&mut b, 1, &sent[0], Duration::ZERO);
+        for s in &sent[5..] {
+            assert!(hear(&mut b, 1, s, Duration::ZERO).is_empty());
+        }
+        for ack in drain(&mut b, SECOND) {
+            hear(&mut a, 2, &ack, SECOND);
+        }
+        assert!(drain(&mut a, 10 * SECOND).is_empty());
+        assert!(deliveries.lock().unwrap().contains(&Delivery {
+            id: 0,
+            status: DeliveryStatus::Failed,
+            attempts: 1
+        }));
+        assert!(deliveries.lock().unwrap().contains(&Delivery {
+            id: 1,
+            status: DeliveryStatus::Acknowledged { by: 2 },
+            attempts: 1
+        }));
+
+        // the next one moves the receiver past the failed fragments
+        a.send(2, b"after".to_vec()).unwrap();
+        let mut received = Vec::new();
+        for s in drain(&mut a, 10 * SECOND) {
+            received.extend(hear(&mut b, 1, &s, 10 * SECOND));
+        }
+        assert_eq!(texts(&received), [&b"short"[..], b"after"]);
+
+        // a sender falling silent mid-message leaves nothing behind
+        let (mut c, _) = station(3, config);
+        c.send(2, b"never finished".to_vec()).unwrap();
+        let sent = drain(&mut c, 20 * SECOND);
+        hear(&mut b, 3, &sent[0], 20 * SECOND);
+        hear(&mut b, 3, &sent[2], 20 * SECOND);
+        drain(&mut b, 20 * SECOND + config.reassembly_timeout);
+        assert!(
+            b.peers
+                .values()
+                .all(|peer| peer.partial.is_none() && peer.buffered.is_empty())
+        );
+    }
+
+    #[test]
+    fn test_timers_run_from_play_behind_mac() {
+        let link = LinkConfig::default();
+        let sample_rate = link.keying.sample_rate() as f32;
+        let freq = tones(link.keying)[0] as f32;
+        // stands in for a frame on the air, carrier sense only hears tones
+        let tone = |duration: Duration| -> Vec<f32> {
+            (0..(duration.as_secs_f32() * sample_rate) as usize)
+                .map(|n| 0.3 * (TAU * freq * n as f32 / sample_rate).sin())
+                .collect()
+        };
+        let block = (0.005 * sample_rate) as usize;
+        let step = Duration::from_secs_f32(block as f32 / sample_rate);
+        let mut rng = XorShift::new(1);
+        let mut noise =
+            move || -> Vec<f32> { (0..block).map(|_| 0.001 * rng.gaussian()).collect() };
+
+        let (a, deliveries) = station(1, ArqConfig::stop_and_wait());
+        let (b, _) = station(2, ArqConfig::stop_and_wait());
+        let mut arqs = [a, b];
+        let mut macs = [1, 2].map(|seed| Mac::new(link.keying, MacConfig::default(), seed));
+        for mac in &mut macs {
+            for _ in 0..40 {
+                mac.push(&noise(), false);
+            }
+        }
+        let mut frames: [Option<Outgoing>; 2] = [None, None]; // handed out, then played
+        let mut air: [Vec<f32>; 2] = [Vec::new(), Vec::new()]; // left to play
+
+        // someone else holds the channel for longer than an answer may take
+        let mut busy = tone(5 * SECOND);
+        arqs[0].send(2, b"held back".to_vec()).unwrap();
+        let mut received = Vec::new();
+        let mut now = Duration::ZERO;
+        while !arqs[0].is_idle() {
+            assert!(now < 30 * SECOND, "never acknowledged");
+            let mut channel = noise();
+            let playing = [!air[0].is_empty(), !air[1].is_empty()];
+            for source in std::iter::once(&mut busy).chain(&mut air) {
+                let taken = source.len().min(block);
+                for (sample, played) in channel.iter_mut().zip(source.drain(..taken)) {
+                    *sample += played;
+                }
+            }
+            now += step;
+
+            for i in 0..2 {
+                if playing[i] {
+                    if air[i].is_empty() {
+                        let out = frames[i].take().unwrap();
+                        received.extend(hear(&mut arqs[1 - i], i as Station + 1, &out, now));
+                    }
+                    continue;
+                }
+                // our own echo stays out of carrier sense
+                macs[i].push(&channel, false);
+                if macs[i].queued() == 0
+                    && let Some(out) = arqs[i].poll_transmit(now)
+                {
+                    macs[i].enqueue(tone(arqs[i].airtime(out.payload.len())));
+                    frames[i] = Some(out);
+                }
+                if let Some(samples) = macs[i].poll() {
+                    arqs[i].started(now);
+                    air[i] = samples;
+                }
+            }
+        }
+
+        // played past the timers' first deadline after hand-out, yet sent once
+        assert_eq!(texts(&received), [b"held back"]);
+        assert_eq!(
+            deliveries.lock().unwrap()[..],
+            [
+                Delivery {
+                    id: 0,
+                    status: DeliveryStatus::Sent,
+                    attempts: 1
+                },
+                Delivery {
+                    id: 0,
+                    status: DeliveryStatus::Acknowledged { by: 2 },
+                    attempts: 1
+                },
+            ]
+        );
+    }
+}
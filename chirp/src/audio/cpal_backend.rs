@@ -0,0 +1,195 @@
+use std::{
+    fmt::Display,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use cpal::{
+    Device, SampleFormat, SampleRate, Stream, StreamConfig, StreamError,
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+};
+
+use crate::audio::{
+    AudioBackend, BUFFER_SECONDS,
+    error::{AudioError, AudioResult},
+    ring::{Consumer, Producer, StreamStats, ring_buffer},
+};
+
+/// which sound card devices to open and how
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpalConfig {
+    pub sample_rate: u32,
+    pub input_device: Option<String>, // default input device when `None`
+    pub output_device: Option<String>, // default output device when `None`
+}
+
+impl Default for CpalConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: chirp_modem::SAMPLE_RATE,
+            input_device: None,
+            output_device: None,
+        }
+    }
+}
+
+/// sound card backend on the platform's default cpal host
+pub struct CpalBackend {
+    sample_rate: u32,
+    capture: Consumer,
+    playback: Producer,
+    errors: Arc<AtomicU64>, // counted by the streams' error callbacks
+    _input: Stream,         // streams stop when dropped
+    _output: Stream,
+}
+
+impl CpalBackend {
+    pub fn open(config: &CpalConfig) -> AudioResult<Self> {
+        let host = cpal::default_host();
+        let input = match &config.input_device {
+            Some(name) => find_device(host.input_devices().map_err(device_error)?, name)?,
+            None => host
+                .default_input_device()
+                .ok_or_else(|| AudioError::Device("no default input device".into()))?,
+        };
+        let output = match &config.output_device {
+            Some(name) => find_device(host.output_devices().map_err(device_error)?, name)?,
+            None => host
+                .default_output_device()
+                .ok_or_else(|| AudioError::Device("no default output device".into()))?,
+        };
+
+        let rate = config.sample_rate;
+        let buffered = (rate * BUFFER_SECONDS) as usize;
+        let (mut captured, capture) = ring_buffer(buffered);
+        let (playback, mut to_play) = ring_buffer(buffered);
+        let errors = Arc::new(AtomicU64::new(0));
+
+        let input_config =
+            stream_config(input.supported_input_configs().map_err(device_error)?, rate)?;
+        let channels = input_config.channels as usize;
+        let input_stream = input
+            .build_input_stream(
+                &input_config,
+                move |data: &[f32], _| captured.write_frames(data, channels),
+                count_errors(&errors),
+                None,
+            )
+            .map_err(stream_error)?;
+
+        let output_config = stream_config(
+            output.supported_output_configs().map_err(device_error)?,
+            rate,
+        )?;
+        let channels = output_config.channels as usize;
+        let output_stream = output
+            .build_output_stream(
+                &output_config,
+                move |data: &mut [f32], _| to_play.read_frames(data, channels),
+                count_errors(&errors),
+                None,
+            )
+            .map_err(stream_error)?;
+
+        input_stream.play().map_err(stream_error)?;
+        output_stream.play().map_err(stream_error)?;
+        Ok(Self {
+            sample_rate: rate,
+            capture,
+            playback,
+            errors,
+            _input: input_stream,
+            _output: output_stream,
+        })
+    }
+}
+
+impl AudioBackend for CpalBackend {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, samples: &[f32]) -> AudioResult<usize> {
+        Ok(self.playback.write(samples))
+    }
+
+    fn pull(&mut self, buffer: &mut [f32]) -> AudioResult<usize> {
+        Ok(self.capture.read(buffer))
+    }
+
+    fn pending(&self) -> usize {
+        self.playback.len()
+    }
+
+    fn finish(&mut self) {
+        self.playback.finish()
+    }
+
+    fn wait(&mut self, timeout: Duration) -> bool {
+        if self.capture.is_empty() {
+            std::thread::sleep(timeout);
+        }
+        true
+    }
+
+    fn stats(&self) -> StreamStats {
+        let (capture, playback) = (self.capture.stats(), self.playback.stats());
+        StreamStats {
+            overruns: capture.overruns,
+            underruns: playback.underruns,
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// stream error callback, counted rather than printed since the terminal may
+// belong to a TUI
+fn count_errors(errors: &Arc<AtomicU64>) -> impl FnMut(StreamError) + Send + 'static {
+    let errors = errors.clone();
+    move |_| {
+        errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// names of every input and output device on the default host
+pub fn device_names() -> AudioResult<(Vec<String>, Vec<String>)> {
+    let host = cpal::default_host();
+    let inputs = host.input_devices().map_err(device_error)?;
+    let outputs = host.output_devices().map_err(device_error)?;
+    Ok((
+        inputs.filter_map(|device| device.name().ok()).collect(),
+        outputs.filter_map(|device| device.name().ok()).collect(),
+    ))
+}
+
+fn find_device(mut devices: impl Iterator<Item = Device>, name: &str) -> AudioResult<Device> {
+    devices
+        .find(|device| device.name().is_ok_and(|n| n == name))
+        .ok_or_else(|| AudioError::Device(format!("no device named {name}")))
+}
+
+// first f32 configuration able to run at `rate`
+fn stream_config(
+    mut configs: impl Iterator<Item = cpal::SupportedStreamConfigRange>,
+    rate: u32,
+) -> AudioResult<StreamConfig> {
+    configs
+        .find(|cfg| {
+            cfg.sample_format() == SampleFormat::F32
+                && cfg.min_sample_rate().0 <= rate
+                && cfg.max_sample_rate().0 >= rate
+        })
+        .map(|cfg| cfg.with_sample_rate(SampleRate(rate)).config())
+        .ok_or_else(|| AudioError::Unsupported(format!("no f32 stream at {rate} Hz")))
+}
+
+fn device_error(err: impl Display) -> AudioError {
+    AudioError::Device(err.to_string())
+}
+
+fn stream_error(err: impl Display) -> AudioError {
+    AudioError::Stream(err.to_string())
+}
@@ -0,0 +1,21 @@
+use thiserror::Error;
+
+pub type AudioResult<T> = Result<T, AudioError>;
+
+#[derive(Error, Debug)]
+pub enum AudioError {
+    #[error("Audio device unavailable: {0}")]
+    Device(String),
+
+    #[error("Unsupported stream configuration: {0}")]
+    Unsupported(String),
+
+    #[error("Audio stream failed: {0}")]
+    Stream(String),
+
+    #[error("JACK error: {0}")]
+    Jack(#[from] jack::Error),
+
+    #[error("WAV error: {0}")]
+    Wav(#[from] hound::Error),
+}
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use crate::audio::{AudioBackend, StreamStats, error::AudioResult};
+
+/// scales played and captured samples of another backend, e.g. by the gains
+/// a calibration recommends
+///
+/// Played samples are clamped to full scale after scaling.
+pub struct GainBackend<B> {
+    inner: B,
+    tx_gain: f32,
+    rx_gain: f32,
+    scaled: Vec<f32>, // played samples after scaling, reused between pushes
+}
+
+impl<B: AudioBackend> GainBackend<B> {
+    pub fn new(inner: B, tx_gain: f32, rx_gain: f32) -> Self {
+        Self {
+            inner,
+            tx_gain,
+            rx_gain,
+            scaled: Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: AudioBackend> AudioBackend for GainBackend<B> {
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn push(&mut self, samples: &[f32]) -> AudioResult<usize> {
+        self.scaled.clear();
+        self.scaled.extend(
+            samples
+                .iter()
+                .map(|sample| (sample * self.tx_gain).clamp(-1.0, 1.0)),
+        );
+        self.inner.push(&self.scaled)
+    }
+
+    fn pull(&mut self, buffer: &mut [f32]) -> AudioResult<usize> {
+        let count = self.inner.pull(buffer)?;
+        buffer[..count]
+            .iter_mut()
+            .for_each(|sample| *sample *= self.rx_gain);
+        Ok(count)
+    }
+
+    fn pending(&self) -> usize {
+        self.inner.pending()
+    }
+
+    fn finish(&mut self) {
+        self.inner.finish()
+    }
+
+    fn wait(&mut self, timeout: Duration) -> bool {
+        self.inner.wait(timeout)
+    }
+
+    fn stats(&self) -> StreamStats {
+        self.inner.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::MemoryBackend;
+
+    #[test]
+    fn test_scales_both_ways() {
+        let mut backend = GainBackend::new(MemoryBackend::loopback(48_000), 0.5, 4.0);
+        assert_eq!(backend.push(&[0.5, -1.0, 4.0]).unwrap(), 3);
+        let mut captured = [0.0; 4];
+        assert_eq!(backend.pull(&mut captured).unwrap(), 3);
+        assert_eq!(captured[..3], [1.0, -2.0, 4.0]);
+        assert_eq!(backend.into_inner().played(), [0.25, -0.5, 1.0]);
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use jack::{
+    AsyncClient, AudioIn, AudioOut, Client, ClientOptions, Control, Port, PortFlags,
+    ProcessHandler, ProcessScope,
+};
+
+use crate::audio::{
+    AudioBackend, BUFFER_SECONDS,
+    error::AudioResult,
+    ring::{Consumer, Producer, StreamStats, ring_buffer},
+};
+
+/// JACK backend, a client with one input and one output port wired to the
+/// first physical capture and playback ports
+///
+/// Requires a running JACK server, the sample rate is whatever it runs at.
+pub struct JackBackend {
+    sample_rate: u32,
+    capture: Consumer,
+    playback: Producer,
+    _client: AsyncClient<(), Duplex>, // deactivates when dropped
+}
+
+// real-time process callback, owns the other end of each ring
+struct Duplex {
+    input: Port<AudioIn>,
+    output: Port<AudioOut>,
+    captured: Producer,
+    to_play: Consumer,
+}
+
+impl ProcessHandler for Duplex {
+    fn process(&mut self, _: &Client, scope: &ProcessScope) -> Control {
+        self.captured.write_frames(self.input.as_slice(scope), 1);
+        self.to_play.read_frames(self.output.as_mut_slice(scope), 1);
+        Control::Continue
+    }
+}
+
+impl JackBackend {
+    pub fn open(client_name: &str) -> AudioResult<Self> {
+        let (client, _status) = Client::new(client_name, ClientOptions::NO_START_SERVER)?;
+        let input = client.register_port("in", AudioIn::default())?;
+        let output = client.register_port("out", AudioOut::default())?;
+        let (input_name, output_name) = (input.name()?, output.name()?);
+
+        let sample_rate = client.sample_rate() as u32;
+        let buffered = (sample_rate * BUFFER_SECONDS) as usize;
+        let (captured, capture) = ring_buffer(buffered);
+        let (playback, to_play) = ring_buffer(buffered);
+        let active = client.activate_async(
+            (),
+            Duplex {
+                input,
+                output,
+                captured,
+                to_play,
+            },
+        )?;
+
+        let client = active.as_client();
+        let physical =
+            |direction| client.ports(None, Some("audio"), direction | PortFlags::IS_PHYSICAL);
+        if let Some(source) = physical(PortFlags::IS_OUTPUT).first() {
+            client.connect_ports_by_name(source, &input_name)?;
+        }
+        if let Some(sink) = physical(PortFlags::IS_INPUT).first() {
+            client.connect_ports_by_name(&output_name, sink)?;
+        }
+        Ok(Self {
+            sample_rate,
+            capture,
+            playback,
+            _client: active,
+        })
+    }
+}
+
+impl AudioBackend for JackBackend {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, samples: &[f32]) -> AudioResult<usize> {
+        Ok(self.playback.write(samples))
+    }
+
+    fn pull(&mut self, buffer: &mut [f32]) -> AudioResult<usize> {
+        Ok(self.capture.read(buffer))
+    }
+
+    fn pending(&self) -> usize {
+        self.playback.len()
+    }
+
+    fn finish(&mut self) {
+        self.playback.finish()
+    }
+
+    fn wait(&mut self, timeout: Duration) -> bool {
+        if self.capture.is_empty() {
+            std::thread::sleep(timeout);
+        }
+        true
+    }
+
+    fn stats(&self) -> StreamStats {
+        let (capture, playback) = (self.capture.stats(), self.playback.stats());
+        StreamStats {
+            overruns: capture.overruns,
+            underruns: playback.underruns,
+            errors: 0,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::audio::{AudioBackend, error::AudioResult};
+
+/// backend over in-memory buffers, for tests and offline processing
+///
+/// Captured samples come from whatever was fed in, played samples are kept
+/// until taken. In loopback mode every played sample is captured as well.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    sample_rate: u32,
+    capture: VecDeque<f32>, // waiting to be pulled
+    played: Vec<f32>,       // everything pushed since the last `take_played`
+    loopback: bool,
+}
+
+impl MemoryBackend {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            ..Self::default()
+        }
+    }
+
+    /// capture whatever gets played
+    pub fn loopback(sample_rate: u32) -> Self {
+        Self {
+            loopback: true,
+            ..Self::new(sample_rate)
+        }
+    }
+
+    pub fn with_capture(mut self, samples: &[f32]) -> Self {
+        self.feed(samples);
+        self
+    }
+
+    /// make samples available for capture
+    pub fn feed(&mut self, samples: &[f32]) {
+        self.capture.extend(samples);
+    }
+
+    pub fn played(&self) -> &[f32] {
+        &self.played
+    }
+
+    pub fn take_played(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.played)
+    }
+}
+
+impl AudioBackend for MemoryBackend {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, samples: &[f32]) -> AudioResult<usize> {
+        self.played.extend_from_slice(samples);
+        if self.loopback {
+            self.capture.extend(samples);
+        }
+        Ok(samples.len())
+    }
+
+    fn pull(&mut self, buffer: &mut [f32]) -> AudioResult<usize> {
+        let count = buffer.len().min(self.capture.len());
+        for (slot, sample) in buffer.iter_mut().zip(self.capture.drain(..count)) {
+            *slot = sample;
+        }
+        Ok(count)
+    }
+
+    fn pending(&self) -> usize {
+        0
+    }
+
+    fn wait(&mut self, _timeout: Duration) -> bool {
+        !self.capture.is_empty()
+    }
+}
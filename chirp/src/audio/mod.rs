@@ -0,0 +1,160 @@
+//! Audio I/O behind one trait, so modems and apps run unchanged against a
+//! sound card, a JACK graph, WAV files or plain memory.
+//!
+//! Every backend speaks mono `f32` samples in `[-1, 1]`. Multi-channel
+//! devices get the same sample on every output channel and are captured
+//! from their first input channel.
+
+pub mod cpal_backend;
+pub mod error;
+pub mod gain;
+pub mod jack_backend;
+pub mod memory;
+pub mod resampled;
+pub mod ring;
+pub mod wav;
+
+use std::time::Duration;
+
+pub use cpal_backend::{CpalBackend, CpalConfig};
+pub use error::{AudioError, AudioResult};
+pub use gain::GainBackend;
+pub use jack_backend::JackBackend;
+pub use memory::MemoryBackend;
+pub use resampled::ResampledBackend;
+pub use ring::StreamStats;
+pub use wav::{WavBackend, read_wav, write_wav};
+
+/// seconds of audio each real-time backend buffers in either direction
+pub const BUFFER_SECONDS: u32 = 4;
+
+/// full-duplex audio stream, open from construction until dropped
+///
+/// Real-time backends hand samples to and from their audio threads through
+/// lock-free `ring` buffers, `push` takes only what fits.
+pub trait AudioBackend {
+    fn sample_rate(&self) -> u32;
+
+    /// queue samples for playback, returns how many were taken
+    fn push(&mut self, samples: &[f32]) -> AudioResult<usize>;
+
+    /// move captured samples into `buffer`, returns how many were written
+    fn pull(&mut self, buffer: &mut [f32]) -> AudioResult<usize>;
+
+    /// samples queued for playback that haven't been played yet
+    fn pending(&self) -> usize;
+
+    /// everything pushed so far is a whole transmission, playback running out
+    /// after it isn't an underrun
+    fn finish(&mut self) {}
+
+    /// block up to `timeout` for more captured samples, false once capture
+    /// has ended for good, e.g. at the end of an input file
+    fn wait(&mut self, timeout: Duration) -> bool;
+
+    /// overruns and underruns of the real-time threads, if there are any
+    fn stats(&self) -> StreamStats {
+        StreamStats::default()
+    }
+}
+
+impl<B: AudioBackend + ?Sized> AudioBackend for Box<B> {
+    fn sample_rate(&self) -> u32 {
+        (**self).sample_rate()
+    }
+
+    fn push(&mut self, samples: &[f32]) -> AudioResult<usize> {
+        (**self).push(samples)
+    }
+
+    fn pull(&mut self, buffer: &mut [f32]) -> AudioResult<usize> {
+        (**self).pull(buffer)
+    }
+
+    fn pending(&self) -> usize {
+        (**self).pending()
+    }
+
+    fn finish(&mut self) {
+        (**self).finish()
+    }
+
+    fn wait(&mut self, timeout: Duration) -> bool {
+        (**self).wait(timeout)
+    }
+
+    fn stats(&self) -> StreamStats {
+        (**self).stats()
+    }
+}
+
+// how long `play_and_record` waits between polls of an idle backend
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// play `samples` and capture for as long as they take to play
+pub fn play_and_record<B: AudioBackend + ?Sized>(
+    backend: &mut B,
+    samples: &[f32],
+) -> AudioResult<Vec<f32>> {
+    let mut recorded = Vec::with_capacity(samples.len());
+    let mut block = [0.0; 1024];
+    let mut queued = 0;
+    while recorded.len() < samples.len() {
+        let pushed = backend.push(&samples[queued..])?;
+        queued += pushed;
+        if pushed > 0 && queued == samples.len() {
+            backend.finish();
+        }
+        let count = backend.pull(&mut block)?;
+        recorded.extend_from_slice(&block[..count]);
+        if count == 0 && !backend.wait(POLL_INTERVAL) {
+            break;
+        }
+    }
+    recorded.truncate(samples.len());
+    Ok(recorded)
+}
+
+/// center and stretch a recording to span exactly `[-1, 1]`
+///
+/// Silent or empty recordings are left alone.
+pub fn normalize_wave(values: &mut [f32]) {
+    let (min, max) = values
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &v| {
+            (min.min(v), max.max(v))
+        });
+    let half_range = (max - min) / 2.0;
+    if !(half_range > 0.0 && half_range.is_finite()) {
+        return;
+    }
+    let mid = (min + max) / 2.0;
+    for val in values.iter_mut() {
+        *val = (*val - mid) / half_range;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_wave() {
+        let mut wave = [0.0, 1.0, 0.5, 2.0];
+        normalize_wave(&mut wave);
+        assert_eq!(wave, [-1.0, 0.0, -0.5, 1.0]);
+
+        let mut flat = [0.25; 4];
+        normalize_wave(&mut flat);
+        assert_eq!(flat, [0.25; 4]);
+        normalize_wave(&mut []);
+    }
+
+    #[test]
+    fn test_play_and_record_loopback() {
+        let tone: Vec<f32> = (0..4000).map(|i| (i as f32 * 0.1).sin()).collect();
+        let mut backend = MemoryBackend::loopback(48_000);
+        assert_eq!(play_and_record(&mut backend, &tone).unwrap(), tone);
+        assert_eq!(backend.pending(), 0);
+    }
+}
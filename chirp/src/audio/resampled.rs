@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use crate::{
+    audio::{AudioBackend, AudioError, StreamStats, error::AudioResult},
+    liquid_modem::{error::ModemError, resamp::Resampler},
+};
+
+/// runs another backend at a different sample rate, resampling both ways
+///
+/// A link keeps its nominal rate whatever the sound card runs at, so symbols
+/// last as long on every station. Resampled playback is handed on as the
+/// inner backend takes it, a transmission's end is marked once the filter
+/// has been flushed behind it.
+pub struct ResampledBackend<B> {
+    inner: B,
+    sample_rate: u32,
+    playback: Resampler,
+    capture: Resampler,
+    to_play: Vec<f32>,  // resampled, not yet taken by `inner`
+    end: Option<usize>, // samples of `to_play` up to a finished transmission
+    captured: Vec<f32>, // resampled, not yet pulled
+    block: Vec<f32>,    // device samples, reused between pulls
+}
+
+// lowpass both ways, with the cutoff just below the lower rate's Nyquist so
+// tones up there still make it through
+const SEMI_LENGTH: u32 = 64;
+const CUTOFF: f32 = 0.49;
+
+impl<B: AudioBackend> ResampledBackend<B> {
+    /// `inner` as seen at `sample_rate`
+    pub fn new(inner: B, sample_rate: u32) -> AudioResult<Self> {
+        let (ours, theirs) = (sample_rate as f32, inner.sample_rate() as f32);
+        let lower = ours.min(theirs);
+        let playback = Resampler::with_cutoff(theirs / ours, CUTOFF * lower / ours, SEMI_LENGTH);
+        let capture = Resampler::with_cutoff(ours / theirs, CUTOFF * lower / theirs, SEMI_LENGTH);
+        Ok(Self {
+            inner,
+            sample_rate,
+            playback: playback.map_err(resampling)?,
+            capture: capture.map_err(resampling)?,
+            to_play: Vec::new(),
+            end: None,
+            captured: Vec::new(),
+            block: Vec::new(),
+        })
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    // hand on as much as `inner` takes, marking the end of a transmission
+    // once it has all gone
+    fn forward(&mut self) -> AudioResult<()> {
+        let upto = self.end.unwrap_or(self.to_play.len());
+        let mut taken = self.inner.push(&self.to_play[..upto])?;
+        if taken == upto && self.end.take().is_some() {
+            self.inner.finish();
+            taken += self.inner.push(&self.to_play[upto..])?;
+        }
+        self.to_play.drain(..taken);
+        if let Some(end) = &mut self.end {
+            *end -= taken;
+        }
+        Ok(())
+    }
+
+    // device samples per one of ours
+    fn ratio(&self) -> f32 {
+        self.playback.rate()
+    }
+}
+
+fn resampling(err: ModemError) -> AudioError {
+    AudioError::Stream(format!("resampling failed: {err}"))
+}
+
+impl<B: AudioBackend> AudioBackend for ResampledBackend<B> {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, samples: &[f32]) -> AudioResult<usize> {
+        self.forward()?;
+        // only take more once the last push has been handed on
+        if !self.to_play.is_empty() {
+            return Ok(0);
+        }
+        self.playback
+            .push_block(samples, &mut self.to_play)
+            .map_err(resampling)?;
+        self.forward()?;
+        Ok(samples.len())
+    }
+
+    fn pull(&mut self, buffer: &mut [f32]) -> AudioResult<usize> {
+        self.forward()?;
+        let wanted = buffer.len().saturating_sub(self.captured.len());
+        self.block
+            .resize((wanted as f32 * self.ratio()).ceil() as usize, 0.0);
+        let count = self.inner.pull(&mut self.block)?;
+        self.capture
+            .push_block(&self.block[..count], &mut self.captured)
+            .map_err(resampling)?;
+
+        let count = buffer.len().min(self.captured.len());
+        buffer[..count].copy_from_slice(&self.captured[..count]);
+        self.captured.drain(..count);
+        Ok(count)
+    }
+
+    fn pending(&self) -> usize {
+        let device = self.inner.pending() + self.to_play.len();
+        (device as f32 / self.ratio()).ceil() as usize
+    }
+
+    fn finish(&mut self) {
+        // push the filter's tail out behind the transmission
+        let flush = 2 * self.playback.delay() + 1;
+        if self
+            .playback
+            .push_block(&vec![0.0; flush], &mut self.to_play)
+            .is_ok()
+        {
+            self.end = Some(self.to_play.len());
+        }
+    }
+
+    fn wait(&mut self, timeout: Duration) -> bool {
+        !self.captured.is_empty() || self.inner.wait(timeout)
+    }
+
+    fn stats(&self) -> StreamStats {
+        self.inner.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audio::{MemoryBackend, play_and_record},
+        frame::Flags,
+        link::{LinkConfig, Receiver, Transmitter},
+    };
+
+    #[test]
+    fn test_link_at_48k_over_a_44k_device() {
+        let link = LinkConfig::default();
+        assert_eq!(link.keying.sample_rate(), 48_000);
+        let mut samples = vec![0.0; 4096];
+        samples.extend(
+            Transmitter::new(link)
+                .transmit(Flags::EMPTY, b"resampled")
+                .unwrap(),
+        );
+        samples.resize(samples.len() + 4096, 0.0);
+
+        let mut backend = ResampledBackend::new(MemoryBackend::loopback(44_100), 48_000).unwrap();
+        assert_eq!(backend.sample_rate(), 48_000);
+        let recorded = play_and_record(&mut backend, &samples).unwrap();
+        assert_eq!(recorded.len(), samples.len());
+        let played = backend.into_inner().played().len() as f32;
+        assert!((played / samples.len() as f32 - 44_100.0 / 48_000.0).abs() < 0.01);
+
+        let mut payloads = Vec::new();
+        Receiver::new(link).push(&recorded, |reception| {
+            payloads.push(reception.frame.unwrap().payload);
+        });
+        assert_eq!(payloads, [b"resampled"]);
+    }
+}
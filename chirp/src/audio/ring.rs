@@ -0,0 +1,294 @@
+use std::{
+    cell::UnsafeCell,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+/// how often a real-time stream had to drop or make up samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StreamStats {
+    /// capture periods that found the ring full and dropped samples
+    pub overruns: u64,
+    /// playback periods that ran dry before the transmission was finished
+    pub underruns: u64,
+    /// failures the device reported on either stream
+    pub errors: u64,
+}
+
+/// lock-free single-producer single-consumer ring of samples
+///
+/// Neither end locks or allocates after creation, so one end can live in a
+/// real-time audio callback while the other is driven by the application.
+pub fn ring_buffer(capacity: usize) -> (Producer, Consumer) {
+    let capacity = capacity.max(1).next_power_of_two();
+    let shared = Arc::new(Shared {
+        slots: (0..capacity).map(|_| UnsafeCell::new(0.0)).collect(),
+        mask: capacity - 1,
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+        end: AtomicUsize::new(0),
+        overruns: AtomicU64::new(0),
+        underruns: AtomicU64::new(0),
+    });
+    (
+        Producer {
+            shared: shared.clone(),
+        },
+        Consumer { shared, dry: None },
+    )
+}
+
+struct Shared {
+    slots: Box<[UnsafeCell<f32>]>,
+    mask: usize,        // capacity is a power of two
+    read: AtomicUsize,  // samples read so far, wrapping, stored by the consumer only
+    write: AtomicUsize, // samples written so far, wrapping, stored by the producer only
+    end: AtomicUsize,   // `write` at the end of the last finished transmission
+    overruns: AtomicU64,
+    underruns: AtomicU64,
+}
+
+// slots in `read..write` belong to the consumer, the rest to the producer,
+// and each index is only advanced after its end is done with the slots
+unsafe impl Sync for Shared {}
+
+impl Shared {
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn len(&self) -> usize {
+        let write = self.write.load(Ordering::Acquire);
+        write.wrapping_sub(self.read.load(Ordering::Acquire))
+    }
+
+    fn stats(&self) -> StreamStats {
+        StreamStats {
+            overruns: self.overruns.load(Ordering::Relaxed),
+            underruns: self.underruns.load(Ordering::Relaxed),
+            errors: 0,
+        }
+    }
+}
+
+/// writing end of a `ring_buffer`
+pub struct Producer {
+    shared: Arc<Shared>,
+}
+
+impl Producer {
+    /// append as many samples as fit, returns how many did
+    pub fn write(&mut self, samples: &[f32]) -> usize {
+        self.write_iter(samples.iter().copied())
+    }
+
+    /// append the first channel of interleaved `frames`, counting an overrun
+    /// when some didn't fit
+    pub fn write_frames(&mut self, frames: &[f32], channels: usize) {
+        let samples = frames.iter().step_by(channels.max(1)).copied();
+        let wanted = samples.len();
+        if self.write_iter(samples) < wanted {
+            self.shared.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// mark everything written so far as one whole transmission, running dry
+    /// once it has played isn't an underrun
+    pub fn finish(&mut self) {
+        let write = self.shared.write.load(Ordering::Relaxed);
+        self.shared.end.store(write, Ordering::Release);
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity()
+    }
+
+    pub fn stats(&self) -> StreamStats {
+        self.shared.stats()
+    }
+
+    fn write_iter(&mut self, samples: impl Iterator<Item = f32>) -> usize {
+        let shared = &*self.shared;
+        let write = shared.write.load(Ordering::Relaxed);
+        let free = shared.capacity() - write.wrapping_sub(shared.read.load(Ordering::Acquire));
+        let mut count = 0;
+        for sample in samples.take(free) {
+            let slot = &shared.slots[write.wrapping_add(count) & shared.mask];
+            // free slots aren't touched by the consumer until `write` moves past them
+            unsafe { *slot.get() = sample };
+            count += 1;
+        }
+        shared
+            .write
+            .store(write.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+/// reading end of a `ring_buffer`
+pub struct Consumer {
+    shared: Arc<Shared>,
+    dry: Option<usize>, // `read` where the last `read_frames` ran out
+}
+
+impl Consumer {
+    /// take up to `buffer.len()` samples, returns how many were read
+    pub fn read(&mut self, buffer: &mut [f32]) -> usize {
+        let shared = &*self.shared;
+        let read = shared.read.load(Ordering::Relaxed);
+        let available = shared.write.load(Ordering::Acquire).wrapping_sub(read);
+        let count = available.min(buffer.len());
+        for (i, out) in buffer[..count].iter_mut().enumerate() {
+            let slot = &shared.slots[read.wrapping_add(i) & shared.mask];
+            // filled slots aren't touched by the producer until `read` moves past them
+            *out = unsafe { *slot.get() };
+        }
+        shared
+            .read
+            .store(read.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    /// copy the next sample to every channel of each frame, silence once empty
+    ///
+    /// Running dry is the normal end of a transmission when the producer
+    /// `finish`ed it right there, anywhere else every short period counts as
+    /// an underrun. A period is judged when the next one is read, so a
+    /// `finish` right after the last write still makes it in time.
+    pub fn read_frames(&mut self, frames: &mut [f32], channels: usize) {
+        let channels = channels.max(1);
+        if let Some(dry) = self.dry
+            && self.shared.end.load(Ordering::Acquire) != dry
+        {
+            self.shared.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut played = 0;
+        for frame in frames.chunks_mut(channels) {
+            let mut sample = [0.0];
+            played += self.read(&mut sample);
+            frame.fill(sample[0]);
+        }
+        self.dry = (played < frames.len().div_ceil(channels))
+            .then(|| self.shared.read.load(Ordering::Relaxed));
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> StreamStats {
+        self.shared.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wraps_and_counts_xruns() {
+        let (mut producer, mut consumer) = ring_buffer(6);
+        assert_eq!(producer.capacity(), 8);
+        let mut out = [0.0; 8];
+        for round in 0..5 {
+            let samples: Vec<f32> = (0..5).map(|i| (round * 5 + i) as f32).collect();
+            assert_eq!(producer.write(&samples), 5);
+            assert_eq!(consumer.read(&mut out), 5);
+            assert_eq!(&out[..5], &samples[..]);
+        }
+
+        // stereo capture keeps the left channel, drops what doesn't fit
+        producer.write_frames(&[1.0, -1.0, 2.0, -2.0], 2);
+        assert_eq!(producer.stats().overruns, 0);
+        producer.write_frames(&[0.5; 20], 2);
+        assert_eq!(consumer.len(), 8);
+        assert_eq!(consumer.stats().overruns, 1);
+
+        // drain most, the transmission ends mid period and the ring idles
+        consumer.read(&mut out[..6]);
+        producer.finish();
+        let mut period = [9.0; 4];
+        consumer.read_frames(&mut period, 1);
+        assert_eq!(period, [0.5, 0.5, 0.0, 0.0]);
+        consumer.read_frames(&mut period, 1);
+        consumer.read_frames(&mut period, 1);
+        assert_eq!(consumer.stats().underruns, 0);
+
+        // the next one runs dry mid period and catches up right away
+        producer.write(&[0.25]);
+        consumer.read_frames(&mut period, 1);
+        producer.write(&[0.25; 4]);
+        consumer.read_frames(&mut period, 1);
+        producer.finish();
+        consumer.read_frames(&mut period, 1);
+        assert_eq!(consumer.stats().underruns, 1);
+    }
+
+    #[test]
+    fn test_whole_periods_without_samples_are_underruns() {
+        let (mut producer, mut consumer) = ring_buffer(16);
+        let mut period = [9.0; 4];
+
+        // the ring empties right at a period boundary, then stays empty for
+        // another whole period before the rest shows up
+        producer.write(&[0.5; 4]);
+        consumer.read_frames(&mut period, 1);
+        consumer.read_frames(&mut period, 1);
+        assert_eq!(period, [0.0; 4]);
+        consumer.read_frames(&mut period, 1);
+        producer.write(&[0.5; 4]);
+        producer.finish();
+        consumer.read_frames(&mut period, 1);
+        assert_eq!(period, [0.5; 4]);
+        assert_eq!(consumer.stats().underruns, 2);
+
+        // ending on a period boundary is fine
+        consumer.read_frames(&mut period, 1);
+        consumer.read_frames(&mut period, 1);
+        assert_eq!(consumer.stats().underruns, 2);
+    }
+
+    #[test]
+    fn test_threads_see_samples_in_order() {
+        let (mut producer, mut consumer) = ring_buffer(64);
+        let count = 20_000;
+        let writer = std::thread::spawn(move || {
+            let samples: Vec<f32> = (0..count).map(|i| i as f32).collect();
+            let mut next = 0;
+            while next < count {
+                let end = (next + 24).min(count);
+                match producer.write(&samples[next..end]) {
+                    0 => std::thread::yield_now(),
+                    written => next += written,
+                }
+            }
+        });
+        let mut expected = 0;
+        let mut block = [0.0; 16];
+        while expected < count {
+            let read = consumer.read(&mut block);
+            if read == 0 {
+                std::thread::yield_now();
+            }
+            for &sample in &block[..read] {
+                assert_eq!(sample, expected as f32);
+                expected += 1;
+            }
+        }
+        writer.join().unwrap();
+    }
+}
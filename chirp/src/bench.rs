@@ -0,0 +1,403 @@
+//! Bit and packet error rates of a link over the channel simulator.
+//!
+//! Every trial sends one frame with a random payload through a fresh
+//! `sim::Channel` into a fresh `Receiver`. A packet error is any frame that
+//! doesn't come back intact, missed preambles included. Bit errors compare
+//! the raw demodulated frame, before error correction, against the bytes
+//! sent, so they only cover frames whose preamble was found.
+//!
+//! PSK and liquid's other linear schemes run over `liquid_modem::passband`,
+//! which has no preamble detection of its own. Their receiver starts right
+//! where the frame was put on the air instead, so they never miss one.
+
+use bitvec::{field::BitField, order::Lsb0, slice::BitSlice, vec::BitVec, view::BitView};
+use chirp_modem::{
+    demodulator::BitDemodulator,
+    keying::{Demodulator, Keying},
+};
+use thiserror::Error;
+
+use crate::{
+    frame::{Coding, Flags, Fragment, Frame, FrameDecoder, FrameError, Header},
+    link::{LinkConfig, Receiver, Transmitter},
+    liquid_modem::{
+        digital::{DigitalModem, ModulationScheme},
+        error::ModemError,
+        passband::{Downconverter, PassbandConfig, Upconverter},
+    },
+    sim::{Channel, ChannelConfig, XorShift},
+    station::{ANONYMOUS, BROADCAST},
+    sync::ChirpPreamble,
+};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BenchError {
+    #[error("Frame encoding failed: {0}")]
+    Frame(#[from] FrameError),
+
+    #[error("Channel setup failed: {0}")]
+    Channel(#[from] ModemError),
+
+    #[error("Passband modem failed: {0}")]
+    Passband(ModemError),
+}
+
+/// how a benchmarked link puts its bits on the air
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Modulation {
+    /// one of the link's own keyings, framed by the chirp preamble
+    Keying(Keying),
+    /// a liquid linear scheme like `qpsk` over `liquid_modem::passband`
+    Passband(ModulationScheme),
+}
+
+impl Modulation {
+    /// `ook`, `fsk`, `mfsk` or a liquid scheme name like `qpsk` or `dpsk4`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Keying::from_name(name)
+            .map(Modulation::Keying)
+            .or_else(|| name.parse().ok().map(Modulation::Passband))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Modulation::Keying(keying) => keying.name(),
+            Modulation::Passband(scheme) => scheme.name(),
+        }
+    }
+}
+
+impl Default for Modulation {
+    fn default() -> Self {
+        Modulation::Keying(Keying::default())
+    }
+}
+
+/// tallies for one link configuration at one channel setting
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BenchPoint {
+    pub modulation: Modulation,
+    pub coding: Coding,
+    pub snr_db: Option<f32>,
+    pub frames: usize,
+    pub frame_errors: usize,
+    pub missed: usize, // frames whose preamble wasn't detected
+    pub bits: usize,   // raw frame bits compared
+    pub bit_errors: usize,
+    pub delivered: usize, // payload bytes received intact
+    pub airtime: f32,     // seconds on the air, preambles and guards included
+}
+
+impl BenchPoint {
+    pub const CSV_HEADER: &'static str =
+        "keying,coding,snr_db,frames,frame_errors,missed,per,bits,bit_errors,ber,throughput_bps";
+
+    /// raw bit error rate, before error correction
+    pub fn ber(&self) -> f32 {
+        ratio(self.bit_errors, self.bits)
+    }
+
+    /// packet error rate
+    pub fn per(&self) -> f32 {
+        ratio(self.frame_errors, self.frames)
+    }
+
+    /// payload bits per second delivered intact
+    pub fn throughput(&self) -> f32 {
+        if self.airtime > 0.0 {
+            (self.delivered * 8) as f32 / self.airtime
+        } else {
+            0.0
+        }
+    }
+
+    pub fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{:.4},{},{},{:.3e},{:.1}",
+            self.modulation.name(),
+            self.coding,
+            self.snr_db.map(|snr| snr.to_string()).unwrap_or_default(),
+            self.frames,
+            self.frame_errors,
+            self.missed,
+            self.per(),
+            self.bits,
+            self.bit_errors,
+            self.ber(),
+            self.throughput(),
+        )
+    }
+
+    /// one flat JSON object, keys as in `CSV_HEADER`
+    pub fn json(&self) -> String {
+        format!(
+            concat!(
+                r#"{{"keying":"{}","coding":"{}","snr_db":{},"frames":{},"frame_errors":{},"#,
+                r#""missed":{},"per":{},"bits":{},"bit_errors":{},"ber":{},"throughput_bps":{}}}"#,
+            ),
+            self.modulation.name(),
+            self.coding,
+            self.snr_db
+                .map(|snr| snr.to_string())
+                .unwrap_or_else(|| "null".into()),
+            self.frames,
+            self.frame_errors,
+            self.missed,
+            self.per(),
+            self.bits,
+            self.bit_errors,
+            self.ber(),
+            self.throughput(),
+        )
+    }
+}
+
+fn ratio(count: usize, total: usize) -> f32 {
+    match total {
+        0 => 0.0,
+        total => count as f32 / total as f32,
+    }
+}
+
+/// send `frames` frames of `payload_len` random bytes through `channel`
+///
+/// Payloads, lead-in silence and each frame's channel seed all derive from
+/// `channel.seed`, so a run repeats exactly.
+pub fn run(
+    link: LinkConfig,
+    channel: &ChannelConfig,
+    frames: usize,
+    payload_len: usize,
+) -> Result<BenchPoint, BenchError> {
+    let sample_rate = link.chirp.sample_rate;
+    let transmitter = Transmitter::new(link);
+    let preamble = ChirpPreamble::new(link.chirp);
+    let mut rng = XorShift::new(channel.seed);
+    let mut point = BenchPoint {
+        modulation: Modulation::Keying(link.keying),
+        coding: link.coding,
+        snr_db: channel.snr_db,
+        ..Default::default()
+    };
+
+    for sequence in 0..frames {
+        let frame = random_frame(link.coding, sequence, payload_len, &mut rng);
+        let sent = frame.encode(link.interleaver)?;
+        // start anywhere within a symbol, after some room for the echoes to build up
+        let lead =
+            link.chirp.sweep_len + rng.next_u64() as usize % link.keying.samples_per_symbol();
+        let mut samples = vec![0.0; lead];
+        samples.extend(transmitter.transmit_frame(&frame)?);
+        point.frames += 1;
+        point.airtime += (samples.len() - lead) as f32 / sample_rate as f32;
+
+        let trial = ChannelConfig {
+            seed: rng.next_u64(),
+            ..channel.clone()
+        };
+        let received = Channel::new(&trial, sample_rate)?.run(&samples)?;
+        let mut reception = None;
+        Receiver::new(link).push(&received, |r| {
+            reception.get_or_insert(r);
+        });
+        let Some(reception) = reception else {
+            point.missed += 1;
+            point.frame_errors += 1;
+            continue;
+        };
+        match reception.frame {
+            Ok(decoded) if decoded.payload == frame.payload => point.delivered += payload_len,
+            _ => point.frame_errors += 1,
+        }
+        let start = (reception.sync.payload_offset(&preamble) as usize).min(received.len());
+        point.bits += sent.len() * 8;
+        point.bit_errors += bit_errors(link.keying, &received[start..], &sent);
+    }
+    Ok(point)
+}
+
+/// send frames like `run`, keyed with liquid's `scheme` over the default
+/// `PassbandConfig`
+///
+/// Coding and interleaving come from `link`, its keying and preamble go
+/// unused. Nothing recovers the carrier phase, so coherent schemes like
+/// `qpsk` suffer any phase shift of the channel, e.g. the rolloff of
+/// `ChannelConfig::room`, while differential ones like `dpsk4` don't.
+pub fn run_passband(
+    scheme: ModulationScheme,
+    link: LinkConfig,
+    channel: &ChannelConfig,
+    frames: usize,
+    payload_len: usize,
+) -> Result<BenchPoint, BenchError> {
+    let config = PassbandConfig::default();
+    let mut rng = XorShift::new(channel.seed);
+    let mut point = BenchPoint {
+        modulation: Modulation::Passband(scheme),
+        coding: link.coding,
+        snr_db: channel.snr_db,
+        ..Default::default()
+    };
+
+    for sequence in 0..frames {
+        let frame = random_frame(link.coding, sequence, payload_len, &mut rng);
+        let sent = frame.encode(link.interleaver)?;
+        let mut samples = modulate(scheme, config, &sent).map_err(BenchError::Passband)?;
+        point.frames += 1;
+        point.airtime += samples.len() as f32 / config.sample_rate as f32;
+        // quiet while the receive filter catches up with the last symbols
+        let tail = config.filter_delay * config.samples_per_symbol;
+        samples.resize(samples.len() + tail as usize, 0.0);
+
+        let trial = ChannelConfig {
+            seed: rng.next_u64(),
+            ..channel.clone()
+        };
+        let received = Channel::new(&trial, config.sample_rate)?.run(&samples)?;
+        let decided = demodulate(scheme, config, &received).map_err(BenchError::Passband)?;
+        let mut decoder = FrameDecoder::with_interleaver(link.interleaver);
+        decoder.push_bits(&decided);
+        match decoder.next_frame() {
+            Some(Ok(decoded)) if decoded.payload == frame.payload => point.delivered += payload_len,
+            _ => point.frame_errors += 1,
+        }
+        point.bits += sent.len() * 8;
+        point.bit_errors += count_errors(&decided, sent.view_bits());
+    }
+    Ok(point)
+}
+
+fn random_frame(coding: Coding, sequence: usize, payload_len: usize, rng: &mut XorShift) -> Frame {
+    Frame {
+        header: Header {
+            flags: Flags::EMPTY,
+            coding,
+            source: ANONYMOUS,
+            destination: BROADCAST,
+            sequence: sequence as u16,
+            length: payload_len as u16,
+            fragment: Fragment::WHOLE,
+        },
+        payload: (0..payload_len).map(|_| rng.next_u64() as u8).collect(),
+    }
+}
+
+// raw errors demodulating `sent` from `received`, missing bits count as wrong
+fn bit_errors(keying: Keying, received: &[f32], sent: &[u8]) -> usize {
+    let sent = sent.view_bits::<Lsb0>();
+    let mut demod = Demodulator::new(keying);
+    for &sample in received {
+        if demod.bit_buffer().len() >= sent.len() {
+            break;
+        }
+        demod.push_sample(sample);
+    }
+    count_errors(demod.bit_buffer(), sent)
+}
+
+fn count_errors(decided: &BitSlice<u8, Lsb0>, sent: &BitSlice<u8, Lsb0>) -> usize {
+    let wrong = decided.iter().zip(sent).filter(|(a, b)| a != b).count();
+    wrong + sent.len().saturating_sub(decided.len())
+}
+
+// `bytes` least significant bit first, `bits_per_symbol` bits to a symbol
+fn modulate(
+    scheme: ModulationScheme,
+    config: PassbandConfig,
+    bytes: &[u8],
+) -> Result<Vec<f32>, ModemError> {
+    let mut modem = DigitalModem::new(scheme)?;
+    let mut upconverter = Upconverter::new(config)?;
+    let mut samples = Vec::new();
+    for bits in bytes
+        .view_bits::<Lsb0>()
+        .chunks(modem.bits_per_symbol() as usize)
+    {
+        let point = modem.modulate(bits.load_le())?;
+        upconverter.push(point, &mut samples)?;
+    }
+    upconverter.flush(&mut samples)?;
+    Ok(samples)
+}
+
+// hard decisions on everything in `samples`, which start on a symbol
+fn demodulate(
+    scheme: ModulationScheme,
+    config: PassbandConfig,
+    samples: &[f32],
+) -> Result<BitVec<u8, Lsb0>, ModemError> {
+    let mut modem = DigitalModem::new(scheme)?;
+    let mut downconverter = Downconverter::new(config)?;
+    let mut symbols = Vec::new();
+    downconverter.push_block(samples, &mut symbols)?;
+    let bits_per_symbol = modem.bits_per_symbol();
+    let mut decided = BitVec::new();
+    for &point in &symbols[(downconverter.delay() as usize).min(symbols.len())..] {
+        let symbol = modem.demodulate(point)?;
+        decided.extend((0..bits_per_symbol).map(|bit| (symbol >> bit) & 1 == 1));
+    }
+    Ok(decided)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_channel_has_no_errors() {
+        let link = LinkConfig::default();
+        let point = run(link, &ChannelConfig::default(), 3, 16).unwrap();
+        assert_eq!(
+            (point.frames, point.frame_errors, point.bit_errors),
+            (3, 0, 0)
+        );
+        assert_eq!(point.bits, 3 * 8 * Frame::encoded_len(link.coding, 16));
+        let expected = (3 * 16 * 8) as f32 / point.airtime;
+        assert!((point.throughput() - expected).abs() < 1e-3);
+        assert_eq!(
+            point.csv_row().split(',').count(),
+            BenchPoint::CSV_HEADER.split(',').count()
+        );
+    }
+
+    #[test]
+    fn test_passband_psk_over_noise() {
+        let link = LinkConfig::default();
+        let at = |scheme, snr_db| {
+            let channel = ChannelConfig {
+                snr_db,
+                seed: 5,
+                ..Default::default()
+            };
+            run_passband(scheme, link, &channel, 3, 16).unwrap()
+        };
+        for scheme in [ModulationScheme::Qpsk, ModulationScheme::Dpsk4] {
+            let clean = at(scheme, None);
+            assert_eq!((clean.frame_errors, clean.bit_errors), (0, 0));
+            assert_eq!(clean.bits, 3 * 8 * Frame::encoded_len(link.coding, 16));
+            assert_eq!(clean.modulation.name(), scheme.name());
+            assert!(at(scheme, Some(0.0)).ber() > 0.0);
+        }
+        assert_eq!(
+            Modulation::from_name("dpsk4"),
+            Some(Modulation::Passband(ModulationScheme::Dpsk4))
+        );
+    }
+
+    #[test]
+    fn test_errors_grow_as_snr_drops() {
+        let link = LinkConfig::default();
+        let at = |snr_db| {
+            let channel = ChannelConfig {
+                snr_db: Some(snr_db),
+                seed: 3,
+                ..Default::default()
+            };
+            run(link, &channel, 4, 32).unwrap()
+        };
+        let (good, bad) = (at(30.0), at(3.0));
+        assert_eq!(good.frame_errors, 0);
+        assert!(bad.ber() > good.ber());
+        assert!(bad.per() > 0.0);
+    }
+}
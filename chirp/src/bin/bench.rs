@@ -0,0 +1,138 @@
+//! Error rates and throughput of link configurations across an SNR sweep.
+//!
+//! ```text
+//! bench [--keying ook,fsk,mfsk,qpsk] [--fec none,h128,v27/rs8] [--snr 0:30:3]
+//!       [--channel ideal|room] [--frames 50] [--payload 64] [--seed 1]
+//!       [--format csv|json]
+//! ```
+//!
+//! Every keying and coding combination runs once per SNR value, results go
+//! to stdout, progress to stderr. Runs are seeded, so two runs of the same
+//! command on different revisions compare directly. Besides the link's own
+//! keyings, any liquid scheme like `qpsk` or `dpsk4` runs over
+//! `liquid_modem::passband`, see `bench::run_passband`.
+
+use chirp::{
+    bench::{self, BenchPoint, Modulation},
+    frame::Coding,
+    link::LinkConfig,
+    sim::ChannelConfig,
+};
+use std::error::Error;
+
+const USAGE: &str =
+    "usage: bench [--keying ook,fsk,mfsk,qpsk] [--fec none,h128,v27/rs8] [--snr 0:30:3]
+             [--channel ideal|room] [--frames 50] [--payload 64] [--seed 1]
+             [--format csv|json]";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut keyings = vec![
+        "ook".to_string(),
+        "fsk".into(),
+        "mfsk".into(),
+        "qpsk".into(),
+    ];
+    let mut codings = vec![Coding::NONE];
+    let mut snrs = sweep("0:30:3")?;
+    let mut channel = ChannelConfig::default();
+    let (mut frames, mut payload_len) = (50, 64);
+    let mut json = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(USAGE);
+        match arg.as_str() {
+            "--keying" => keyings = value()?.split(',').map(String::from).collect(),
+            "--fec" => {
+                codings = value()?
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()?
+            }
+            "--snr" => snrs = sweep(value()?.as_str())?,
+            "--channel" => {
+                channel = match value()?.as_str() {
+                    "ideal" => ChannelConfig::default(),
+                    "room" => ChannelConfig::room(),
+                    _ => return Err(USAGE.into()),
+                }
+            }
+            "--frames" => frames = value()?.parse()?,
+            "--payload" => payload_len = value()?.parse()?,
+            "--seed" => channel.seed = value()?.parse()?,
+            "--format" => {
+                json = match value()?.as_str() {
+                    "csv" => false,
+                    "json" => true,
+                    _ => return Err(USAGE.into()),
+                }
+            }
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let modulations = keyings
+        .iter()
+        .map(|name| Modulation::from_name(name).ok_or(format!("unknown keying {name}")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut points = Vec::new();
+    for &modulation in &modulations {
+        for &coding in &codings {
+            for &snr in &snrs {
+                let link = LinkConfig {
+                    coding,
+                    ..Default::default()
+                };
+                let channel = ChannelConfig {
+                    snr_db: Some(snr),
+                    ..channel.clone()
+                };
+                let point = match modulation {
+                    Modulation::Keying(keying) => {
+                        bench::run(LinkConfig { keying, ..link }, &channel, frames, payload_len)?
+                    }
+                    Modulation::Passband(scheme) => {
+                        bench::run_passband(scheme, link, &channel, frames, payload_len)?
+                    }
+                };
+                eprintln!(
+                    "{} {coding} at {snr} dB: ber {:.2e}, per {:.3}",
+                    modulation.name(),
+                    point.ber(),
+                    point.per()
+                );
+                points.push(point);
+            }
+        }
+    }
+    print(&points, json);
+    Ok(())
+}
+
+// `from:to:step` in dB, or a single value
+fn sweep(spec: &str) -> Result<Vec<f32>, Box<dyn Error>> {
+    let parts = spec
+        .split(':')
+        .map(str::parse)
+        .collect::<Result<Vec<f32>, _>>()?;
+    match parts[..] {
+        [snr] => Ok(vec![snr]),
+        [from, to, step] if step > 0.0 && from <= to => {
+            let count = ((to - from) / step).floor() as usize + 1;
+            Ok((0..count).map(|i| from + i as f32 * step).collect())
+        }
+        _ => Err(format!("bad snr sweep {spec}, expected from:to:step").into()),
+    }
+}
+
+fn print(points: &[BenchPoint], json: bool) {
+    if json {
+        let rows: Vec<String> = points.iter().map(BenchPoint::json).collect();
+        println!("[\n  {}\n]", rows.join(",\n  "));
+    } else {
+        println!("{}", BenchPoint::CSV_HEADER);
+        for point in points {
+            println!("{}", point.csv_row());
+        }
+    }
+}
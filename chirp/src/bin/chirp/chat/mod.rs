@@ -0,0 +1,274 @@
+//! Chat with everyone in earshot. Each message is one `arq` message,
+//! carrying the sender's nickname ahead of the text, and shows whether
+//! anyone acknowledged it. `/msg <nick> text` goes to one station only,
+//! stations heard from recently are listed as nearby. `/send <nick> <path>`
+//! offers a file, `/accept` or `/decline` answers the latest one offered.
+
+mod tui;
+mod worker;
+
+use std::{collections::HashSet, error::Error, io::BufRead, path::PathBuf, thread};
+
+use chirp::{
+    arq::{ArqConfig, DeliveryStatus},
+    calibrate::Calibration,
+    link::LinkConfig,
+    mac::MacConfig,
+    station::{BROADCAST, Neighbor, Station, format_station, parse_station},
+};
+
+use crate::options::AudioOptions;
+use worker::{Event, Request};
+
+/// longest nickname sent, longer ones are cut
+pub const MAX_NICK: usize = 32;
+
+/// longest message sent, nickname included, longer ones are cut
+pub const MAX_MESSAGE: usize = 4096;
+
+/// one line of chat as carried in an `arq` message
+///
+/// ```text
+/// | nick length | nick           | text       |
+/// | 1 byte      | 0-32 bytes     | the rest   |
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub nick: String,
+    pub text: String,
+}
+
+impl Message {
+    /// message data, nick and text cut at a character boundary to fit
+    /// `MAX_MESSAGE`
+    pub fn encode(&self) -> Vec<u8> {
+        let nick = truncate(&self.nick, MAX_NICK);
+        let text = truncate(&self.text, MAX_MESSAGE - 1 - nick.len());
+        let mut bytes = Vec::with_capacity(1 + nick.len() + text.len());
+        bytes.push(nick.len() as u8);
+        bytes.extend_from_slice(nick.as_bytes());
+        bytes.extend_from_slice(text.as_bytes());
+        bytes
+    }
+
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let (&len, rest) = payload.split_first()?;
+        let (nick, text) = rest.split_at_checked(len as usize)?;
+        Some(Self {
+            nick: String::from_utf8_lossy(nick).into_owned(),
+            text: String::from_utf8_lossy(text).into_owned(),
+        })
+    }
+}
+
+/// a line as typed, `/msg <nick|#id> text` for a single station, the file
+/// commands and anything else for everyone
+pub fn request(line: &str) -> Result<Request, &'static str> {
+    match line.trim_end() {
+        "/accept" => return Ok(Request::Answer { accept: true }),
+        "/decline" => return Ok(Request::Answer { accept: false }),
+        _ => {}
+    }
+    if let Some(rest) = line.strip_prefix("/send ") {
+        let usage = "usage: /send <nick|#id> <path>";
+        let (who, path) = rest.trim_start().split_once(' ').ok_or(usage)?;
+        let to = parse_station(who).ok_or(usage)?;
+        let path = path.trim();
+        if path.is_empty() {
+            return Err(usage);
+        }
+        return Ok(Request::SendFile {
+            to,
+            path: PathBuf::from(path),
+        });
+    }
+    let Some(rest) = line.strip_prefix("/msg ") else {
+        return Ok(Request::Send {
+            to: BROADCAST,
+            text: line.to_string(),
+        });
+    };
+    let usage = "usage: /msg <nick|#id> text";
+    let (who, text) = rest.trim_start().split_once(' ').ok_or(usage)?;
+    let to = parse_station(who).ok_or(usage)?;
+    let text = text.trim_start();
+    if text.is_empty() {
+        return Err(usage);
+    }
+    Ok(Request::Send {
+        to,
+        text: text.to_string(),
+    })
+}
+
+/// nickname of a neighbor, its station id if no beacon told it yet
+pub fn label(neighbor: &Neighbor) -> String {
+    neighbor
+        .nick
+        .clone()
+        .unwrap_or_else(|| format_station(neighbor.station))
+}
+
+fn truncate(text: &str, max: usize) -> &str {
+    let mut end = max.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    audio: AudioOptions,
+    calibration: Option<Calibration>,
+    link: LinkConfig,
+    arq: ArqConfig,
+    mac: MacConfig,
+    nick: &str,
+    station: Station,
+    plain: bool,
+) -> Result<(), Box<dyn Error>> {
+    let nick = truncate(nick, MAX_NICK).to_string();
+    let (requests, events, worker) =
+        worker::spawn(audio, calibration, link, arq, mac, nick.clone(), station);
+    if plain {
+        run_plain(requests, &events)?;
+    } else {
+        tui::run(requests, &events, &nick, station, link)?;
+        // the terminal is back, show what still holds up the exit
+        for event in &events {
+            match event {
+                Event::Log(line) => eprintln!("{line}"),
+                Event::Stopped(_) => break,
+                _ => {}
+            }
+        }
+    }
+    // lets the worker play what's left and close the backend, files included
+    let _ = worker.join();
+    Ok(())
+}
+
+// lines typed on stdin go out, messages received are printed as `nick: text`
+// and stations as they come into earshot
+fn run_plain(
+    requests: std::sync::mpsc::Sender<Request>,
+    events: &std::sync::mpsc::Receiver<Event>,
+) -> Result<(), Box<dyn Error>> {
+    // the worker finishes sending once stdin closes and this sender drops
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            match request(&line) {
+                Ok(request) => {
+                    if requests.send(request).is_err() {
+                        break;
+                    }
+                }
+                Err(usage) => eprintln!("{usage}"),
+            }
+        }
+    });
+    let mut nearby = HashSet::new();
+    for event in events {
+        match event {
+            Event::Received {
+                message, direct, ..
+            } if direct => println!("{} (direct): {}", message.nick, message.text),
+            Event::Received { message, .. } => println!("{}: {}", message.nick, message.text),
+            Event::Nearby { neighbors, .. } => {
+                for neighbor in neighbors {
+                    if nearby.insert(neighbor.station) {
+                        eprintln!(
+                            "{} ({}) is nearby, {:.1} dB",
+                            label(&neighbor),
+                            format_station(neighbor.station),
+                            neighbor.snr_db
+                        );
+                    }
+                }
+            }
+            Event::Offered { from, name, size } => {
+                eprintln!("{from} offers {name}, {size} bytes, /accept or /decline");
+            }
+            Event::Delivery(delivery) if delivery.status == DeliveryStatus::Failed => {
+                eprintln!(
+                    "message {} not acknowledged after {} attempts",
+                    delivery.id, delivery.attempts
+                );
+            }
+            Event::Log(line) => eprintln!("{line}"),
+            Event::Stopped(Some(err)) => return Err(err.into()),
+            Event::Stopped(None) => break,
+            Event::Delivery(_) | Event::Status(_) => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_round_trip() {
+        let message = Message {
+            nick: "wren".into(),
+            text: "anyone there?".into(),
+        };
+        assert_eq!(Message::decode(&message.encode()), Some(message));
+        assert_eq!(Message::decode(&[5, b'a']), None);
+
+        // cut on character boundaries
+        let long = Message {
+            nick: "é".repeat(20),
+            text: "ü".repeat(MAX_MESSAGE),
+        };
+        let bytes = long.encode();
+        assert!(bytes.len() <= MAX_MESSAGE);
+        let decoded = Message::decode(&bytes).unwrap();
+        assert_eq!(decoded.nick, "é".repeat(16));
+        assert!(decoded.text.chars().all(|c| c == 'ü'));
+    }
+
+    #[test]
+    fn test_direct_messages() {
+        let Ok(Request::Send { to, text }) = request("/msg Wren  hi there") else {
+            panic!("expected a direct message")
+        };
+        assert_eq!(to, chirp::station::station_id("wren"));
+        assert_eq!(text, "hi there");
+        assert!(matches!(
+            request("/msg #00ff hi"),
+            Ok(Request::Send { to: 0x00ff, .. })
+        ));
+        assert!(matches!(
+            request("hello /msg"),
+            Ok(Request::Send { to: BROADCAST, .. })
+        ));
+        assert!(request("/msg wren").is_err());
+        assert!(request("/msg #xyz hi").is_err());
+    }
+
+    #[test]
+    fn test_file_commands() {
+        let Ok(Request::SendFile { to, path }) = request("/send wren  notes/todo list.txt") else {
+            panic!("expected a file offer")
+        };
+        assert_eq!(to, chirp::station::station_id("wren"));
+        assert_eq!(path, PathBuf::from("notes/todo list.txt"));
+        assert!(request("/send wren").is_err());
+        assert!(matches!(
+            request("/accept"),
+            Ok(Request::Answer { accept: true })
+        ));
+        assert!(matches!(
+            request("/decline "),
+            Ok(Request::Answer { accept: false })
+        ));
+        assert!(matches!(
+            request("/accepted it"),
+            Ok(Request::Send { to: BROADCAST, .. })
+        ));
+    }
+}
@@ -0,0 +1,397 @@
+//! Full-screen chat: message history on top with the stations nearby next
+//! to it, link status and log below, the input line at the bottom.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    sync::mpsc::{Receiver, Sender, TryRecvError},
+    time::Duration,
+};
+
+use chirp::{
+    arq::{DeliveryStatus, MessageId},
+    link::LinkConfig,
+    station::{BROADCAST, Neighbor, Station, format_station},
+};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event as TermEvent, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Wrap},
+};
+
+use crate::chat::{
+    label, request,
+    worker::{Event, Request, Status},
+};
+
+// how long to wait for a key before looking at the worker again
+const TICK: Duration = Duration::from_millis(50);
+// log lines kept, the pane shows the newest that fit
+const LOG_LINES: usize = 200;
+// columns of the nearby pane
+const NEARBY_WIDTH: u16 = 30;
+// nicknames get one of these, the same one every time
+const PALETTE: [Color; 6] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Magenta,
+    Color::LightBlue,
+    Color::LightRed,
+];
+
+pub fn run(
+    requests: Sender<Request>,
+    events: &Receiver<Event>,
+    nick: &str,
+    station: Station,
+    link: LinkConfig,
+) -> io::Result<()> {
+    let mut app = App {
+        title: format!(
+            " chirp · {} · {} · {} ",
+            format_station(station),
+            link.keying.name(),
+            link.coding
+        ),
+        nick: nick.to_string(),
+        requests: Some(requests),
+        history: Vec::new(),
+        own: HashMap::new(),
+        next_id: 0,
+        log: VecDeque::new(),
+        nearby: Vec::new(),
+        now: Duration::ZERO,
+        input: String::new(),
+        status: Status::default(),
+        snr_db: None,
+        scroll: 0,
+        stopped: false,
+    };
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal, events);
+    ratatui::restore();
+    result
+}
+
+struct App {
+    title: String,
+    nick: String,
+    requests: Option<Sender<Request>>, // dropped to stop the worker
+    history: Vec<Line<'static>>,
+    own: HashMap<MessageId, usize>, // history line of each message we sent
+    next_id: MessageId,             // the worker's id of the next one
+    log: VecDeque<String>,
+    nearby: Vec<Neighbor>, // most recently heard first
+    now: Duration,         // the worker's clock when `nearby` came
+    input: String,
+    status: Status,
+    snr_db: Option<f32>, // of the last message received
+    scroll: usize,       // history lines hidden below the bottom
+    stopped: bool,       // the worker is gone
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut DefaultTerminal, events: &Receiver<Event>) -> io::Result<()> {
+        loop {
+            loop {
+                match events.try_recv() {
+                    Ok(event) => self.handle(event),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.stopped = true;
+                        break;
+                    }
+                }
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(TICK)? {
+                continue;
+            }
+            let TermEvent::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(());
+                }
+                KeyCode::Enter => self.submit(),
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Char(c) => self.input.push(c),
+                KeyCode::PageUp | KeyCode::Up => {
+                    self.scroll = (self.scroll + 1).min(self.history.len().saturating_sub(1));
+                }
+                KeyCode::PageDown | KeyCode::Down => self.scroll = self.scroll.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Received {
+                message,
+                direct,
+                snr_db,
+            } => {
+                self.snr_db = Some(snr_db);
+                let to = direct.then_some("you");
+                self.say(&message.nick, to, &message.text, false);
+            }
+            Event::Delivery(delivery) => {
+                let (mark, style) = match delivery.status {
+                    DeliveryStatus::Sent => (" ·", Style::new().dark_gray()),
+                    DeliveryStatus::Acknowledged { by } => {
+                        self.log(format!(
+                            "message {} acknowledged by {}, {} attempts",
+                            delivery.id,
+                            self.label(by),
+                            delivery.attempts
+                        ));
+                        (" ✓", Style::new().green())
+                    }
+                    DeliveryStatus::Failed => {
+                        self.log(format!(
+                            "message {} not acknowledged after {} attempts",
+                            delivery.id, delivery.attempts
+                        ));
+                        (" ✗", Style::new().red())
+                    }
+                };
+                if let Some(line) = self.own.get(&delivery.id).map(|&i| &mut self.history[i]) {
+                    // the last span is the mark
+                    line.spans.pop();
+                    line.spans.push(Span::styled(mark, style));
+                }
+            }
+            Event::Offered { from, name, size } => {
+                self.history.push(Line::from(vec![
+                    Span::styled(from.clone(), Style::new().fg(color(&from))),
+                    format!(" offers {name}, {size} bytes, /accept or /decline").dark_gray(),
+                ]));
+                self.scroll = 0;
+            }
+            Event::Status(status) => self.status = status,
+            Event::Nearby { now, neighbors } => {
+                self.now = now;
+                self.nearby = neighbors;
+            }
+            Event::Log(line) => self.log(line),
+            Event::Stopped(err) => {
+                self.stopped = true;
+                self.requests = None;
+                self.log(match err {
+                    Some(err) => format!("audio stopped: {err}"),
+                    None => "audio stopped".into(),
+                });
+            }
+        }
+    }
+
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        if line.trim().is_empty() {
+            return;
+        }
+        let (to, text) = match request(&line) {
+            Ok(Request::Send { to, text }) => (to, text),
+            // nothing to show until the worker says how it went
+            Ok(other) => {
+                if self
+                    .requests
+                    .as_ref()
+                    .is_none_or(|requests| requests.send(other).is_err())
+                {
+                    self.log("not connected, request dropped".into());
+                }
+                return;
+            }
+            Err(usage) => {
+                self.log(usage.into());
+                return;
+            }
+        };
+        let recipient = (to != BROADCAST).then(|| self.label(to));
+        let sent = Request::Send {
+            to,
+            text: text.clone(),
+        };
+        match &self.requests {
+            Some(requests) if requests.send(sent).is_ok() => {
+                let nick = self.nick.clone();
+                self.say(&nick, recipient.as_deref(), &text, true);
+                self.own.insert(self.next_id, self.history.len() - 1);
+                self.next_id += 1;
+                self.scroll = 0;
+            }
+            _ => self.log("not connected, message dropped".into()),
+        }
+    }
+
+    // `to` is set for direct messages
+    fn say(&mut self, nick: &str, to: Option<&str>, text: &str, own: bool) {
+        let mut style = Style::new().fg(color(nick)).add_modifier(Modifier::BOLD);
+        if own {
+            style = style.add_modifier(Modifier::ITALIC);
+        }
+        let mut spans = vec![Span::styled(nick.to_string(), style)];
+        if let Some(to) = to {
+            spans.push(Span::styled(format!(" → {to}"), Style::new().dark_gray()));
+        }
+        spans.push(Span::raw(": "));
+        spans.push(Span::raw(text.to_string()));
+        if own {
+            // waiting for its turn, see `Event::Delivery`
+            spans.push(Span::styled(" …", Style::new().dark_gray()));
+        }
+        self.history.push(Line::from(spans));
+    }
+
+    // nickname of a station if it's nearby, its id otherwise
+    fn label(&self, station: Station) -> String {
+        self.nearby
+            .iter()
+            .find(|neighbor| neighbor.station == station)
+            .map_or_else(|| format_station(station), label)
+    }
+
+    fn log(&mut self, line: String) {
+        if self.log.len() == LOG_LINES {
+            self.log.pop_front();
+        }
+        self.log.push_back(line);
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [history, status, log, input] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(6),
+            Constraint::Length(3),
+        ])
+        .areas(frame.area());
+        let [history, nearby] =
+            Layout::horizontal([Constraint::Min(20), Constraint::Length(NEARBY_WIDTH)])
+                .areas(history);
+        self.draw_history(frame, history);
+        self.draw_nearby(frame, nearby);
+        frame.render_widget(Paragraph::new(self.status_line()), status);
+
+        let log_block = Block::bordered().title(" log ").dark_gray();
+        let shown = log_block.inner(log).height as usize;
+        let lines: Vec<Line> = self
+            .log
+            .iter()
+            .skip(self.log.len().saturating_sub(shown))
+            .map(|line| Line::raw(line.as_str()))
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(log_block), log);
+
+        let input_block = Block::bordered().title(format!(" {} ", self.nick));
+        let inner = input_block.inner(input);
+        // keep the end of a long line in view
+        let width = inner.width.saturating_sub(1) as usize;
+        let chars = self.input.chars().count();
+        let visible: String = self
+            .input
+            .chars()
+            .skip(chars.saturating_sub(width))
+            .collect();
+        frame.set_cursor_position((inner.x + visible.chars().count() as u16, inner.y));
+        frame.render_widget(Paragraph::new(visible).block(input_block), input);
+    }
+
+    // newest lines at the bottom, as many as fit once wrapped
+    fn draw_history(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(self.title.as_str());
+        let inner = block.inner(area);
+        let width = inner.width.max(1) as usize;
+        let end = self.history.len().saturating_sub(self.scroll);
+        let mut rows = 0;
+        let mut start = end;
+        while start > 0 {
+            let needed = self.history[start - 1].width().div_ceil(width).max(1);
+            if rows + needed > inner.height as usize {
+                break;
+            }
+            rows += needed;
+            start -= 1;
+        }
+        frame.render_widget(
+            Paragraph::new(self.history[start..end].to_vec())
+                .block(block)
+                .wrap(Wrap { trim: false }),
+            area,
+        );
+    }
+
+    // who's in earshot, with how long ago and how well they were heard
+    fn draw_nearby(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" nearby ");
+        let lines: Vec<Line> = if self.nearby.is_empty() {
+            vec![Line::raw("nobody heard yet").dark_gray()]
+        } else {
+            self.nearby
+                .iter()
+                .map(|neighbor| {
+                    let name = label(neighbor);
+                    let age = self.now.saturating_sub(neighbor.last_heard).as_secs();
+                    Line::from(vec![
+                        Span::styled(name.clone(), Style::new().fg(color(&name))),
+                        format!(" {}", format_station(neighbor.station)).dark_gray(),
+                        format!(" {age}s {:.0} dB", neighbor.snr_db).into(),
+                    ])
+                })
+                .collect()
+        };
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn status_line(&self) -> Line<'static> {
+        let mut spans = Vec::new();
+        spans.push(if self.stopped {
+            " ■ stopped ".white().on_red()
+        } else if self.status.busy {
+            " ● carrier busy ".black().on_yellow()
+        } else {
+            " ○ quiet ".black().on_green()
+        });
+        if let Some(progress) = self.status.sending {
+            spans.push(format!("  ▶ sending {:.0}%", 100.0 * progress).yellow());
+        }
+        if self.status.waiting {
+            spans.push("  ⧗ waiting for a quiet channel".yellow());
+        }
+        if let Some(progress) = self.status.transfer {
+            spans.push(format!("  ⇅ file {:.0}%", 100.0 * progress).cyan());
+        }
+        if self.status.queued > 0 {
+            spans.push(format!("  {} queued", self.status.queued).dark_gray());
+        }
+        if let Some(snr) = self.snr_db {
+            spans.push(format!("  last snr {snr:.1} dB").into());
+        }
+        if self.scroll > 0 {
+            spans.push(format!("  ↑ {} newer", self.scroll).dark_gray());
+        }
+        Line::from(spans)
+    }
+}
+
+// stable color per nickname, FNV-1a over its bytes
+fn color(nick: &str) -> Color {
+    let hash = nick.bytes().fold(0x811c_9dc5u32, |hash, b| {
+        (hash ^ b as u32).wrapping_mul(0x0100_0193)
+    });
+    PALETTE[hash as usize % PALETTE.len()]
+}
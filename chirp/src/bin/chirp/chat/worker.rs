@@ -0,0 +1,597 @@
+//! Audio backend and modem on a thread of their own, so neither the
+//! terminal nor a slow reader ever stalls the sound card. Beacons go out
+//! from here too, whenever nothing else is waiting, and files offered to us
+//! are saved to the working directory.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, OpenOptions},
+    hash::{BuildHasher, RandomState},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use chirp::{
+    arq::{Arq, ArqConfig, Delivery, DeliveryStatus, MessageId},
+    audio::AudioBackend,
+    calibrate::Calibration,
+    frame::Flags,
+    link::{self, LinkConfig, Transmitter},
+    mac::{Mac, MacConfig},
+    station::{BEACON_INTERVAL, BROADCAST, Neighbor, Presence, Station, format_station},
+    transfer::{self, Failure, Key, Offer, TransferEvent, Transfers},
+};
+
+use crate::{
+    chat::{Message, label},
+    commands::POLL_INTERVAL,
+    options::AudioOptions,
+};
+
+// capture ignored after our own transmission ends, covers input latency
+const ECHO_SECONDS: f32 = 0.1;
+// between neighbor list updates
+const NEARBY_INTERVAL: Duration = Duration::from_secs(1);
+// longest the worker keeps retrying unacknowledged messages after the
+// interface closed
+const DRAIN_LIMIT: Duration = Duration::from_secs(10);
+
+/// from the interface to the worker, dropping the sender stops it once the
+/// outbox is empty
+pub enum Request {
+    /// to a station or `BROADCAST`
+    Send { to: Station, text: String },
+    /// offer a file to a station
+    SendFile { to: Station, path: PathBuf },
+    /// answer the latest file offered to us
+    Answer { accept: bool },
+}
+
+/// from the worker to the interface
+pub enum Event {
+    Received {
+        message: Message,
+        direct: bool, // sent to us alone
+        snr_db: f32,
+    },
+    /// a message of ours changed status, ids count up from 0 in the order
+    /// of `Request::Send`
+    Delivery(Delivery),
+    /// a station offers us a file, see `Request::Answer`
+    Offered {
+        from: String,
+        name: String,
+        size: usize,
+    },
+    Status(Status),
+    /// stations heard recently, most recent first, `last_heard` is on the
+    /// same clock as `now`
+    Nearby {
+        now: Duration,
+        neighbors: Vec<Neighbor>,
+    },
+    Log(String),
+    /// the worker is gone, with the reason if it failed
+    Stopped(Option<String>),
+}
+
+/// what the link is doing right now
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Status {
+    pub busy: bool,            // someone else is on the air
+    pub sending: Option<f32>,  // share of the current frame played
+    pub waiting: bool,         // a frame is held back for a quiet channel
+    pub queued: usize,         // messages not acknowledged yet
+    pub transfer: Option<f32>, // share of the oldest file transfer done
+}
+
+pub fn spawn(
+    audio: AudioOptions,
+    calibration: Option<Calibration>,
+    link: LinkConfig,
+    arq: ArqConfig,
+    mac: MacConfig,
+    nick: String,
+    station: Station,
+) -> (Sender<Request>, Receiver<Event>, JoinHandle<()>) {
+    let (requests, inbox) = mpsc::channel();
+    let (events, updates) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        // backends may not move between threads, open it on this one
+        let result = audio
+            .open_modem(&link, calibration.as_ref())
+            .map_err(|err| err.to_string())
+            .and_then(|mut backend| {
+                Worker::new(link, arq, mac, nick, station, &events)
+                    .run(backend.as_mut(), &inbox)
+                    .map_err(|err| err.to_string())
+            });
+        let _ = events.send(Event::Stopped(result.err()));
+    });
+    (requests, updates, worker)
+}
+
+struct Worker<'a> {
+    nick: String,
+    events: &'a Sender<Event>,
+    transmitter: Transmitter,
+    receiver: link::Receiver,
+    arq: Arq,
+    deliveries: Receiver<Delivery>, // from `arq`, chat and transfers mixed
+    chat: HashMap<MessageId, MessageId>, // `arq` ids of chat messages pending, to ours
+    next_chat: MessageId,
+    mac: Mac,
+    presence: Presence,
+    transfers: Transfers,
+    offers: Vec<Offer>,                      // not answered yet, latest last
+    names: HashMap<Key, String>,             // of files on the way
+    parts: HashMap<Key, PathBuf>,            // where downloads are kept until complete
+    progress: BTreeMap<Key, (usize, usize)>, // bytes done and total
+    next_nearby: Duration,                   // on the clock of `arq`
+    outgoing: Vec<f32>,                      // samples of the frame being played
+    queued: usize,                           // of `outgoing` handed to the backend
+    muted: usize,                            // captured samples still to ignore
+    captured: u64,                           // samples so far, the clock of `arq`
+    status: Status,                          // last one reported
+    stream_errors: u64,                      // of the backend, logged so far
+}
+
+impl<'a> Worker<'a> {
+    fn new(
+        link: LinkConfig,
+        arq: ArqConfig,
+        mac: MacConfig,
+        nick: String,
+        station: Station,
+        events: &'a Sender<Event>,
+    ) -> Self {
+        // a fresh sequence every run, so a restart isn't taken for
+        // duplicates of the last one
+        let random = RandomState::new().hash_one(&nick);
+        let (delivered, deliveries) = mpsc::channel();
+        let arq = Arq::new(station, link, arq)
+            .with_sequence(random as u16)
+            .with_delivery_callback(move |delivery| {
+                let _ = delivered.send(delivery);
+            });
+        Self {
+            nick,
+            events,
+            transmitter: Transmitter::new(link).with_station(station),
+            receiver: link::Receiver::new(link),
+            arq,
+            deliveries,
+            chat: HashMap::new(),
+            next_chat: 0,
+            mac: Mac::new(link.keying, mac, random.rotate_left(32)),
+            presence: Presence::new(station, BEACON_INTERVAL, random >> 16),
+            transfers: Transfers::new(station, random.rotate_left(16)),
+            offers: Vec::new(),
+            names: HashMap::new(),
+            parts: HashMap::new(),
+            progress: BTreeMap::new(),
+            next_nearby: Duration::ZERO,
+            outgoing: Vec::new(),
+            queued: 0,
+            muted: 0,
+            captured: 0,
+            status: Status::default(),
+            stream_errors: 0,
+        }
+    }
+
+    fn run(
+        &mut self,
+        backend: &mut dyn AudioBackend,
+        inbox: &Receiver<Request>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let sample_rate = backend.sample_rate();
+        let echo = (ECHO_SECONDS * sample_rate as f32) as usize;
+        let mut open = true; // requests may still arrive
+        let mut give_up = Duration::MAX; // on the clock of `arq`, once closed
+        let mut block = [0.0; 1024];
+        loop {
+            let now = Duration::from_secs_f64(self.captured as f64 / sample_rate as f64);
+            match inbox.try_recv() {
+                Ok(Request::Send { to, text }) => {
+                    let message = Message {
+                        nick: self.nick.clone(),
+                        text,
+                    };
+                    let id = self.arq.send(to, message.encode())?;
+                    self.chat.insert(id, self.next_chat);
+                    self.next_chat += 1;
+                }
+                Ok(Request::SendFile { to, path }) => self.offer(to, path)?,
+                Ok(Request::Answer { accept }) => self.answer(accept)?,
+                Err(TryRecvError::Disconnected) if open => {
+                    open = false;
+                    give_up = now + DRAIN_LIMIT;
+                    if !self.arq.is_idle() {
+                        self.events.send(Event::Log(format!(
+                            "finishing {} messages, at most {} s",
+                            self.arq.queued(),
+                            DRAIN_LIMIT.as_secs()
+                        )))?;
+                    }
+                }
+                Err(TryRecvError::Disconnected | TryRecvError::Empty) => {}
+            }
+            if now >= give_up && !self.playing(backend) {
+                if self.arq.queued() > 0 {
+                    self.events.send(Event::Log(format!(
+                        "gave up on {} messages",
+                        self.arq.queued()
+                    )))?;
+                }
+                return Ok(());
+            }
+
+            for event in self.transfers.poll(&mut self.arq, now)? {
+                self.transfer(event)?;
+            }
+            if !self.playing(backend) {
+                // one frame at a time, so `started` below is about the last
+                // thing `arq` handed out
+                if self.mac.queued() == 0 {
+                    match self.arq.poll_transmit(now) {
+                        Some(out) => {
+                            let samples = self.transmitter.transmit_fragment(
+                                out.to,
+                                out.fragment,
+                                Flags::EMPTY,
+                                &out.payload,
+                            )?;
+                            self.mac.enqueue(samples);
+                        }
+                        None if !open && self.arq.is_idle() => return Ok(()),
+                        None if self.presence.poll_beacon(now) => {
+                            let samples = self
+                                .transmitter
+                                .transmit(Flags::BEACON, self.nick.as_bytes())?;
+                            self.mac.enqueue(samples);
+                        }
+                        None => {}
+                    }
+                }
+                // only start talking when nobody else is
+                if let Some(samples) = self.mac.poll() {
+                    // the retransmit timers run from here, not from
+                    // `poll_transmit`, the frame may have waited a while
+                    self.arq.started(now);
+                    self.outgoing = samples;
+                    self.queued = 0;
+                }
+            }
+            let pushed = backend.push(&self.outgoing[self.queued..])?;
+            self.queued += pushed;
+            if pushed > 0 && self.queued == self.outgoing.len() {
+                backend.finish();
+            }
+
+            // our own transmission comes right back through the microphone
+            let count = backend.pull(&mut block)?;
+            self.captured += count as u64;
+            if self.playing(backend) {
+                self.muted = echo;
+                self.receiver.reset();
+            } else if self.muted > 0 {
+                self.muted = self.muted.saturating_sub(count);
+            } else {
+                let (events, arq, presence) = (self.events, &mut self.arq, &mut self.presence);
+                let transfers = &mut self.transfers;
+                let mut files = Vec::new(); // transfer events, handled below
+                self.receiver.push(&block[..count], |reception| {
+                    let snr_db = reception.sync.snr_db();
+                    let frame = match reception.frame {
+                        Ok(frame) => frame,
+                        Err(err) => {
+                            let _ = events
+                                .send(Event::Log(format!("lost a frame at {snr_db:.1} dB: {err}")));
+                            return;
+                        }
+                    };
+                    let header = frame.header;
+                    if header.flags.contains(Flags::BEACON) {
+                        let nick = String::from_utf8_lossy(&frame.payload);
+                        presence.heard(header.source, Some(&nick), snr_db, now);
+                        return;
+                    }
+                    presence.heard(header.source, None, snr_db, now);
+                    let received = match arq.receive(&header, &frame.payload, now) {
+                        Ok(received) => received,
+                        Err(err) => {
+                            let _ = events.send(Event::Log(format!(
+                                "frame {} isn't for chat: {err}",
+                                header.sequence
+                            )));
+                            return;
+                        }
+                    };
+                    for data in received {
+                        if transfer::is_packet(&data.data) {
+                            match transfers.receive(data.from, &data.data, now) {
+                                Ok(happened) => files.extend(happened),
+                                Err(err) => {
+                                    let _ = events.send(Event::Log(format!(
+                                        "{} sent a garbled file transfer: {err}",
+                                        format_station(data.from)
+                                    )));
+                                }
+                            }
+                            continue;
+                        }
+                        let _ = events.send(match Message::decode(&data.data) {
+                            Some(message) => Event::Received {
+                                message,
+                                direct: data.to != BROADCAST,
+                                snr_db,
+                            },
+                            None => Event::Log(format!(
+                                "{} sent a garbled message",
+                                format_station(data.from)
+                            )),
+                        });
+                    }
+                });
+                self.mac.push(&block[..count], self.receiver.is_receiving());
+                for event in files {
+                    self.transfer(event)?;
+                }
+            }
+            self.deliveries()?;
+            self.report(backend)?;
+            if now >= self.next_nearby {
+                self.next_nearby = now + NEARBY_INTERVAL;
+                let neighbors = self.presence.nearby(now).into_iter().cloned().collect();
+                self.events.send(Event::Nearby { now, neighbors })?;
+            }
+
+            if count == 0 && !backend.wait(POLL_INTERVAL) {
+                // capture is over for good, keep the clock going for the
+                // timers and whatever is still typed
+                thread::sleep(POLL_INTERVAL);
+                let silence =
+                    vec![0.0; (POLL_INTERVAL.as_secs_f64() * sample_rate as f64) as usize];
+                self.captured += silence.len() as u64;
+                self.mac.push(&silence, false);
+            }
+        }
+    }
+
+    // chat messages go to the interface, the rest belong to file transfers
+    fn deliveries(&mut self) -> Result<(), mpsc::SendError<Event>> {
+        while let Ok(delivery) = self.deliveries.try_recv() {
+            if let Some(&id) = self.chat.get(&delivery.id) {
+                if delivery.status != DeliveryStatus::Sent {
+                    self.chat.remove(&delivery.id);
+                }
+                self.events
+                    .send(Event::Delivery(Delivery { id, ..delivery }))?;
+            } else if let Some(event) = self.transfers.delivered(&delivery) {
+                self.transfer(event)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn offer(&mut self, to: Station, path: PathBuf) -> Result<(), mpsc::SendError<Event>> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let offered = fs::read(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|data| {
+                let size = data.len();
+                let key = self
+                    .transfers
+                    .offer(to, &name, data)
+                    .map_err(|err| err.to_string())?;
+                Ok((key, size))
+            });
+        let line = match offered {
+            Ok((key, size)) => {
+                self.names.insert(key, name.clone());
+                format!("offering {name} to {}, {size} bytes", self.label(to))
+            }
+            Err(err) => format!("can't send {}: {err}", path.display()),
+        };
+        self.events.send(Event::Log(line))
+    }
+
+    fn answer(&mut self, accept: bool) -> Result<(), mpsc::SendError<Event>> {
+        let Some(offer) = self.offers.pop() else {
+            return self.events.send(Event::Log("no file offered".into()));
+        };
+        let (key, from) = (offer.key, self.label(offer.key.from));
+        if !accept {
+            let _ = self.transfers.decline(key);
+            return self
+                .events
+                .send(Event::Log(format!("declined {} from {from}", offer.name)));
+        }
+        // what an earlier attempt at the same file left behind
+        let part = part_path(&offer);
+        let kept = fs::read(&part).unwrap_or_default();
+        let line = match kept.len() {
+            0 => format!("accepted {} from {from}", offer.name),
+            len => format!(
+                "accepted {} from {from}, resuming at {len} bytes",
+                offer.name
+            ),
+        };
+        self.names.insert(key, offer.name);
+        self.parts.insert(key, part);
+        self.progress
+            .insert(key, (kept.len().min(offer.size), offer.size));
+        self.events.send(Event::Log(line))?;
+        match self.transfers.accept(key, kept) {
+            Ok(events) => events
+                .into_iter()
+                .try_for_each(|event| self.transfer(event)),
+            Err(err) => self.events.send(Event::Log(err.to_string())),
+        }
+    }
+
+    // keeps the files on disk up to date and tells the interface
+    fn transfer(&mut self, event: TransferEvent) -> Result<(), mpsc::SendError<Event>> {
+        let name = |key: &Key| self.names.get(key).cloned().unwrap_or_default();
+        let line = match event {
+            TransferEvent::Offered(offer) => {
+                let event = Event::Offered {
+                    from: self.label(offer.key.from),
+                    name: offer.name.clone(),
+                    size: offer.size,
+                };
+                self.offers.push(offer);
+                return self.events.send(event);
+            }
+            TransferEvent::Accepted { key, offset } => {
+                let size = self.progress.get(&key).map_or(0, |&(_, size)| size);
+                self.progress.insert(key, (offset, size));
+                match offset {
+                    0 => format!("{} accepted", name(&key)),
+                    _ => format!("{} accepted, resuming at {offset} bytes", name(&key)),
+                }
+            }
+            TransferEvent::Declined { key } => {
+                let line = format!("{} declined", name(&key));
+                self.finish(key);
+                line
+            }
+            TransferEvent::Progress { key, done, size } => {
+                // kept in case the transfer is cut short, the part file
+                // already holds everything before the last progress
+                let written = self.progress.get(&key).map_or(0, |&(done, _)| done);
+                if let (Some(part), Some(data)) =
+                    (self.parts.get(&key), self.transfers.partial(key))
+                    && let Some(chunk) = data.get(written..done)
+                    && let Err(err) = append(part, chunk)
+                {
+                    self.events
+                        .send(Event::Log(format!("can't keep {}: {err}", part.display())))?;
+                }
+                self.progress.insert(key, (done, size));
+                format!("{}: {done} of {size} bytes", name(&key))
+            }
+            TransferEvent::Received { offer, data } => {
+                let path = free_path(&offer.name);
+                let line = match fs::write(&path, &data) {
+                    Ok(()) => format!(
+                        "saved {} from {}, {} bytes, SHA-256 checked",
+                        path.display(),
+                        self.label(offer.key.from),
+                        offer.size
+                    ),
+                    Err(err) => format!("can't save {}: {err}", path.display()),
+                };
+                if let Some(part) = self.parts.get(&offer.key) {
+                    let _ = fs::remove_file(part);
+                }
+                self.finish(offer.key);
+                line
+            }
+            TransferEvent::Delivered { key } => {
+                let line = format!("{} delivered, SHA-256 checked", name(&key));
+                self.finish(key);
+                line
+            }
+            TransferEvent::Failed { key, failure } => {
+                let upload = key.from == self.transfers.station();
+                let line = match (upload, failure) {
+                    (true, Failure::HashMismatch) => format!("{}: {failure}", name(&key)),
+                    (true, _) => format!("{}: {failure}, send it again to resume", name(&key)),
+                    (false, Failure::HashMismatch) => {
+                        // not worth resuming from
+                        if let Some(part) = self.parts.get(&key) {
+                            let _ = fs::remove_file(part);
+                        }
+                        format!("{}: {failure}, dropped", name(&key))
+                    }
+                    (false, _) => format!("{}: {failure}, kept what arrived", name(&key)),
+                };
+                self.finish(key);
+                line
+            }
+        };
+        self.events.send(Event::Log(line))
+    }
+
+    fn finish(&mut self, key: Key) {
+        self.names.remove(&key);
+        self.parts.remove(&key);
+        self.progress.remove(&key);
+    }
+
+    // nickname of a station heard from, its id otherwise
+    fn label(&self, station: Station) -> String {
+        self.presence
+            .neighbor(station)
+            .map_or_else(|| format_station(station), label)
+    }
+
+    fn playing(&self, backend: &dyn AudioBackend) -> bool {
+        self.queued < self.outgoing.len() || backend.pending() > 0
+    }
+
+    // send a status update when anything visible changed
+    fn report(&mut self, backend: &dyn AudioBackend) -> Result<(), mpsc::SendError<Event>> {
+        let sending = self.playing(backend).then(|| {
+            let played = self.queued.saturating_sub(backend.pending());
+            // whole percent steps, so playback doesn't flood the interface
+            (100 * played / self.outgoing.len().max(1)) as f32 / 100.0
+        });
+        let status = Status {
+            busy: self.receiver.is_receiving() || self.mac.is_busy(),
+            sending,
+            waiting: self.mac.queued() > 0,
+            queued: self.chat.len(),
+            transfer: self
+                .progress
+                .values()
+                .next()
+                .map(|&(done, size)| (100 * done / size.max(1)) as f32 / 100.0),
+        };
+        if status != self.status {
+            self.status = status;
+            self.events.send(Event::Status(status))?;
+        }
+        let errors = backend.stats().errors;
+        if errors > self.stream_errors {
+            let new = errors - self.stream_errors;
+            self.stream_errors = errors;
+            self.events.send(Event::Log(format!(
+                "the sound card reported {new} stream errors"
+            )))?;
+        }
+        Ok(())
+    }
+}
+
+// hidden next to where the download ends up, by content so a new offer of
+// the same file finds it
+fn part_path(offer: &Offer) -> PathBuf {
+    let hash: String = offer.hash[..4].iter().map(|b| format!("{b:02x}")).collect();
+    PathBuf::from(format!(".{}.{hash}.part", offer.name))
+}
+
+// add the newest bytes of a download to its part file
+fn append(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(bytes)
+}
+
+// `name` in the working directory, numbered if it's taken
+fn free_path(name: &str) -> PathBuf {
+    std::iter::once(PathBuf::from(name))
+        .chain((1..).map(|n| PathBuf::from(format!("{n}-{name}"))))
+        .find(|path| !path.exists())
+        .unwrap_or_default()
+}
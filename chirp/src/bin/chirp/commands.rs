@@ -0,0 +1,357 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    f32::consts::TAU,
+    hash::{BuildHasher, RandomState},
+    io::Write,
+    path::Path,
+    time::Duration,
+};
+
+use chirp::{
+    arq::MAX_FRAGMENTS,
+    audio::{self, AudioBackend, AudioResult, normalize_wave, play_and_record, write_wav},
+    calibrate::{Calibration, CalibrationStore, MIN_SNR_DB, SoundingPlan},
+    frame::{Flags, Fragment, MAX_PAYLOAD},
+    link::{LinkConfig, Receiver, Reception, Transmitter},
+    mac::{Mac, MacConfig},
+    profile::Profiles,
+    station::{BROADCAST, Station, format_station},
+};
+use chirp_modem::keying::Keying;
+
+// how long to block for captured samples before checking on everything else
+pub const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+pub fn send(
+    backend: &mut dyn AudioBackend,
+    link: LinkConfig,
+    message: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    if message.is_empty() {
+        return Err("nothing to send, the message is empty".into());
+    }
+    let count = message.len().div_ceil(MAX_PAYLOAD);
+    if count > MAX_FRAGMENTS {
+        return Err(format!(
+            "message too long: {} bytes, at most {} fit {MAX_FRAGMENTS} frames",
+            message.len(),
+            MAX_FRAGMENTS * MAX_PAYLOAD
+        )
+        .into());
+    }
+    let mut transmitter = Transmitter::new(link);
+    let mut samples = Vec::new();
+    for (index, chunk) in message.chunks(MAX_PAYLOAD).enumerate() {
+        let fragment = Fragment {
+            index: index as u8,
+            count: count as u8,
+        };
+        samples.extend(transmitter.transmit_fragment(BROADCAST, fragment, Flags::EMPTY, chunk)?);
+    }
+    let len = samples.len();
+    let mut mac = Mac::new(
+        link.keying,
+        MacConfig::default(),
+        RandomState::new().hash_one(message),
+    );
+    mac.enqueue(samples);
+    let samples = wait_for_channel(backend, link, &mut mac)?;
+    play(backend, &samples)?;
+    eprintln!(
+        "sent {count} frames, {:.2} s of {}",
+        len as f32 / backend.sample_rate() as f32,
+        link.keying.name(),
+    );
+    Ok(())
+}
+
+// listen until `mac` lets its next frame go
+fn wait_for_channel(
+    backend: &mut dyn AudioBackend,
+    link: LinkConfig,
+    mac: &mut Mac,
+) -> AudioResult<Vec<f32>> {
+    let mut receiver = Receiver::new(link);
+    let mut block = [0.0; 1024];
+    let mut waiting = false;
+    loop {
+        if let Some(samples) = mac.poll() {
+            return Ok(samples);
+        }
+        let mut count = backend.pull(&mut block)?;
+        if count == 0 && !backend.wait(POLL_INTERVAL) {
+            // nothing more will be captured, nobody left to wait for
+            block.fill(0.0);
+            count = block.len();
+        }
+        receiver.push(&block[..count], |_| {});
+        mac.push(&block[..count], receiver.is_receiving());
+        let heard = receiver.is_receiving() || mac.carrier_sense().is_active();
+        if heard && !waiting {
+            eprintln!("channel busy, waiting for it to clear");
+            waiting = true;
+        }
+    }
+}
+
+pub fn listen(backend: &mut dyn AudioBackend, link: LinkConfig) -> Result<(), Box<dyn Error>> {
+    let mut stdout = std::io::stdout().lock();
+    let mut receiver = Receiver::new(link);
+    let mut block = [0.0; 1024];
+    let mut fragments = HashMap::new(); // last one heard from each station
+    loop {
+        let count = backend.pull(&mut block)?;
+        let mut result = Ok(());
+        receiver.push(&block[..count], |reception| {
+            if let Ok(frame) = &reception.frame {
+                let (source, fragment) = (frame.header.source, frame.header.fragment);
+                for gap in missing_fragments(&mut fragments, source, fragment) {
+                    eprintln!("missing {gap} from {}", format_station(source));
+                }
+            }
+            if let Some(payload) = report(&reception)
+                && result.is_ok()
+            {
+                result = stdout.write_all(payload).and_then(|_| stdout.flush());
+            }
+        });
+        result?;
+        if count == 0 && !backend.wait(POLL_INTERVAL) {
+            // capture is over, unfinished messages stay that way
+            let sources: Vec<Station> = fragments.keys().copied().collect();
+            for source in sources {
+                for gap in missing_fragments(&mut fragments, source, Fragment::WHOLE) {
+                    eprintln!("missing {gap} from {}", format_station(source));
+                }
+            }
+            return Ok(());
+        }
+    }
+}
+
+// fragments skipped between the last one heard from `source` and `fragment`,
+// the rest of an unfinished message included
+fn missing_fragments(
+    last: &mut HashMap<Station, Fragment>,
+    source: Station,
+    fragment: Fragment,
+) -> Vec<String> {
+    let gap = |from: u8, to: u8, count: u8| match to.saturating_sub(from) {
+        0 => None,
+        1 => Some(format!("fragment {to} of {count}")),
+        _ => Some(format!("fragments {} to {to} of {count}", from + 1)),
+    };
+    let previous = last.insert(source, fragment).filter(|p| !p.is_last());
+    let (rest, start) = match previous {
+        Some(p) if p.count == fragment.count && fragment.index > p.index => {
+            (gap(p.index + 1, fragment.index, fragment.count), None)
+        }
+        Some(p) => (
+            gap(p.index + 1, p.count, p.count),
+            gap(0, fragment.index, fragment.count),
+        ),
+        None => (None, gap(0, fragment.index, fragment.count)),
+    };
+    rest.into_iter().chain(start).collect()
+}
+
+/// describe a reception on stderr, returns its payload if it decoded
+pub fn report(reception: &Reception) -> Option<&[u8]> {
+    let sync = format!(
+        "sync quality {:.2}, offset {:+.1} Hz",
+        reception.sync.quality, reception.sync.freq_offset
+    );
+    match &reception.frame {
+        Ok(frame) => {
+            let to = match frame.header.destination {
+                BROADCAST => "everyone".to_string(),
+                destination => format_station(destination),
+            };
+            let fragment = frame.header.fragment;
+            let part = match fragment.count {
+                1 => String::new(),
+                count => format!(", fragment {} of {count}", fragment.index + 1),
+            };
+            eprintln!(
+                "frame {} from {} to {to} with {sync}: {} bytes{part}, coding {}",
+                frame.header.sequence,
+                format_station(frame.header.source),
+                frame.payload.len(),
+                frame.header.coding,
+            );
+            Some(&frame.payload)
+        }
+        Err(err) => {
+            eprintln!("failed with {sync}: {err}");
+            None
+        }
+    }
+}
+
+/// play `samples` to the end, discarding whatever is captured meanwhile
+pub fn play(backend: &mut dyn AudioBackend, samples: &[f32]) -> AudioResult<()> {
+    let mut queued = 0;
+    let mut discard = [0.0; 1024];
+    while queued < samples.len() || backend.pending() > 0 {
+        let pushed = backend.push(&samples[queued..])?;
+        queued += pushed;
+        if pushed > 0 && queued == samples.len() {
+            backend.finish();
+        }
+        while backend.pull(&mut discard)? > 0 {}
+        backend.wait(POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+pub fn tone(
+    backend: &mut dyn AudioBackend,
+    freq: f32,
+    duration: f32,
+    amplitude: f32,
+    record: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let sample_rate = backend.sample_rate();
+    if !(0.0..sample_rate as f32 / 2.0).contains(&freq) {
+        return Err(format!("{freq} Hz is not below nyquist at {sample_rate} Hz").into());
+    }
+    let step = TAU * freq / sample_rate as f32;
+    let tone: Vec<f32> = (0..(duration * sample_rate as f32) as usize)
+        .map(|n| amplitude * (step * n as f32).sin())
+        .collect();
+    match record {
+        Some(path) => {
+            let mut recorded = play_and_record(backend, &tone)?;
+            normalize_wave(&mut recorded);
+            write_wav(path, sample_rate, &recorded)?;
+            eprintln!("recorded {} samples to {}", recorded.len(), path.display());
+        }
+        None => play(backend, &tone)?,
+    }
+    Ok(())
+}
+
+pub fn record(
+    backend: &mut dyn AudioBackend,
+    path: &Path,
+    duration: f32,
+    normalize: bool,
+) -> Result<(), Box<dyn Error>> {
+    let sample_rate = backend.sample_rate();
+    let wanted = (duration * sample_rate as f32) as usize;
+    let mut recorded = Vec::with_capacity(wanted);
+    let mut block = [0.0; 1024];
+    while recorded.len() < wanted {
+        let count = backend.pull(&mut block)?;
+        recorded.extend_from_slice(&block[..count]);
+        if count == 0 && !backend.wait(POLL_INTERVAL) {
+            break;
+        }
+    }
+    recorded.truncate(wanted);
+    if normalize {
+        normalize_wave(&mut recorded);
+    }
+    write_wav(path, sample_rate, &recorded)?;
+    let stats = backend.stats();
+    eprintln!(
+        "recorded {:.2} s to {}, {} overruns",
+        recorded.len() as f32 / sample_rate as f32,
+        path.display(),
+        stats.overruns,
+    );
+    Ok(())
+}
+
+pub fn devices() -> Result<(), Box<dyn Error>> {
+    let (inputs, outputs) = audio::cpal_backend::device_names()?;
+    println!("input devices:");
+    for name in inputs {
+        println!("  {name}");
+    }
+    println!("output devices:");
+    for name in outputs {
+        println!("  {name}");
+    }
+    Ok(())
+}
+
+pub fn profiles(profiles: &Profiles) -> Result<(), Box<dyn Error>> {
+    for name in profiles.names() {
+        match profiles.get(name) {
+            Ok(profile) => {
+                let link = profile.link;
+                println!(
+                    "{name:<20} {:<5} {:>6.0} bit/s  {:<15} {:<10} {}",
+                    link.keying.name(),
+                    link.keying.bit_rate(),
+                    band(link.keying),
+                    link.coding,
+                    profile.description,
+                );
+            }
+            Err(err) => println!("{name:<20} {err}"),
+        }
+    }
+    Ok(())
+}
+
+pub fn calibrate(
+    backend: &mut dyn AudioBackend,
+    plan: SoundingPlan,
+    device: &str,
+    save: bool,
+) -> Result<(), Box<dyn Error>> {
+    plan.validate()?;
+    eprintln!(
+        "sounding {device}, {} tones from {} to {} Hz, keep the room quiet",
+        plan.tones().len(),
+        plan.low_freq,
+        plan.high_freq,
+    );
+    let recorded = play_and_record(backend, &plan.samples())?;
+    let sounding = plan.analyze(&recorded);
+
+    println!(
+        "noise floor {:.1} dBFS, captured peak {:.2}",
+        sounding.noise_floor_db, sounding.peak
+    );
+    println!("{:>8} {:>8} {:>8}", "Hz", "gain dB", "snr dB");
+    for tone in &sounding.response {
+        let bar = "#".repeat((tone.snr_db.max(0.0) / 2.0) as usize);
+        let usable = if tone.snr_db >= MIN_SNR_DB { ' ' } else { '-' };
+        println!(
+            "{:>8} {:>8.1} {:>8.1} {usable}{bar}",
+            tone.freq, tone.gain_db, tone.snr_db
+        );
+    }
+
+    let calibration = Calibration::from(sounding);
+    match &calibration.recommendation {
+        Some(rec) => println!(
+            "carrier {} Hz, {} Hz wide, worst snr {:.1} dB, tx gain {:.2}, rx gain {:.2}",
+            rec.carrier, rec.bandwidth, rec.min_snr_db, rec.tx_gain, rec.rx_gain
+        ),
+        None => println!("no tone reached {MIN_SNR_DB} dB snr, check volume and cabling"),
+    }
+    if !save {
+        return Ok(());
+    }
+    let path = CalibrationStore::default_path().ok_or("no config directory to store it in")?;
+    let mut store = CalibrationStore::load(&path)?;
+    store.insert(device, calibration);
+    store.save(&path)?;
+    eprintln!("stored in {}", path.display());
+    Ok(())
+}
+
+// frequencies a keying puts on the air
+fn band(keying: Keying) -> String {
+    let (low, high) = match keying {
+        Keying::Amplitude(config) => return format!("{:.0} Hz", config.carrier_freq()),
+        Keying::Frequency(tones) => (tones.mark.min(tones.space), tones.mark.max(tones.space)),
+        Keying::MultiFrequency(config) => (config.tone(0), config.tone(config.order - 1)),
+    };
+    format!("{low}-{high} Hz")
+}
@@ -0,0 +1,252 @@
+//! `chirp`, ultrasonic messages between nearby computers from the command line.
+//!
+//! Every subcommand runs over the backend picked with `--backend`. The `wav`
+//! backend captures from `--wav-in` and plays into `--wav-out` instead of a
+//! sound card, so a session can be replayed exactly, e.g.
+//!
+//! ```text
+//! chirp send "hello" --backend wav --wav-out hello.wav
+//! chirp listen --backend wav --wav-in hello.wav
+//! ```
+
+mod chat;
+mod commands;
+mod options;
+
+use std::{error::Error, path::PathBuf};
+
+use chirp::{
+    arq::{ArqConfig, MAX_DATA, MAX_WINDOW},
+    audio::AudioBackend,
+    calibrate::SoundingPlan,
+    link::LinkConfig,
+    mac::MacConfig,
+    station::station_id,
+};
+use chirp_modem::{CARRIER_FREQ, Hz};
+use clap::{Parser, Subcommand};
+
+use crate::options::{AudioOptions, ModemOptions};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Ultrasonic messaging over speaker and microphone")]
+struct Cli {
+    #[command(flatten)]
+    audio: AudioOptions,
+
+    #[command(flatten)]
+    modem: ModemOptions,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Transmit a message, read from stdin when none is given
+    Send { text: Option<String> },
+
+    /// Write every payload received to stdout until the input ends
+    Listen,
+
+    /// Full-screen chat with everyone in earshot
+    Chat {
+        /// name shown to the others
+        #[arg(long, env = "USER", default_value = "anonymous")]
+        nick: String,
+
+        /// what the station id is derived from instead of the nickname, so
+        /// friends can find you under another name
+        #[arg(long)]
+        key: Option<String>,
+
+        /// plain lines on stdin and stdout instead of the full-screen interface
+        #[arg(long)]
+        plain: bool,
+
+        /// frames sent ahead of their acknowledgement, 1 is stop-and-wait
+        #[arg(
+            long,
+            default_value_t = 4,
+            value_parser = clap::value_parser!(u16).range(1..=MAX_WINDOW as i64),
+        )]
+        window: u16,
+
+        /// message bytes per frame, longer messages are split, shorter
+        /// frames survive a noisy channel more often
+        #[arg(
+            long,
+            default_value_t = ArqConfig::default().fragment_size as u16,
+            value_parser = clap::value_parser!(u16).range(32..=MAX_DATA as i64),
+        )]
+        fragment_size: u16,
+
+        /// chance to key in each quiet slot once the backoff is over, 1 sends
+        /// as soon as the channel clears
+        #[arg(
+            long,
+            default_value_t = MacConfig::default().persistence,
+            value_parser = parse_persistence,
+        )]
+        persistence: f32,
+    },
+
+    /// Play a sine tone, optionally recording meanwhile
+    Tone {
+        /// frequency in Hz
+        #[arg(long, default_value_t = CARRIER_FREQ as f32)]
+        freq: f32,
+
+        /// seconds to play
+        #[arg(long, default_value_t = 1.0)]
+        duration: f32,
+
+        /// peak amplitude, 1.0 is full scale
+        #[arg(long, default_value_t = 0.5)]
+        amplitude: f32,
+
+        /// also capture into this WAV file while playing
+        #[arg(long)]
+        record: Option<PathBuf>,
+    },
+
+    /// Capture into a WAV file
+    Record {
+        path: PathBuf,
+
+        /// seconds to capture
+        #[arg(long, default_value_t = 5.0)]
+        duration: f32,
+
+        /// stretch the recording to full scale
+        #[arg(long)]
+        normalize: bool,
+    },
+
+    /// List sound card devices
+    Devices,
+
+    /// List the link profiles available to `--profile`
+    Profiles,
+
+    /// Sound out speaker and microphone, then store the gains and carrier
+    /// that suit them
+    Calibrate {
+        /// lowest comb tone in Hz
+        #[arg(long, default_value_t = 16_000)]
+        low: Hz,
+
+        /// highest comb tone in Hz, 2 kHz short of nyquist when not given
+        #[arg(long)]
+        high: Option<Hz>,
+
+        /// spacing of the comb tones in Hz
+        #[arg(long, default_value_t = 250)]
+        step: Hz,
+
+        /// peak amplitude of the comb, 1.0 is full scale
+        #[arg(long, default_value_t = 0.5)]
+        level: f32,
+
+        /// show the results without storing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Send { ref text } => {
+            let (mut backend, link) = open_modem(&cli)?;
+            let message = match text {
+                Some(text) => text.as_bytes().to_vec(),
+                None => {
+                    let mut message = Vec::new();
+                    std::io::Read::read_to_end(&mut std::io::stdin(), &mut message)?;
+                    message
+                }
+            };
+            commands::send(backend.as_mut(), link, &message)
+        }
+        Command::Listen => {
+            let (mut backend, link) = open_modem(&cli)?;
+            commands::listen(backend.as_mut(), link)
+        }
+        Command::Chat {
+            ref nick,
+            ref key,
+            plain,
+            window,
+            fragment_size,
+            persistence,
+        } => {
+            let calibration = cli.audio.calibration()?;
+            let link = cli.modem.link(calibration.as_ref())?;
+            let mac = MacConfig {
+                persistence,
+                ..MacConfig::default()
+            };
+            let arq = ArqConfig {
+                fragment_size: fragment_size as usize,
+                access_delay: mac.max_wait(),
+                ..ArqConfig::sliding_window(window)
+            };
+            let station = station_id(key.as_deref().unwrap_or(nick));
+            let audio = cli.audio.clone();
+            chat::run(audio, calibration, link, arq, mac, nick, station, plain)
+        }
+        Command::Tone {
+            freq,
+            duration,
+            amplitude,
+            ref record,
+        } => commands::tone(
+            cli.audio.open()?.as_mut(),
+            freq,
+            duration,
+            amplitude,
+            record.as_deref(),
+        ),
+        Command::Record {
+            ref path,
+            duration,
+            normalize,
+        } => commands::record(cli.audio.open()?.as_mut(), path, duration, normalize),
+        Command::Devices => commands::devices(),
+        Command::Profiles => commands::profiles(&cli.modem.load_profiles()?),
+        Command::Calibrate {
+            low,
+            high,
+            step,
+            level,
+            dry_run,
+        } => {
+            let mut backend = cli.audio.open()?;
+            let defaults = SoundingPlan::new(backend.sample_rate());
+            let plan = SoundingPlan {
+                low_freq: low,
+                high_freq: high.unwrap_or(defaults.high_freq),
+                step,
+                level,
+                ..defaults
+            };
+            commands::calibrate(backend.as_mut(), plan, &cli.audio.device_key(), !dry_run)
+        }
+    }
+}
+
+// backend and link for the subcommands that run the modem
+fn open_modem(cli: &Cli) -> Result<(Box<dyn AudioBackend>, LinkConfig), Box<dyn Error>> {
+    let calibration = cli.audio.calibration()?;
+    let link = cli.modem.link(calibration.as_ref())?;
+    Ok((cli.audio.open_modem(&link, calibration.as_ref())?, link))
+}
+
+// a probability, zero would never send at all
+fn parse_persistence(s: &str) -> Result<f32, String> {
+    match s.parse() {
+        Ok(p) if p > 0.0 && p <= 1.0 => Ok(p),
+        _ => Err(format!("persistence {s} is not a probability above 0")),
+    }
+}
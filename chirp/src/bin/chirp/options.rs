@@ -0,0 +1,298 @@
+use std::{error::Error, path::PathBuf, str::FromStr};
+
+use chirp::{
+    audio::{
+        AudioBackend, AudioError, AudioResult, CpalBackend, CpalConfig, GainBackend, JackBackend,
+        ResampledBackend, WavBackend,
+    },
+    calibrate::{Calibration, CalibrationResult, CalibrationStore, device_key},
+    frame::Coding,
+    link::LinkConfig,
+    profile::{self, Profiles},
+};
+use chirp_modem::{
+    Hz, SAMPLE_RATE, config::ModemConfig, fsk::FskTones, keying::Keying, mfsk::MfskConfig,
+};
+use clap::{Args, ValueEnum};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// sound card through the platform's default host
+    Cpal,
+    /// a running JACK server, at whatever rate it runs
+    Jack,
+    /// capture from `--wav-in`, play into `--wav-out`
+    Wav,
+}
+
+/// where samples come from and go to
+#[derive(Debug, Clone, Args)]
+pub struct AudioOptions {
+    #[arg(long, value_enum, default_value_t = Backend::Cpal, global = true)]
+    pub backend: Backend,
+
+    /// capture device, the default input when not given
+    #[arg(long, global = true)]
+    pub input_device: Option<String>,
+
+    /// playback device, the default output when not given
+    #[arg(long, global = true)]
+    pub output_device: Option<String>,
+
+    /// samples per second
+    #[arg(long, default_value_t = SAMPLE_RATE, global = true)]
+    pub sample_rate: u32,
+
+    /// file the wav backend captures from
+    #[arg(long, global = true)]
+    pub wav_in: Option<PathBuf>,
+
+    /// file the wav backend plays into
+    #[arg(long, global = true)]
+    pub wav_out: Option<PathBuf>,
+
+    /// ignore what `chirp calibrate` stored for these devices
+    #[arg(long, global = true)]
+    pub uncalibrated: bool,
+}
+
+impl AudioOptions {
+    pub fn open(&self) -> AudioResult<Box<dyn AudioBackend>> {
+        Ok(match self.backend {
+            Backend::Cpal => Box::new(CpalBackend::open(&CpalConfig {
+                sample_rate: self.sample_rate,
+                input_device: self.input_device.clone(),
+                output_device: self.output_device.clone(),
+            })?),
+            Backend::Jack => Box::new(JackBackend::open("chirp")?),
+            Backend::Wav => {
+                let mut backend = WavBackend::new(self.sample_rate);
+                if let Some(path) = &self.wav_in {
+                    backend = backend.with_input(path)?;
+                }
+                if let Some(path) = &self.wav_out {
+                    backend = backend.with_output(path)?;
+                }
+                Box::new(backend)
+            }
+        })
+    }
+
+    /// open a backend with the gains `calibration` recommends, running at
+    /// the rate of `link`
+    ///
+    /// The link keeps its nominal rate so its symbols last as long as on
+    /// every other station, a device running at another rate is resampled.
+    pub fn open_modem(
+        &self,
+        link: &LinkConfig,
+        calibration: Option<&Calibration>,
+    ) -> Result<Box<dyn AudioBackend>, Box<dyn Error>> {
+        let mut backend = self.open()?;
+        let (device_rate, sample_rate) = (backend.sample_rate(), link.keying.sample_rate());
+        // every tone still has to fit below the device's Nyquist
+        link.with_sample_rate(device_rate)
+            .map_err(|err| AudioError::Unsupported(format!("can't run the modem: {err}")))?;
+        if device_rate != sample_rate {
+            backend = Box::new(ResampledBackend::new(backend, sample_rate)?);
+        }
+        if let Some(recommendation) = calibration.and_then(|c| c.recommendation) {
+            backend = Box::new(GainBackend::new(
+                backend,
+                recommendation.tx_gain,
+                recommendation.rx_gain,
+            ));
+        }
+        Ok(backend)
+    }
+
+    /// key calibrations of the chosen devices are stored under
+    pub fn device_key(&self) -> String {
+        match self.backend {
+            Backend::Cpal => device_key(
+                "cpal",
+                self.input_device.as_deref(),
+                self.output_device.as_deref(),
+            ),
+            Backend::Jack => device_key("jack", None, None),
+            Backend::Wav => device_key("wav", None, None),
+        }
+    }
+
+    /// what `chirp calibrate` stored for the chosen devices, unless told to
+    /// ignore it
+    pub fn calibration(&self) -> CalibrationResult<Option<Calibration>> {
+        let path = match CalibrationStore::default_path() {
+            Some(path) if !self.uncalibrated => path,
+            _ => return Ok(None),
+        };
+        let store = CalibrationStore::load(path)?;
+        Ok(store.get(&self.device_key()).cloned())
+    }
+}
+
+/// how messages are put on the air
+#[derive(Debug, Args)]
+pub struct ModemOptions {
+    /// named link settings, see `chirp profiles`
+    #[arg(long, env = "CHIRP_PROFILE", global = true)]
+    pub profile: Option<String>,
+
+    /// profiles file, `~/.config/chirp/profiles.toml` when it exists
+    #[arg(long, env = "CHIRP_PROFILES", global = true)]
+    pub profiles: Option<PathBuf>,
+
+    /// ook, fsk or mfsk with default parameters, overrides the profile
+    #[arg(long, value_parser = parse_keying, global = true)]
+    pub keying: Option<Keying>,
+
+    /// carrier in Hz, the center of the tones for fsk and mfsk, `auto` for
+    /// the one `chirp calibrate` recommended
+    #[arg(long, global = true)]
+    pub carrier: Option<Carrier>,
+
+    /// error correction, `inner` or `inner/outer`, e.g. `h128` or `v27/rs8`,
+    /// overrides the profile
+    #[arg(long, global = true)]
+    pub fec: Option<Coding>,
+}
+
+/// a `--carrier` frequency
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Carrier {
+    Fixed(Hz),
+    /// recommended by the devices' calibration
+    Auto,
+}
+
+impl FromStr for Carrier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "auto" => Ok(Carrier::Auto),
+            hz => hz
+                .parse()
+                .map(Carrier::Fixed)
+                .map_err(|_| format!("carrier {s} is neither a frequency in Hz nor auto")),
+        }
+    }
+}
+
+impl ModemOptions {
+    pub fn link(&self, calibration: Option<&Calibration>) -> Result<LinkConfig, String> {
+        let mut link = match &self.profile {
+            Some(name) => {
+                self.load_profiles()?
+                    .get(name)
+                    .map_err(|err| err.to_string())?
+                    .link
+            }
+            None => LinkConfig::default(),
+        };
+        if let Some(keying) = self.keying {
+            link.keying = keying;
+        }
+        if let Some(fec) = self.fec {
+            link.coding = fec;
+        }
+        match self.carrier {
+            Some(Carrier::Fixed(carrier)) => link.keying = retune(link.keying, carrier)?,
+            Some(Carrier::Auto) => {
+                let recommendation = calibration
+                    .and_then(|calibration| calibration.recommendation)
+                    .ok_or("no carrier calibrated for these devices, run `chirp calibrate`")?;
+                link.keying = retune(link.keying, recommendation.carrier)?;
+                // the tones have to stay within the band that was measured usable
+                let span = span(&link.keying);
+                if span > recommendation.bandwidth {
+                    return Err(format!(
+                        "{} tones span {span} Hz, wider than the {} Hz band calibrated \
+                         around {} Hz",
+                        link.keying.name(),
+                        recommendation.bandwidth,
+                        recommendation.carrier
+                    ));
+                }
+            }
+            None => {}
+        }
+        Ok(link)
+    }
+
+    /// built-in profiles and the user's, from `--profiles` or the default file
+    pub fn load_profiles(&self) -> Result<Profiles, String> {
+        let path = match &self.profiles {
+            Some(path) => Some(path.clone()),
+            None => profile::user_path().filter(|path| path.exists()),
+        };
+        match path {
+            Some(path) => Profiles::load(path).map_err(|err| err.to_string()),
+            None => Ok(Profiles::built_in()),
+        }
+    }
+}
+
+fn parse_keying(name: &str) -> Result<Keying, String> {
+    Keying::from_name(name)
+        .ok_or_else(|| format!("unknown keying {name}, expected ook, fsk or mfsk"))
+}
+
+// lowest to highest tone, nothing for a single carrier
+fn span(keying: &Keying) -> Hz {
+    match keying {
+        Keying::Amplitude(_) => 0,
+        Keying::Frequency(tones) => tones.mark.abs_diff(tones.space),
+        Keying::MultiFrequency(config) => config.tone(config.order - 1) - config.tone(0),
+    }
+}
+
+// move the tones of `keying` to be centered on `carrier`, keeping their spacing
+fn retune(keying: Keying, carrier: Hz) -> Result<Keying, String> {
+    let sample_rate = keying.sample_rate();
+    let nyquist = sample_rate / 2;
+    let fits = |low: Option<Hz>, high: Hz| match low {
+        Some(low) if low > 0 && high < nyquist => Ok(()),
+        _ => Err(format!(
+            "tones around {carrier} Hz don't fit below {nyquist} Hz"
+        )),
+    };
+    match keying {
+        Keying::Amplitude(config) => Ok(Keying::Amplitude(
+            ModemConfig::new(sample_rate, carrier, config.bit_rate() as Hz)
+                .map_err(|err| err.to_string())?,
+        )),
+        Keying::Frequency(tones) => {
+            let half = tones.mark.abs_diff(tones.space) / 2;
+            fits(carrier.checked_sub(half), carrier + half)?;
+            FskTones::new(
+                sample_rate,
+                carrier + half,
+                carrier - half,
+                tones.samples_per_bit,
+            )
+            .map(Keying::Frequency)
+            .map_err(|err| err.to_string())
+        }
+        Keying::MultiFrequency(config) => {
+            // tones sit on DFT bins of one symbol, the lowest is the first bin
+            // at or above half the span below the carrier
+            let (rate, samples) = (sample_rate as u64, config.symbol_samples as u64);
+            let gaps = config.order as u64 - 1;
+            let base_bin = (2 * carrier as u64 * samples)
+                .checked_sub(gaps * rate)
+                .map(|twice| twice.div_ceil(2 * rate));
+            let freq = |bin: u64| (bin * rate / samples) as Hz;
+            let base = base_bin.map(freq);
+            fits(base, base_bin.map_or(0, |bin| freq(bin + gaps)))?;
+            MfskConfig::new(
+                sample_rate,
+                config.order,
+                config.symbol_samples,
+                base.unwrap_or_default(),
+            )
+            .map(Keying::MultiFrequency)
+            .map_err(|err| err.to_string())
+        }
+    }
+}
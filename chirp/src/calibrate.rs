@@ -0,0 +1,408 @@
+//! Channel sounding, to find where a speaker and microphone pair works best.
+//!
+//! A sounding is a quiet stretch followed by a multitone comb. The quiet
+//! part gives the noise floor at every comb frequency, the comb the level
+//! each tone arrives at. Both are measured with Goertzel filters over long
+//! windows, so nothing has to line the recording up with what was played,
+//! the comb only has to have started by `SETTLE_SECONDS` in.
+//!
+//! Results are kept per device pair in `calibration.toml` next to the
+//! profiles, so later sessions start with the right gains.
+
+use std::{
+    collections::BTreeMap,
+    f32::consts::{PI, TAU},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chirp_modem::{Hz, detect::Goertzel};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::profile;
+
+/// SNR a tone needs for its frequency to count as usable
+pub const MIN_SNR_DB: f32 = 20.0;
+
+/// bandwidth SNRs are given in, about what a symbol of the modems occupies
+pub const REFERENCE_BANDWIDTH: f32 = 1000.0;
+
+/// comb samples skipped before measuring, covers output and input latency
+pub const SETTLE_SECONDS: f32 = 0.25;
+
+// captured peak aimed for at full output, leaves room before clipping
+const TARGET_PEAK: f32 = 0.7;
+// noise floor is averaged over windows this long
+const NOISE_WINDOW_SECONDS: f32 = 0.1;
+// carriers are recommended on this grid, keeps the ook carrier table short
+const CARRIER_GRID: Hz = 250;
+
+#[derive(Error, Debug)]
+pub enum CalibrationError {
+    #[error("Reading {path} failed: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Parsing {path} failed: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("Saving calibration failed: {0}")]
+    Serialize(#[from] toml::ser::Error),
+
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+}
+
+pub type CalibrationResult<T> = Result<T, CalibrationError>;
+
+/// what to play and how to measure it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoundingPlan {
+    pub sample_rate: Hz,
+    pub low_freq: Hz, // lowest comb tone
+    pub high_freq: Hz,
+    pub step: Hz,           // spacing of the comb tones
+    pub level: f32,         // peak amplitude of the comb
+    pub quiet_seconds: f32, // noise recording ahead of the comb
+    pub comb_seconds: f32,  // comb duration, settling included
+}
+
+impl SoundingPlan {
+    pub fn new(sample_rate: Hz) -> Self {
+        Self {
+            sample_rate,
+            low_freq: 16_000,
+            high_freq: sample_rate.min(48_000) / 2 - 2_000,
+            step: 250,
+            level: 0.5,
+            quiet_seconds: 1.0,
+            comb_seconds: 1.5,
+        }
+    }
+
+    pub fn validate(&self) -> CalibrationResult<()> {
+        let invalid = |reason: &str| Err(CalibrationError::InvalidParameter(reason.into()));
+        if self.step == 0 || self.low_freq == 0 || self.low_freq > self.high_freq {
+            return invalid("comb needs a positive step and a band from low to high");
+        }
+        if 2 * self.high_freq >= self.sample_rate {
+            return invalid("comb reaches past nyquist");
+        }
+        if !(0.0 < self.level && self.level <= 1.0) {
+            return invalid("comb level must be in (0, 1]");
+        }
+        if self.quiet_seconds < NOISE_WINDOW_SECONDS || self.comb_seconds <= 2.0 * SETTLE_SECONDS {
+            return invalid("quiet and comb stretches are too short to measure");
+        }
+        Ok(())
+    }
+
+    pub fn tones(&self) -> Vec<Hz> {
+        (self.low_freq..=self.high_freq)
+            .step_by(self.step as usize)
+            .collect()
+    }
+
+    /// the whole sounding, quiet stretch and comb, ready to play and record
+    pub fn samples(&self) -> Vec<f32> {
+        let mut samples = vec![0.0; self.seconds(self.quiet_seconds)];
+        samples.extend(self.comb(|_| 1.0).0);
+        samples
+    }
+
+    /// measure a recording of `samples()` taken while it played
+    pub fn analyze(&self, recorded: &[f32]) -> Sounding {
+        let quiet = self.seconds(self.quiet_seconds).min(recorded.len());
+        let settle = quiet + self.seconds(SETTLE_SECONDS);
+        let end = (quiet + self.seconds(self.comb_seconds)).min(recorded.len());
+        let (noise, comb) = (&recorded[..quiet], &recorded[settle.min(end)..end]);
+
+        let sent = self.tone_amplitude();
+        let window = self.seconds(NOISE_WINDOW_SECONDS);
+        let response = self
+            .tones()
+            .into_iter()
+            .map(|freq| {
+                let goertzel = Goertzel::new(freq, self.sample_rate);
+                let received = goertzel.power(comb.iter().copied()).sqrt();
+                // bin noise averaged over windows, scaled to the reference band
+                let chunks = noise.chunks_exact(window);
+                let count = chunks.len().max(1);
+                let bin = chunks
+                    .map(|c| goertzel.power(c.iter().copied()))
+                    .sum::<f32>()
+                    / count as f32;
+                let density = bin * window as f32 / (2.0 * self.sample_rate as f32);
+                let signal = received * received / 2.0;
+                ToneResponse {
+                    freq,
+                    gain_db: db(received / sent),
+                    // capped, a digitally silent input has no noise at all
+                    snr_db: db((signal / (density * REFERENCE_BANDWIDTH)).sqrt()).min(99.0),
+                }
+            })
+            .collect();
+        Sounding {
+            sample_rate: self.sample_rate,
+            level: self.level,
+            noise_floor_db: db(rms(noise)),
+            peak: comb.iter().fold(0.0f32, |peak, s| peak.max(s.abs())),
+            response,
+        }
+    }
+
+    fn seconds(&self, seconds: f32) -> usize {
+        (seconds * self.sample_rate as f32) as usize
+    }
+
+    // amplitude each comb tone is played at
+    fn tone_amplitude(&self) -> f32 {
+        self.comb(|_| 1.0).1
+    }
+
+    // the comb with every tone scaled by `gain(freq)` before normalizing, and
+    // the amplitude a tone of unit gain ends up at
+    fn comb(&self, gain: impl Fn(Hz) -> f32) -> (Vec<f32>, f32) {
+        let tones = self.tones();
+        let count = tones.len() as f32;
+        let fs = self.sample_rate as u64;
+        let mut samples = vec![0.0; self.seconds(self.comb_seconds)];
+        for (k, &freq) in tones.iter().enumerate() {
+            // Schroeder phases keep the crest factor of the sum low
+            let phase = PI * (k * k) as f32 / count;
+            let gain = gain(freq);
+            for (n, sample) in samples.iter_mut().enumerate() {
+                // whole cycles dropped in integers, f32 phase would drift over seconds
+                let cycle = (freq as u64 * n as u64 % fs) as f32 / fs as f32;
+                *sample += gain * (TAU * cycle + phase).sin();
+            }
+        }
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        let scale = if peak > 0.0 { self.level / peak } else { 0.0 };
+        samples.iter_mut().for_each(|sample| *sample *= scale);
+        (samples, scale)
+    }
+}
+
+/// measured response of one comb tone
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ToneResponse {
+    pub freq: Hz,
+    pub gain_db: f32, // level received relative to level played
+    pub snr_db: f32,  // over `REFERENCE_BANDWIDTH`
+}
+
+/// everything one sounding measured
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sounding {
+    pub sample_rate: Hz,
+    pub level: f32,          // comb peak played
+    pub noise_floor_db: f32, // RMS of the quiet stretch, dB full scale
+    pub peak: f32,           // largest sample captured during the comb
+    pub response: Vec<ToneResponse>,
+}
+
+/// settings a sounding suggests
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Recommendation {
+    pub carrier: Hz,   // middle of the widest usable band
+    pub bandwidth: Hz, // width of that band
+    pub tx_gain: f32,  // applied to played samples
+    pub rx_gain: f32,  // applied to captured ones, restores the carrier level played
+    pub min_snr_db: f32,
+}
+
+impl Sounding {
+    /// the widest run of comb tones at or above `MIN_SNR_DB`, ties going to
+    /// the run with the better worst tone, None when no tone made it
+    pub fn recommend(&self) -> Option<Recommendation> {
+        let mut best: Option<(&[ToneResponse], f32)> = None;
+        for run in self
+            .response
+            .split(|tone| tone.snr_db < MIN_SNR_DB)
+            .filter(|run| !run.is_empty())
+        {
+            let min_snr = run.iter().fold(f32::INFINITY, |min, t| min.min(t.snr_db));
+            let better = match best {
+                None => true,
+                Some((other, other_snr)) => (run.len(), min_snr) > (other.len(), other_snr),
+            };
+            if better {
+                best = Some((run, min_snr));
+            }
+        }
+        let (run, min_snr_db) = best?;
+        let (low, high) = (run[0].freq, run[run.len() - 1].freq);
+        let carrier = (low + high) / 2 / CARRIER_GRID * CARRIER_GRID;
+        let at_carrier = self
+            .response
+            .iter()
+            .min_by_key(|tone| tone.freq.abs_diff(carrier))?;
+
+        // full-scale output would capture at `peak / level`
+        let tx_gain = if self.peak > 0.0 {
+            (TARGET_PEAK * self.level / self.peak).min(1.0)
+        } else {
+            1.0
+        };
+        let rx_gain = 1.0 / (tx_gain * 10f32.powf(at_carrier.gain_db / 20.0));
+        Some(Recommendation {
+            carrier,
+            bandwidth: high - low,
+            tx_gain,
+            rx_gain,
+            min_snr_db,
+        })
+    }
+}
+
+/// a sounding and what it suggests, as stored for a device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    pub recommendation: Option<Recommendation>,
+    pub sounding: Sounding,
+}
+
+impl From<Sounding> for Calibration {
+    fn from(sounding: Sounding) -> Self {
+        Self {
+            recommendation: sounding.recommend(),
+            sounding,
+        }
+    }
+}
+
+/// calibrations by device, see `device_key`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CalibrationStore {
+    #[serde(default)]
+    pub devices: BTreeMap<String, Calibration>,
+}
+
+impl CalibrationStore {
+    /// `calibration.toml` in `profile::config_dir`
+    pub fn default_path() -> Option<PathBuf> {
+        Some(profile::config_dir()?.join("calibration.toml"))
+    }
+
+    /// the store at `path`, empty if there's no file yet
+    pub fn load(path: impl AsRef<Path>) -> CalibrationResult<Self> {
+        let path = path.as_ref();
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(source) => {
+                return Err(CalibrationError::Io {
+                    path: path.to_path_buf(),
+                    source,
+                });
+            }
+        };
+        toml::from_str(&text).map_err(|source| CalibrationError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> CalibrationResult<()> {
+        let path = path.as_ref();
+        let io = |source| CalibrationError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io)?;
+        }
+        fs::write(path, toml::to_string(self)?).map_err(io)
+    }
+
+    pub fn get(&self, device: &str) -> Option<&Calibration> {
+        self.devices.get(device)
+    }
+
+    pub fn insert(&mut self, device: &str, calibration: Calibration) {
+        self.devices.insert(device.to_string(), calibration);
+    }
+}
+
+/// key calibrations are stored under, a backend name and the devices used
+pub fn device_key(backend: &str, input: Option<&str>, output: Option<&str>) -> String {
+    format!(
+        "{backend}:{}>{}",
+        output.unwrap_or("default"),
+        input.unwrap_or("default")
+    )
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    let energy: f32 = samples.iter().map(|s| s * s).sum();
+    (energy / samples.len().max(1) as f32).sqrt()
+}
+
+// amplitude ratio in decibels, silence reads as a very low number
+fn db(ratio: f32) -> f32 {
+    20.0 * ratio.max(1e-9).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::XorShift;
+
+    // a speaker that gives out above `cutoff`, a quiet room and some latency
+    fn recording(plan: &SoundingPlan, cutoff: Hz, loss: f32) -> Vec<f32> {
+        let mut rng = XorShift::new(7);
+        let latency = plan.seconds(0.05);
+        let mut played = vec![0.0; plan.seconds(plan.quiet_seconds) + latency];
+        let (comb, _) = plan.comb(|freq| if freq > cutoff { 0.001 } else { 1.0 });
+        played.extend(comb.iter().map(|s| s * loss));
+        played.iter().map(|s| s + 0.001 * rng.gaussian()).collect()
+    }
+
+    #[test]
+    fn test_recommends_band_below_rolloff() {
+        let plan = SoundingPlan::new(48_000);
+        plan.validate().unwrap();
+        let sounding = plan.analyze(&recording(&plan, 20_000, 0.5));
+        assert!((sounding.noise_floor_db + 60.0).abs() < 1.0);
+        assert_eq!(sounding.response.len(), plan.tones().len());
+
+        let recommendation = sounding.recommend().unwrap();
+        assert_eq!(recommendation.bandwidth, 4_000);
+        assert_eq!(recommendation.carrier, 18_000);
+        assert!(recommendation.min_snr_db >= MIN_SNR_DB);
+        assert!(recommendation.tx_gain <= 1.0);
+        // what's captured at the carrier, scaled back up, matches what's played
+        let carrier = sounding
+            .response
+            .iter()
+            .find(|tone| tone.freq == 18_000)
+            .unwrap();
+        let through = recommendation.tx_gain * 10f32.powf(carrier.gain_db / 20.0);
+        assert!((through * recommendation.rx_gain - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_silence_recommends_nothing() {
+        let plan = SoundingPlan::new(48_000);
+        let quiet = vec![0.0; plan.samples().len()];
+        assert_eq!(plan.analyze(&quiet).recommend(), None);
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let plan = SoundingPlan::new(48_000);
+        let calibration = Calibration::from(plan.analyze(&recording(&plan, 21_000, 0.2)));
+        let mut store = CalibrationStore::default();
+        store.insert(&device_key("cpal", None, Some("USB Audio")), calibration);
+        let text = toml::to_string(&store).unwrap();
+        assert_eq!(toml::from_str::<CalibrationStore>(&text).unwrap(), store);
+        assert!(store.get("cpal:USB Audio>default").is_some());
+    }
+}
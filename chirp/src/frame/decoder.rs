@@ -0,0 +1,283 @@
+use std::ops::Range;
+
+use bitvec::{field::BitField, order::Lsb0, slice::BitSlice, vec::BitVec, view::BitView};
+use chirp_modem::interleave::Interleaver;
+
+use crate::frame::{
+    CRC_LEN, Frame, SYNC_WORD,
+    crc::crc32,
+    error::{FrameError, FrameResult},
+    header::Header,
+};
+
+const SYNC_BITS: usize = SYNC_WORD.len() * 8;
+
+/// streaming frame decoder over demodulated bits
+///
+/// Hunts for the sync word bit by bit, so bits straight out of a demodulator
+/// can be pushed without knowing where bytes start. Header and body are error
+/// corrected before their checks; a candidate that fails them is reported and
+/// hunting resumes one bit later.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    bits: BitVec<u8, Lsb0>,   // received bits from the current hunt position on
+    interleaver: Interleaver, // must match the sending `FrameEncoder`
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_interleaver(interleaver: Interleaver) -> Self {
+        Self {
+            interleaver,
+            ..Self::default()
+        }
+    }
+
+    pub fn push_bits(&mut self, bits: &BitSlice<u8, Lsb0>) {
+        self.bits.extend_from_bitslice(bits);
+    }
+
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        self.push_bits(bytes.view_bits::<Lsb0>());
+    }
+
+    pub fn push_bit(&mut self, bit: bool) {
+        self.bits.push(bit);
+    }
+
+    /// next complete frame or failed candidate, `None` when more bits are needed
+    pub fn next_frame(&mut self) -> Option<FrameResult<Frame>> {
+        let sync = SYNC_WORD.view_bits::<Lsb0>();
+        let Some(start) = self.bits.windows(SYNC_BITS).position(|w| w == sync) else {
+            // keep a partial sync word that may complete with the next push
+            let keep = self.bits.len().min(SYNC_BITS - 1);
+            self.drain(self.bits.len() - keep);
+            return None;
+        };
+        self.drain(start);
+
+        let body_start = SYNC_BITS + Header::encoded_len() * 8;
+        if self.bits.len() < body_start {
+            return None;
+        }
+        let header = match Header::decode(&self.section(SYNC_BITS..body_start)) {
+            Ok(header) => header,
+            Err(err) => {
+                self.drain(1);
+                return Some(Err(err));
+            }
+        };
+
+        let end = body_start + header.coding.body_len(header.length as usize) * 8;
+        if self.bits.len() < end {
+            return None;
+        }
+        let result = decode_body(&header, &self.section(body_start..end));
+        match result {
+            Ok(_) => self.drain(end),
+            Err(_) => self.drain(1),
+        }
+        Some(result)
+    }
+
+    /// error describing why the buffered bits don't hold a complete frame
+    pub fn incomplete(&self) -> FrameError {
+        let sync = SYNC_WORD.view_bits::<Lsb0>();
+        if !self.bits.starts_with(sync) {
+            return FrameError::MissingSync;
+        }
+        let available = (self.bits.len() - SYNC_BITS) / 8;
+        let header_len = Header::encoded_len();
+        let header_end = SYNC_BITS + header_len * 8;
+        let needed = if self.bits.len() < header_end {
+            header_len
+        } else {
+            match Header::decode(&self.section(SYNC_BITS..header_end)) {
+                Ok(header) => header_len + header.coding.body_len(header.length as usize),
+                Err(err) => return err,
+            }
+        };
+        FrameError::Truncated { needed, available }
+    }
+
+    /// bits buffered but not yet consumed
+    pub fn pending_bits(&self) -> usize {
+        self.bits.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.bits.clear();
+    }
+
+    fn drain(&mut self, count: usize) {
+        self.bits.drain(..count);
+    }
+
+    // bytes of an interleaved header or body as they were before interleaving
+    fn section(&self, range: Range<usize>) -> Vec<u8> {
+        load_bytes(&self.interleaver.deinterleave(&self.bits[range]))
+    }
+}
+
+// correct the body and check its CRC against the header it came with
+fn decode_body(header: &Header, encoded: &[u8]) -> FrameResult<Frame> {
+    let length = header.length as usize;
+    let body = header.coding.decode(encoded, length + CRC_LEN)?;
+    let (payload, crc) = body.split_at(length);
+    let expected = u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]);
+    let computed = crc32([&header.to_bytes()[..], payload]);
+    if expected != computed {
+        return Err(FrameError::BadCrc { expected, computed });
+    }
+    Ok(Frame {
+        header: *header,
+        payload: payload.to_vec(),
+    })
+}
+
+// pack whole bytes, least significant bit first
+fn load_bytes(bits: &BitSlice<u8, Lsb0>) -> Vec<u8> {
+    bits.chunks_exact(8)
+        .map(|chunk| chunk.load_le::<u8>())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        frame::{
+            FrameEncoder, PREAMBLE, VERSION,
+            header::{Coding, Flags},
+        },
+        liquid_modem::fec::FecScheme,
+    };
+    use chirp_modem::{
+        demodulator::BitDemodulator,
+        keying::{Demodulator, Keying, Modulator},
+    };
+
+    #[test]
+    fn test_round_trip_through_modulator() {
+        let mut encoder = FrameEncoder::new();
+        let mut decoder = FrameDecoder::new();
+        for keying in [
+            Keying::Amplitude(Default::default()),
+            Keying::MultiFrequency(Default::default()),
+        ] {
+            let bytes = encoder
+                .encode(Flags::ACK_REQUESTED, b"hello, nearby friend")
+                .unwrap();
+            // leading junk that doesn't end on a byte boundary
+            let mut samples: Vec<f32> = Modulator::new(keying, &[0x3C])
+                .take(3 * keying.samples_per_symbol())
+                .collect();
+            samples.extend(Modulator::new(keying, &bytes));
+            let mut demod = Demodulator::new(keying);
+            demod.push(&samples);
+            decoder.reset();
+            decoder.push_bits(demod.bit_buffer());
+            let frame = decoder.next_frame().unwrap().unwrap();
+            assert_eq!(frame.payload, b"hello, nearby friend");
+            assert!(frame.header.flags.contains(Flags::ACK_REQUESTED));
+        }
+        assert_eq!(encoder.sequence(), 2);
+    }
+
+    #[test]
+    fn test_typed_errors() {
+        let bytes = FrameEncoder::new()
+            .encode(Flags::EMPTY, b"payload")
+            .unwrap();
+        assert_eq!(Frame::decode(&bytes).unwrap().payload, b"payload");
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 0x01;
+        assert!(matches!(
+            Frame::decode(&corrupted),
+            Err(FrameError::BadCrc { .. })
+        ));
+
+        let mut future = Header::decode(&bytes[6..6 + Header::encoded_len()])
+            .unwrap()
+            .to_bytes();
+        assert_eq!(future[0], VERSION);
+        future[0] = 9;
+        assert_eq!(
+            Header::from_bytes(&future),
+            Err(FrameError::UnknownVersion(9))
+        );
+        future[0] = VERSION;
+        // past the last fragment
+        future[12] = 1;
+        assert_eq!(
+            Header::from_bytes(&future),
+            Err(FrameError::BadFragment { index: 1, count: 1 })
+        );
+
+        let header_len = Header::encoded_len();
+        assert_eq!(
+            Frame::decode(&bytes[..bytes.len() - 3]),
+            Err(FrameError::Truncated {
+                needed: header_len + 7 + CRC_LEN,
+                available: header_len + 7 + 1,
+            })
+        );
+        assert_eq!(Frame::decode(b"static"), Err(FrameError::MissingSync));
+    }
+
+    #[test]
+    fn test_coding_corrects_bit_errors() {
+        for coding in [
+            Coding::new(FecScheme::Hamming128, FecScheme::None),
+            Coding::new(FecScheme::Golay2412, FecScheme::None),
+            Coding::new(FecScheme::ConvV27, FecScheme::ReedSolomonM8),
+        ] {
+            // liquid built without libfec
+            if coding.inner.needs_libfec() && coding.check().is_err() {
+                continue;
+            }
+            let mut encoder = FrameEncoder::new().with_coding(coding);
+            let payload = b"a little redundancy goes a long way";
+            let mut bytes = encoder.encode(Flags::EMPTY, payload).unwrap();
+            assert_eq!(bytes.len(), Frame::encoded_len(coding, payload.len()));
+
+            // one flipped bit in the header and a few spread over the body
+            let body_start = PREAMBLE.len() + SYNC_WORD.len() + Header::encoded_len();
+            bytes[body_start - 2] ^= 0x04;
+            for i in (body_start..bytes.len()).step_by(16) {
+                bytes[i] ^= 0x01;
+            }
+            let frame = Frame::decode(&bytes).unwrap();
+            assert_eq!(frame.header.coding, coding);
+            assert_eq!(frame.payload, payload);
+        }
+    }
+
+    #[test]
+    fn test_interleaver_spreads_burst_over_codewords() {
+        let coding = Coding::new(FecScheme::Hamming128, FecScheme::None);
+        let payload = b"a door slams mid sentence";
+        let body_start = PREAMBLE.len() + SYNC_WORD.len() + Header::encoded_len();
+        for interleaver in [Interleaver::None, Interleaver::block(16, 24)] {
+            let mut bytes = FrameEncoder::new()
+                .with_coding(coding)
+                .with_interleaver(interleaver)
+                .encode(Flags::EMPTY, payload)
+                .unwrap();
+            // 16 consecutive bits lost on the air
+            bytes[body_start + 2] ^= 0xFF;
+            bytes[body_start + 3] ^= 0xFF;
+            let mut decoder = FrameDecoder::with_interleaver(interleaver);
+            decoder.push_bytes(&bytes);
+            let result = decoder.next_frame().unwrap();
+            match interleaver {
+                Interleaver::None => assert!(result.is_err()),
+                _ => assert_eq!(result.unwrap().payload, payload),
+            }
+        }
+    }
+}
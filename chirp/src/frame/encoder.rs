@@ -0,0 +1,151 @@
+use bitvec::{order::Lsb0, view::BitView};
+use chirp_modem::interleave::Interleaver;
+
+use crate::{
+    frame::{
+        Frame, MAX_PAYLOAD, PREAMBLE, SYNC_WORD,
+        crc::crc32,
+        error::{FrameError, FrameResult},
+        header::{Coding, Flags, Fragment, Header},
+    },
+    station::{BROADCAST, Station},
+};
+
+/// serializes payloads into frames, numbering them in sending order
+///
+/// The output is plain bytes, ready for `chirp_modem::keying::Modulator` or
+/// any other byte-consuming modulator. Header and body are interleaved
+/// separately after coding, preamble and sync word never are.
+#[derive(Debug, Default)]
+pub struct FrameEncoder {
+    station: Station,         // source of every frame, anonymous by default
+    sequence: u16,            // sequence number of the next frame
+    coding: Coding,           // applied to every frame body
+    interleaver: Interleaver, // must match the receiving `FrameDecoder`
+}
+
+impl FrameEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// continue numbering from `sequence`, e.g. after a restart
+    pub fn with_sequence(sequence: u16) -> Self {
+        Self {
+            sequence,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_station(mut self, station: Station) -> Self {
+        self.station = station;
+        self
+    }
+
+    pub fn station(&self) -> Station {
+        self.station
+    }
+
+    pub fn with_coding(mut self, coding: Coding) -> Self {
+        self.coding = coding;
+        self
+    }
+
+    /// sequence number the next frame will carry
+    pub fn sequence(&self) -> u16 {
+        self.sequence
+    }
+
+    pub fn with_interleaver(mut self, interleaver: Interleaver) -> Self {
+        self.interleaver = interleaver;
+        self
+    }
+
+    pub fn coding(&self) -> Coding {
+        self.coding
+    }
+
+    pub fn interleaver(&self) -> Interleaver {
+        self.interleaver
+    }
+
+    /// change the codes for following frames, receivers pick them up from the header
+    pub fn set_coding(&mut self, coding: Coding) {
+        self.coding = coding;
+    }
+
+    /// a frame for everyone in earshot
+    pub fn encode(&mut self, flags: Flags, payload: &[u8]) -> FrameResult<Vec<u8>> {
+        self.encode_to(BROADCAST, flags, payload)
+    }
+
+    /// a frame for `destination` alone, others still hear it
+    pub fn encode_to(
+        &mut self,
+        destination: Station,
+        flags: Flags,
+        payload: &[u8],
+    ) -> FrameResult<Vec<u8>> {
+        self.encode_fragment(destination, Fragment::WHOLE, flags, payload)
+    }
+
+    /// a frame carrying one `fragment` of a longer message
+    pub fn encode_fragment(
+        &mut self,
+        destination: Station,
+        fragment: Fragment,
+        flags: Flags,
+        payload: &[u8],
+    ) -> FrameResult<Vec<u8>> {
+        if payload.len() > MAX_PAYLOAD {
+            return Err(FrameError::PayloadTooLong(payload.len()));
+        }
+        let header = Header {
+            flags,
+            coding: self.coding,
+            source: self.station,
+            destination,
+            sequence: self.sequence,
+            length: payload.len() as u16,
+            fragment,
+        };
+        let bytes = encode_frame(&header, payload, self.interleaver)?;
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(bytes)
+    }
+}
+
+fn encode_frame(header: &Header, payload: &[u8], interleaver: Interleaver) -> FrameResult<Vec<u8>> {
+    let crc = crc32([&header.to_bytes()[..], payload]);
+    let mut body = Vec::with_capacity(payload.len() + crc.to_be_bytes().len());
+    body.extend_from_slice(payload);
+    body.extend_from_slice(&crc.to_be_bytes());
+
+    let len = Frame::encoded_len(header.coding, payload.len());
+    let mut bytes = Vec::with_capacity(len);
+    bytes.extend_from_slice(&PREAMBLE);
+    bytes.extend_from_slice(&SYNC_WORD);
+    for section in [header.encode()?, header.coding.encode(&body)?] {
+        bytes.extend(
+            interleaver
+                .interleave(section.view_bits::<Lsb0>())
+                .into_vec(),
+        );
+    }
+    debug_assert_eq!(bytes.len(), len);
+    Ok(bytes)
+}
+
+impl Frame {
+    /// serialize this frame as-is, keeping its sequence number and coding
+    pub fn encode(&self, interleaver: Interleaver) -> FrameResult<Vec<u8>> {
+        if self.payload.len() > MAX_PAYLOAD {
+            return Err(FrameError::PayloadTooLong(self.payload.len()));
+        }
+        let header = Header {
+            length: self.payload.len() as u16,
+            ..self.header
+        };
+        encode_frame(&header, &self.payload, interleaver)
+    }
+}
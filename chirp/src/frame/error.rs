@@ -0,0 +1,32 @@
+use thiserror::Error;
+
+use crate::liquid_modem::error::ModemError;
+
+pub type FrameResult<T> = Result<T, FrameError>;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    #[error("No sync word found.")]
+    MissingSync,
+
+    #[error("Frame truncated: needed {needed} bytes, got {available}.")]
+    Truncated { needed: usize, available: usize },
+
+    #[error("Unknown protocol version: {0}")]
+    UnknownVersion(u8),
+
+    #[error("Unknown FEC scheme code: {0}")]
+    UnknownFec(u8),
+
+    #[error("Payload too long: {0} bytes")]
+    PayloadTooLong(usize),
+
+    #[error("Bad fragment: {index} of {count}")]
+    BadFragment { index: u8, count: u8 },
+
+    #[error("Bad CRC: expected {expected:#010x}, computed {computed:#010x}")]
+    BadCrc { expected: u32, computed: u32 },
+
+    #[error("FEC failed: {0}")]
+    Fec(#[from] ModemError),
+}
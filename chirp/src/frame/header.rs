@@ -0,0 +1,248 @@
+use crate::{
+    frame::{
+        CRC_LEN, MAX_PAYLOAD, VERSION,
+        error::{FrameError, FrameResult},
+    },
+    liquid_modem::{
+        error::ModemError,
+        fec::{Fec, FecScheme},
+    },
+    station::{BROADCAST, Station},
+};
+use std::{fmt, str::FromStr};
+
+/// per-frame option bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct Flags(u8);
+
+impl Flags {
+    pub const EMPTY: Flags = Flags(0);
+    /// sender wants the frame acknowledged
+    pub const ACK_REQUESTED: Flags = Flags(1 << 0);
+    /// presence beacon, the payload is the sender's nickname
+    pub const BEACON: Flags = Flags(1 << 1);
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Flags(bits)
+    }
+
+    pub fn contains(&self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Flags) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Flags) {
+        self.0 &= !other.0;
+    }
+}
+
+impl std::ops::BitOr for Flags {
+    type Output = Flags;
+    fn bitor(self, rhs: Flags) -> Flags {
+        Flags(self.0 | rhs.0)
+    }
+}
+
+/// position of a frame's payload in a message split over several frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fragment {
+    pub index: u8, // from 0
+    pub count: u8, // fragments of the whole message, at least 1
+}
+
+impl Fragment {
+    /// a message that fits one frame
+    pub const WHOLE: Fragment = Fragment { index: 0, count: 1 };
+
+    pub fn is_last(&self) -> bool {
+        self.index + 1 == self.count
+    }
+}
+
+impl Default for Fragment {
+    fn default() -> Self {
+        Self::WHOLE
+    }
+}
+
+/// error correction applied to a frame body, payload and CRC together
+///
+/// The outer code is applied first and removed last, e.g. Reed-Solomon
+/// outside a convolutional code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct Coding {
+    pub inner: FecScheme,
+    pub outer: FecScheme,
+}
+
+impl Coding {
+    pub const NONE: Coding = Coding {
+        inner: FecScheme::None,
+        outer: FecScheme::None,
+    };
+
+    pub fn new(inner: FecScheme, outer: FecScheme) -> Self {
+        Self { inner, outer }
+    }
+
+    /// bytes on the air for a body of `payload_len` payload bytes
+    pub fn body_len(&self, payload_len: usize) -> usize {
+        self.inner
+            .encoded_len(self.outer.encoded_len(payload_len + CRC_LEN))
+    }
+
+    /// create both codes once, convolutional and Reed-Solomon ones fail
+    /// where liquid was built without libfec
+    pub fn check(&self) -> Result<(), ModemError> {
+        Fec::new(self.inner)?;
+        Fec::new(self.outer)?;
+        Ok(())
+    }
+
+    pub fn encode(&self, body: &[u8]) -> FrameResult<Vec<u8>> {
+        let outer = Fec::new(self.outer)?.encode(body)?;
+        Ok(Fec::new(self.inner)?.encode(&outer)?)
+    }
+
+    /// `len` is the body length before coding
+    pub fn decode(&self, encoded: &[u8], len: usize) -> FrameResult<Vec<u8>> {
+        let outer_len = self.outer.encoded_len(len);
+        let outer = Fec::new(self.inner)?.decode(encoded, outer_len)?;
+        Ok(Fec::new(self.outer)?.decode(&outer, len)?)
+    }
+}
+
+impl fmt::Display for Coding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.inner, self.outer)
+    }
+}
+
+/// `inner` or `inner/outer`, e.g. `h128` or `v27/rs8`
+impl FromStr for Coding {
+    type Err = ModemError;
+
+    fn from_str(s: &str) -> Result<Self, ModemError> {
+        let (inner, outer) = s.split_once('/').unwrap_or((s, "none"));
+        Ok(Coding::new(inner.parse()?, outer.parse()?))
+    }
+}
+
+/// fixed-size frame header, multi-byte fields are big endian
+///
+/// | byte  | field       |
+/// |-------|-------------|
+/// | 0     | version     |
+/// | 1     | flags       |
+/// | 2     | inner code  |
+/// | 3     | outer code  |
+/// | 4-5   | source      |
+/// | 6-7   | destination |
+/// | 8-9   | sequence    |
+/// | 10-11 | length      |
+/// | 12    | fragment    |
+/// | 13    | fragments   |
+///
+/// The header always travels under `Header::FEC` since the receiver has to
+/// read it before it knows which codes protect the body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub flags: Flags,
+    pub coding: Coding,
+    pub source: Station,
+    pub destination: Station, // `station::BROADCAST` for everyone
+    pub sequence: u16,        // wraps around, per sender
+    pub length: u16,          // payload bytes following the header, before coding
+    pub fragment: Fragment,   // `Fragment::WHOLE` unless the payload is part of a longer message
+}
+
+impl Header {
+    pub const LEN: usize = 14;
+    pub const FEC: FecScheme = FecScheme::Hamming128;
+
+    /// header bytes on the air, after `Header::FEC`
+    pub fn encoded_len() -> usize {
+        Self::FEC.encoded_len(Self::LEN)
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let [src_hi, src_lo] = self.source.to_be_bytes();
+        let [dst_hi, dst_lo] = self.destination.to_be_bytes();
+        let [seq_hi, seq_lo] = self.sequence.to_be_bytes();
+        let [len_hi, len_lo] = self.length.to_be_bytes();
+        [
+            VERSION,
+            self.flags.bits(),
+            self.coding.inner.code(),
+            self.coding.outer.code(),
+            src_hi,
+            src_lo,
+            dst_hi,
+            dst_lo,
+            seq_hi,
+            seq_lo,
+            len_hi,
+            len_lo,
+            self.fragment.index,
+            self.fragment.count,
+        ]
+    }
+
+    /// addressed to `station` or to everyone
+    pub fn is_for(&self, station: Station) -> bool {
+        self.destination == station || self.destination == BROADCAST
+    }
+
+    pub fn encode(&self) -> FrameResult<Vec<u8>> {
+        Ok(Fec::new(Self::FEC)?.encode(&self.to_bytes())?)
+    }
+
+    /// correct and parse a header as received
+    pub fn decode(encoded: &[u8]) -> FrameResult<Self> {
+        let bytes = Fec::new(Self::FEC)?.decode(encoded, Self::LEN)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> FrameResult<Self> {
+        if bytes.len() < Self::LEN {
+            return Err(FrameError::Truncated {
+                needed: Self::LEN,
+                available: bytes.len(),
+            });
+        }
+        if bytes[0] != VERSION {
+            return Err(FrameError::UnknownVersion(bytes[0]));
+        }
+        let scheme = |code| FecScheme::from_code(code).ok_or(FrameError::UnknownFec(code));
+        let u16_at = |i: usize| u16::from_be_bytes([bytes[i], bytes[i + 1]]);
+        let header = Header {
+            flags: Flags::from_bits(bytes[1]),
+            coding: Coding::new(scheme(bytes[2])?, scheme(bytes[3])?),
+            source: u16_at(4),
+            destination: u16_at(6),
+            sequence: u16_at(8),
+            length: u16_at(10),
+            fragment: Fragment {
+                index: bytes[12],
+                count: bytes[13],
+            },
+        };
+        if header.length as usize > MAX_PAYLOAD {
+            return Err(FrameError::PayloadTooLong(header.length as usize));
+        }
+        if header.fragment.index >= header.fragment.count {
+            return Err(FrameError::BadFragment {
+                index: header.fragment.index,
+                count: header.fragment.count,
+            });
+        }
+        Ok(header)
+    }
+}
@@ -0,0 +1,56 @@
+//! Versioned chirp frame, the unit every higher layer sends and receives.
+//!
+//! ```text
+//! | preamble | sync word | header   | payload      | crc-32  |
+//! | 4 bytes  | 2 bytes   | 14 bytes | 0-1024 bytes | 4 bytes |
+//!                        |<- h128 ->|<- outer, then inner code ->|
+//! ```
+//!
+//! Bytes go onto the air least significant bit first, the same order the
+//! `chirp_modem` modulators consume them. The CRC covers header and payload
+//! and is checked after error correction.
+
+pub mod crc;
+pub mod decoder;
+pub mod encoder;
+pub mod error;
+pub mod header;
+
+pub use decoder::FrameDecoder;
+pub use encoder::FrameEncoder;
+pub use error::{FrameError, FrameResult};
+pub use header::{Coding, Flags, Fragment, Header};
+
+/// protocol version written into every header
+pub const VERSION: u8 = 4;
+/// alternating bits give the receiver a bit clock before the sync word
+pub const PREAMBLE: [u8; 4] = [0x55; 4];
+/// marks the first header bit
+pub const SYNC_WORD: [u8; 2] = [0x2D, 0xD4];
+/// longest payload a single frame may carry
+pub const MAX_PAYLOAD: usize = 1024;
+pub const CRC_LEN: usize = 4;
+
+/// decoded frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub header: Header,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// decode a byte-aligned buffer holding a single frame
+    pub fn decode(bytes: &[u8]) -> FrameResult<Frame> {
+        let mut decoder = FrameDecoder::new();
+        decoder.push_bytes(bytes);
+        match decoder.next_frame() {
+            Some(result) => result,
+            None => Err(decoder.incomplete()),
+        }
+    }
+
+    /// bytes on the air for a frame with `payload_len` payload bytes
+    pub fn encoded_len(coding: Coding, payload_len: usize) -> usize {
+        PREAMBLE.len() + SYNC_WORD.len() + Header::encoded_len() + coding.body_len(payload_len)
+    }
+}
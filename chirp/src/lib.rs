@@ -1,2 +1,13 @@
+pub mod arq;
+pub mod audio;
+pub mod bench;
+pub mod calibrate;
+pub mod frame;
+pub mod link;
 pub mod liquid_modem;
-pub mod modem;
\ No newline at end of file
+pub mod mac;
+pub mod profile;
+pub mod sim;
+pub mod station;
+pub mod sync;
+pub mod transfer;
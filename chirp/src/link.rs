@@ -0,0 +1,364 @@
+//! Transmit and receive pipelines, payload to samples and back.
+//!
+//! A transmission is the sweep preamble from `sync`, immediately followed by
+//! a `frame` keyed onto the carrier, then a short silence. The receiver finds
+//! the preamble, rewinds to where the frame starts, so symbol timing comes
+//! from the preamble instead of the demodulator, and demodulates until the
+//! frame decoder has an answer.
+
+use std::collections::VecDeque;
+
+use chirp_modem::{
+    Hz,
+    config::{ConfigError, ConfigResult},
+    demodulator::BitDemodulator,
+    interleave::Interleaver,
+    keying::{Demodulator, Keying, Modulator},
+};
+
+use crate::{
+    frame::{
+        Coding, Flags, Fragment, Frame, FrameDecoder, FrameEncoder, FrameError, FrameResult,
+        MAX_PAYLOAD, PREAMBLE, SYNC_WORD,
+    },
+    station::Station,
+    sync::{ChirpConfig, ChirpPreamble, ChirpSynchronizer, SyncEvent},
+};
+
+/// everything both ends of a link have to agree on
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LinkConfig {
+    pub keying: Keying,
+    pub coding: Coding, // the transmitter's, receivers read it from each header
+    pub interleaver: Interleaver,
+    pub chirp: ChirpConfig,
+}
+
+impl LinkConfig {
+    /// the same link for a device running at `sample_rate`, see
+    /// `Keying::with_sample_rate`
+    pub fn with_sample_rate(&self, sample_rate: Hz) -> ConfigResult<Self> {
+        if 2.0 * self.chirp.high_freq >= sample_rate as f32 {
+            return Err(ConfigError::AboveNyquist {
+                freq: self.chirp.high_freq as Hz,
+                sample_rate,
+            });
+        }
+        Ok(Self {
+            keying: self.keying.with_sample_rate(sample_rate)?,
+            chirp: self.chirp.with_sample_rate(sample_rate),
+            ..*self
+        })
+    }
+
+    /// samples on the air for a frame carrying `payload_len` bytes, preamble
+    /// and trailing silence included
+    pub fn frame_samples(&self, payload_len: usize) -> usize {
+        let bits = Frame::encoded_len(self.coding, payload_len) * 8;
+        let symbols = bits.div_ceil(self.keying.bits_per_symbol());
+        2 * self.chirp.sweep_len + symbols * self.keying.samples_per_symbol() + self.guard()
+    }
+
+    // silence after each frame, lets the receiver's correlator settle
+    fn guard(&self) -> usize {
+        self.chirp.sweep_len
+    }
+}
+
+/// turns payloads into samples ready for an `AudioBackend`
+pub struct Transmitter {
+    config: LinkConfig,
+    preamble: Vec<f32>,
+    encoder: FrameEncoder,
+}
+
+impl Transmitter {
+    pub fn new(config: LinkConfig) -> Self {
+        Self {
+            preamble: ChirpPreamble::new(config.chirp).samples().collect(),
+            encoder: FrameEncoder::new()
+                .with_coding(config.coding)
+                .with_interleaver(config.interleaver),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &LinkConfig {
+        &self.config
+    }
+
+    /// frames name `station` as their source
+    pub fn with_station(mut self, station: Station) -> Self {
+        self.encoder = self.encoder.with_station(station);
+        self
+    }
+
+    /// frame numbering and coding of following transmissions
+    pub fn encoder_mut(&mut self) -> &mut FrameEncoder {
+        &mut self.encoder
+    }
+
+    /// samples for one frame with the next sequence number, for everyone
+    pub fn transmit(&mut self, flags: Flags, payload: &[u8]) -> FrameResult<Vec<f32>> {
+        let bytes = self.encoder.encode(flags, payload)?;
+        Ok(self.modulate(&bytes))
+    }
+
+    /// samples for one frame addressed to `destination`
+    pub fn transmit_to(
+        &mut self,
+        destination: Station,
+        flags: Flags,
+        payload: &[u8],
+    ) -> FrameResult<Vec<f32>> {
+        let bytes = self.encoder.encode_to(destination, flags, payload)?;
+        Ok(self.modulate(&bytes))
+    }
+
+    /// samples for one frame carrying `fragment` of a longer message
+    pub fn transmit_fragment(
+        &mut self,
+        destination: Station,
+        fragment: Fragment,
+        flags: Flags,
+        payload: &[u8],
+    ) -> FrameResult<Vec<f32>> {
+        let bytes = self
+            .encoder
+            .encode_fragment(destination, fragment, flags, payload)?;
+        Ok(self.modulate(&bytes))
+    }
+
+    /// samples for an already numbered frame, e.g. a retransmission
+    pub fn transmit_frame(&self, frame: &Frame) -> FrameResult<Vec<f32>> {
+        let bytes = frame.encode(self.config.interleaver)?;
+        Ok(self.modulate(&bytes))
+    }
+
+    fn modulate(&self, bytes: &[u8]) -> Vec<f32> {
+        let mut samples = self.preamble.clone();
+        samples.extend(Modulator::new(self.config.keying, bytes));
+        samples.resize(samples.len() + self.config.guard(), 0.0);
+        samples
+    }
+}
+
+/// outcome of one detected transmission
+#[derive(Debug, PartialEq)]
+pub struct Reception {
+    pub sync: SyncEvent,
+    pub frame: FrameResult<Frame>,
+}
+
+/// streaming receiver over captured samples
+///
+/// While a frame is being demodulated further preambles are ignored. A
+/// preamble without a sync word right behind it is reported as
+/// `FrameError::MissingSync`, a frame still incomplete after the longest one
+/// this link could send as truncated.
+pub struct Receiver {
+    config: LinkConfig,
+    sync: ChirpSynchronizer,
+    recent: VecDeque<f32>, // enough history to rewind to a payload start
+    rewind: usize,
+    capture: Option<Capture>,
+}
+
+// bits after a preamble within which the sync word has to show up
+const SYNC_SEARCH_BITS: usize = (PREAMBLE.len() + SYNC_WORD.len() + 1) * 8;
+
+// demodulation in progress after a preamble
+struct Capture {
+    sync: SyncEvent,
+    demod: Demodulator,
+    decoder: FrameDecoder,
+    bits: usize,      // demodulated so far
+    synced: bool,     // sync word seen
+    remaining: usize, // samples left before giving up
+}
+
+impl Capture {
+    fn push_sample(&mut self, sample: f32) -> Option<Reception> {
+        self.remaining = self.remaining.saturating_sub(1);
+        let decided = self.demod.push_sample(sample);
+        if decided > 0 {
+            self.bits += decided;
+            self.decoder.push_bits(self.demod.bit_buffer());
+            self.demod.bit_buffer_mut().clear();
+            if let Some(frame) = self.decoder.next_frame() {
+                return Some(self.done(frame));
+            }
+            if !self.synced && self.bits >= SYNC_SEARCH_BITS {
+                match self.decoder.incomplete() {
+                    FrameError::MissingSync => {
+                        return Some(self.done(Err(FrameError::MissingSync)));
+                    }
+                    _ => self.synced = true,
+                }
+            }
+        }
+        if self.remaining == 0 {
+            return Some(self.done(Err(self.decoder.incomplete())));
+        }
+        None
+    }
+
+    fn done(&self, frame: FrameResult<Frame>) -> Reception {
+        Reception {
+            sync: self.sync,
+            frame,
+        }
+    }
+}
+
+impl Receiver {
+    pub fn new(config: LinkConfig) -> Self {
+        let sync = ChirpSynchronizer::new(ChirpPreamble::new(config.chirp));
+        let rewind = sync.latency() + config.keying.samples_per_symbol();
+        Self {
+            config,
+            sync,
+            recent: VecDeque::with_capacity(rewind),
+            rewind,
+            capture: None,
+        }
+    }
+
+    pub fn config(&self) -> &LinkConfig {
+        &self.config
+    }
+
+    /// whether a frame is being demodulated right now
+    pub fn is_receiving(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    /// consume a block, calling `on_reception` for every transmission found
+    pub fn push(&mut self, samples: &[f32], mut on_reception: impl FnMut(Reception)) {
+        for &sample in samples {
+            if let Some(reception) = self.push_sample(sample) {
+                on_reception(reception);
+            }
+        }
+    }
+
+    pub fn push_sample(&mut self, sample: f32) -> Option<Reception> {
+        let event = self.sync.push_sample(sample);
+        if self.recent.len() == self.rewind {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(sample);
+
+        if let Some(capture) = &mut self.capture {
+            let reception = capture.push_sample(sample);
+            if reception.is_some() {
+                self.capture = None;
+            }
+            return reception;
+        }
+        let event = event?;
+        let mut capture = Capture {
+            sync: event,
+            demod: Demodulator::new(self.config.keying),
+            decoder: FrameDecoder::with_interleaver(self.config.interleaver),
+            bits: 0,
+            synced: false,
+            remaining: self.config.frame_samples(MAX_PAYLOAD),
+        };
+        // replay what arrived between payload start and detection
+        let start = event.payload_offset(self.sync.preamble());
+        let behind = (self.sync.position() - start) as usize;
+        let skip = self.recent.len().saturating_sub(behind);
+        for &sample in self.recent.iter().skip(skip) {
+            if let Some(reception) = capture.push_sample(sample) {
+                return Some(reception);
+            }
+        }
+        self.capture = Some(capture);
+        None
+    }
+
+    pub fn reset(&mut self) {
+        self.sync.reset();
+        self.recent.clear();
+        self.capture = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_at_any_delay() {
+        for keying in [
+            Keying::Amplitude(Default::default()),
+            Keying::Frequency(Default::default()),
+            Keying::MultiFrequency(Default::default()),
+        ] {
+            let config = LinkConfig {
+                keying,
+                ..Default::default()
+            };
+            let mut transmitter = Transmitter::new(config);
+            let mut receiver = Receiver::new(config);
+
+            // two frames at an awkward delay, as a recording would hold them
+            let mut samples = vec![0.0; 777];
+            samples.extend(transmitter.transmit(Flags::EMPTY, b"first").unwrap());
+            let second = transmitter.transmit(Flags::EMPTY, b"second").unwrap();
+            assert_eq!(second.len(), config.frame_samples(b"second".len()));
+            samples.extend(second);
+
+            let mut payloads = Vec::new();
+            for block in samples.chunks(500) {
+                receiver.push(block, |reception| {
+                    payloads.push(reception.frame.unwrap().payload);
+                });
+            }
+            assert_eq!(payloads, [&b"first"[..], b"second"]);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_at_other_sample_rates() {
+        for sample_rate in [44_100, 96_000] {
+            let config = LinkConfig::default().with_sample_rate(sample_rate).unwrap();
+            assert_eq!(config.keying.sample_rate(), sample_rate);
+            let samples = Transmitter::new(config)
+                .transmit(Flags::EMPTY, b"any rate")
+                .unwrap();
+            let mut payloads = Vec::new();
+            Receiver::new(config).push(&samples, |reception| {
+                payloads.push(reception.frame.unwrap().payload);
+            });
+            assert_eq!(payloads, [b"any rate"]);
+        }
+        assert!(LinkConfig::default().with_sample_rate(32_000).is_err());
+    }
+
+    #[test]
+    fn test_failures_are_reported() {
+        let config = LinkConfig::default();
+        let preamble: Vec<f32> = ChirpPreamble::new(config.chirp).samples().collect();
+        let mut cut_off = Transmitter::new(config)
+            .transmit(Flags::EMPTY, b"cut off mid-air")
+            .unwrap();
+        cut_off.truncate(cut_off.len() / 2);
+
+        // a lone preamble, then a frame that stops halfway
+        let mut samples = preamble.clone();
+        samples.resize(3 * preamble.len(), 0.0);
+        samples.extend(cut_off);
+        samples.resize(samples.len() + config.frame_samples(MAX_PAYLOAD), 0.0);
+
+        let mut receptions = Vec::new();
+        Receiver::new(config).push(&samples, |reception| receptions.push(reception));
+        assert_eq!(receptions.len(), 2);
+        assert_eq!(receptions[0].frame, Err(FrameError::MissingSync));
+        assert!(matches!(
+            receptions[1].frame,
+            Err(FrameError::BadCrc { .. })
+        ));
+    }
+}
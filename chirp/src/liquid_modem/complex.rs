@@ -15,6 +15,16 @@ impl Complex {
         self.0.im
     }
 
+    /// squared magnitude
+    pub fn norm_sqr(&self) -> f32 {
+        self.0.re * self.0.re + self.0.im * self.0.im
+    }
+
+    /// by-value form for liquid calls taking a complex argument
+    pub fn raw(&self) -> ffi::liquid_float_complex {
+        self.0
+    }
+
     pub fn as_ptr(&self) -> *const ffi::liquid_float_complex {
         &self.0
     }
@@ -24,6 +34,12 @@ impl Complex {
     }
 }
 
+impl Default for Complex {
+    fn default() -> Self {
+        Self::new(0.0, 0.0)
+    }
+}
+
 impl From<ffi::liquid_float_complex> for Complex {
     fn from(val: ffi::liquid_float_complex) -> Self {
         Self(val)
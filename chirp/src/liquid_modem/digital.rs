@@ -1,29 +1,198 @@
 use crate::liquid_modem::{
     complex::Complex,
-    error::{ModemError, ModemResult},
+    error::{ModemError, ModemResult, check},
 };
 use liquid_dsp_sys::ffi;
-use std::ptr::NonNull;
+use std::{fmt, ptr::NonNull, str::FromStr};
 
-struct DigitalModem {
+// map each variant onto liquid's scheme constant and short name
+macro_rules! modulation_schemes {
+    ($($variant:ident => $raw:ident, $name:literal;)*) => {
+        /// linear modulation schemes implemented by liquid's `modemcf`
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum ModulationScheme {
+            $($variant,)*
+        }
+
+        impl ModulationScheme {
+            pub const ALL: &'static [ModulationScheme] = &[$(ModulationScheme::$variant,)*];
+
+            /// liquid's short name, e.g. `qpsk` or `qam16`
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(ModulationScheme::$variant => $name,)*
+                }
+            }
+
+            fn raw(&self) -> ffi::modulation_scheme {
+                match self {
+                    $(ModulationScheme::$variant => ffi::$raw,)*
+                }
+            }
+        }
+    };
+}
+
+modulation_schemes! {
+    Psk2 => modulation_scheme_LIQUID_MODEM_PSK2, "psk2";
+    Psk4 => modulation_scheme_LIQUID_MODEM_PSK4, "psk4";
+    Psk8 => modulation_scheme_LIQUID_MODEM_PSK8, "psk8";
+    Psk16 => modulation_scheme_LIQUID_MODEM_PSK16, "psk16";
+    Psk32 => modulation_scheme_LIQUID_MODEM_PSK32, "psk32";
+    Psk64 => modulation_scheme_LIQUID_MODEM_PSK64, "psk64";
+    Psk128 => modulation_scheme_LIQUID_MODEM_PSK128, "psk128";
+    Psk256 => modulation_scheme_LIQUID_MODEM_PSK256, "psk256";
+    Dpsk2 => modulation_scheme_LIQUID_MODEM_DPSK2, "dpsk2";
+    Dpsk4 => modulation_scheme_LIQUID_MODEM_DPSK4, "dpsk4";
+    Dpsk8 => modulation_scheme_LIQUID_MODEM_DPSK8, "dpsk8";
+    Dpsk16 => modulation_scheme_LIQUID_MODEM_DPSK16, "dpsk16";
+    Dpsk32 => modulation_scheme_LIQUID_MODEM_DPSK32, "dpsk32";
+    Dpsk64 => modulation_scheme_LIQUID_MODEM_DPSK64, "dpsk64";
+    Dpsk128 => modulation_scheme_LIQUID_MODEM_DPSK128, "dpsk128";
+    Dpsk256 => modulation_scheme_LIQUID_MODEM_DPSK256, "dpsk256";
+    Ask2 => modulation_scheme_LIQUID_MODEM_ASK2, "ask2";
+    Ask4 => modulation_scheme_LIQUID_MODEM_ASK4, "ask4";
+    Ask8 => modulation_scheme_LIQUID_MODEM_ASK8, "ask8";
+    Ask16 => modulation_scheme_LIQUID_MODEM_ASK16, "ask16";
+    Ask32 => modulation_scheme_LIQUID_MODEM_ASK32, "ask32";
+    Ask64 => modulation_scheme_LIQUID_MODEM_ASK64, "ask64";
+    Ask128 => modulation_scheme_LIQUID_MODEM_ASK128, "ask128";
+    Ask256 => modulation_scheme_LIQUID_MODEM_ASK256, "ask256";
+    Qam4 => modulation_scheme_LIQUID_MODEM_QAM4, "qam4";
+    Qam8 => modulation_scheme_LIQUID_MODEM_QAM8, "qam8";
+    Qam16 => modulation_scheme_LIQUID_MODEM_QAM16, "qam16";
+    Qam32 => modulation_scheme_LIQUID_MODEM_QAM32, "qam32";
+    Qam64 => modulation_scheme_LIQUID_MODEM_QAM64, "qam64";
+    Qam128 => modulation_scheme_LIQUID_MODEM_QAM128, "qam128";
+    Qam256 => modulation_scheme_LIQUID_MODEM_QAM256, "qam256";
+    Apsk4 => modulation_scheme_LIQUID_MODEM_APSK4, "apsk4";
+    Apsk8 => modulation_scheme_LIQUID_MODEM_APSK8, "apsk8";
+    Apsk16 => modulation_scheme_LIQUID_MODEM_APSK16, "apsk16";
+    Apsk32 => modulation_scheme_LIQUID_MODEM_APSK32, "apsk32";
+    Apsk64 => modulation_scheme_LIQUID_MODEM_APSK64, "apsk64";
+    Apsk128 => modulation_scheme_LIQUID_MODEM_APSK128, "apsk128";
+    Apsk256 => modulation_scheme_LIQUID_MODEM_APSK256, "apsk256";
+    Bpsk => modulation_scheme_LIQUID_MODEM_BPSK, "bpsk";
+    Qpsk => modulation_scheme_LIQUID_MODEM_QPSK, "qpsk";
+    Ook => modulation_scheme_LIQUID_MODEM_OOK, "ook";
+    Sqam32 => modulation_scheme_LIQUID_MODEM_SQAM32, "sqam32";
+    Sqam128 => modulation_scheme_LIQUID_MODEM_SQAM128, "sqam128";
+    V29 => modulation_scheme_LIQUID_MODEM_V29, "v29";
+}
+
+impl fmt::Display for ModulationScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ModulationScheme {
+    type Err = ModemError;
+
+    fn from_str(s: &str) -> ModemResult<Self> {
+        ModulationScheme::ALL
+            .iter()
+            .find(|scheme| scheme.name().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| ModemError::InvalidParameter(format!("unknown modulation scheme {s}")))
+    }
+}
+
+/// safe wrapper over a liquid linear modem (`modemcf`)
+pub struct DigitalModem {
     modem: NonNull<ffi::modemcf_s>,
+    scheme: ModulationScheme,
+    bps: u32,
 }
 
+// the modem owns its state exclusively and liquid keeps no thread-local data,
+// so it can move between threads; shared access would race on that state
+unsafe impl Send for DigitalModem {}
+
 impl DigitalModem {
-    pub fn new() -> Self {
-        let modem = unsafe {
-            let modem = ffi::modemcf_create(ffi::modulation_scheme_LIQUID_MODEM_ASK2);
-            NonNull::new(modem).expect("Failed to create modem.")
-        };
-        DigitalModem { modem }
+    pub fn new(scheme: ModulationScheme) -> ModemResult<Self> {
+        let modem = unsafe { ffi::modemcf_create(scheme.raw()) };
+        let modem = NonNull::new(modem).ok_or(ModemError::CreationError)?;
+        let bps = unsafe { ffi::modemcf_get_bps(modem.as_ptr()) };
+        Ok(DigitalModem { modem, scheme, bps })
+    }
+
+    pub fn scheme(&self) -> ModulationScheme {
+        self.scheme
+    }
+
+    pub fn bits_per_symbol(&self) -> u32 {
+        self.bps
+    }
+
+    /// symbols in the constellation
+    pub fn order(&self) -> u32 {
+        1 << self.bps
     }
 
-    // symbol must be 0 or 1, representing bit values
-    pub fn modulate(&self, symbol: u32, complex: &mut Complex) {
-        unsafe {
-            let output = complex.as_mut_ptr();
-            ffi::modemcf_modulate(self.modem.as_ptr(), symbol, output);
+    /// clear internal state, e.g. the reference phase of differential schemes
+    pub fn reset(&mut self) -> ModemResult<()> {
+        check(
+            unsafe { ffi::modemcf_reset(self.modem.as_ptr()) },
+            "modemcf_reset",
+        )
+    }
+
+    /// map a symbol in `0..order()` onto the constellation
+    pub fn modulate(&mut self, symbol: u32) -> ModemResult<Complex> {
+        if symbol >= self.order() {
+            return Err(ModemError::InvalidParameter(format!(
+                "symbol {symbol} out of range for {}",
+                self.scheme
+            )));
+        }
+        let mut output = Complex::default();
+        let code =
+            unsafe { ffi::modemcf_modulate(self.modem.as_ptr(), symbol, output.as_mut_ptr()) };
+        check(code, "modemcf_modulate")?;
+        Ok(output)
+    }
+
+    /// hard decision on the nearest constellation point
+    pub fn demodulate(&mut self, sample: Complex) -> ModemResult<u32> {
+        let mut symbol = 0;
+        let code =
+            unsafe { ffi::modemcf_demodulate(self.modem.as_ptr(), sample.raw(), &mut symbol) };
+        check(code, "modemcf_demodulate")?;
+        Ok(symbol)
+    }
+
+    /// hard decision plus one soft bit per symbol bit, most significant first;
+    /// soft bits range from 0 (certain zero) to 255 (certain one)
+    pub fn demodulate_soft(&mut self, sample: Complex, soft_bits: &mut [u8]) -> ModemResult<u32> {
+        if soft_bits.len() < self.bps as usize {
+            return Err(ModemError::InvalidParameter(format!(
+                "soft bit buffer holds {} of {} bits",
+                soft_bits.len(),
+                self.bps
+            )));
         }
+        let mut symbol = 0;
+        let code = unsafe {
+            ffi::modemcf_demodulate_soft(
+                self.modem.as_ptr(),
+                sample.raw(),
+                &mut symbol,
+                soft_bits.as_mut_ptr(),
+            )
+        };
+        check(code, "modemcf_demodulate_soft")?;
+        Ok(symbol)
+    }
+
+    /// error vector magnitude of the last demodulated sample
+    pub fn evm(&self) -> f32 {
+        unsafe { ffi::modemcf_get_demodulator_evm(self.modem.as_ptr()) }
+    }
+
+    /// phase error in radians of the last demodulated sample
+    pub fn phase_error(&self) -> f32 {
+        unsafe { ffi::modemcf_get_demodulator_phase_error(self.modem.as_ptr()) }
     }
 }
 
@@ -36,8 +205,51 @@ impl Drop for DigitalModem {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
+
     #[test]
     fn test_digital_modem_new() {
-        let modem = DigitalModem::new();
+        for &scheme in ModulationScheme::ALL {
+            let modem = DigitalModem::new(scheme).unwrap();
+            assert_eq!(modem.scheme(), scheme);
+            assert!(modem.bits_per_symbol() >= 1);
+        }
+    }
+
+    #[rstest]
+    #[case(ModulationScheme::Bpsk, 1)]
+    #[case(ModulationScheme::Qpsk, 2)]
+    #[case(ModulationScheme::Dpsk8, 3)]
+    #[case(ModulationScheme::Qam16, 4)]
+    #[case(ModulationScheme::Apsk32, 5)]
+    #[case(ModulationScheme::Ask4, 2)]
+    fn test_round_trip(#[case] scheme: ModulationScheme, #[case] bps: u32) {
+        let mut tx = DigitalModem::new(scheme).unwrap();
+        let mut rx = DigitalModem::new(scheme).unwrap();
+        assert_eq!(tx.bits_per_symbol(), bps);
+        let mut soft_bits = vec![0u8; bps as usize];
+        for symbol in 0..tx.order() {
+            let sample = tx.modulate(symbol).unwrap();
+            assert_eq!(rx.demodulate_soft(sample, &mut soft_bits).unwrap(), symbol);
+            assert!(rx.evm() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_invalid_symbol() {
+        let mut modem = DigitalModem::new(ModulationScheme::Qpsk).unwrap();
+        assert!(matches!(
+            modem.modulate(4),
+            Err(ModemError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_scheme_names() {
+        assert_eq!(
+            "QAM16".parse::<ModulationScheme>().unwrap(),
+            ModulationScheme::Qam16
+        );
+        assert!("qam3".parse::<ModulationScheme>().is_err());
     }
 }
@@ -2,7 +2,7 @@ use thiserror::Error;
 
 pub type ModemResult<T> = Result<T, ModemError>;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, PartialEq, Eq)]
 pub enum ModemError {
     #[error("Failed to create modem.")]
     CreationError,
@@ -15,4 +15,14 @@ pub enum ModemError {
 
     #[error("Operation failed: {0}")]
     OperationFailed(String),
-}
\ No newline at end of file
+}
+
+// liquid returns LIQUID_OK (zero) on success, an error code otherwise
+pub(crate) fn check(code: i32, operation: &str) -> ModemResult<()> {
+    match code {
+        0 => Ok(()),
+        code => Err(ModemError::OperationFailed(format!(
+            "{operation} returned liquid error {code}"
+        ))),
+    }
+}
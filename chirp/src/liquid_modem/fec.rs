@@ -0,0 +1,210 @@
+use crate::liquid_modem::error::{ModemError, ModemResult, check};
+use liquid_dsp_sys::ffi;
+use std::{fmt, ptr::NonNull, str::FromStr};
+
+// map each variant onto liquid's scheme constant, short name and stable wire code
+macro_rules! fec_schemes {
+    ($($(#[$attr:meta])* $variant:ident => $raw:ident, $name:literal, $code:literal;)*) => {
+        /// forward error correction codes implemented by liquid's `fec`
+        ///
+        /// Convolutional and Reed-Solomon codes need liquid built against libfec,
+        /// creating them fails otherwise.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+        pub enum FecScheme {
+            $($(#[$attr])* $variant,)*
+        }
+
+        impl FecScheme {
+            pub const ALL: &'static [FecScheme] = &[$(FecScheme::$variant,)*];
+
+            /// liquid's short name, e.g. `h128` or `v27`
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(FecScheme::$variant => $name,)*
+                }
+            }
+
+            /// identifier carried in frame headers, independent of liquid's enum values
+            pub fn code(&self) -> u8 {
+                match self {
+                    $(FecScheme::$variant => $code,)*
+                }
+            }
+
+            pub fn from_code(code: u8) -> Option<FecScheme> {
+                match code {
+                    $($code => Some(FecScheme::$variant),)*
+                    _ => None,
+                }
+            }
+
+            fn raw(&self) -> ffi::fec_scheme {
+                match self {
+                    $(FecScheme::$variant => ffi::$raw,)*
+                }
+            }
+        }
+    };
+}
+
+fec_schemes! {
+    #[default]
+    None => fec_scheme_LIQUID_FEC_NONE, "none", 0;
+    Repeat3 => fec_scheme_LIQUID_FEC_REP3, "rep3", 1;
+    Repeat5 => fec_scheme_LIQUID_FEC_REP5, "rep5", 2;
+    Hamming74 => fec_scheme_LIQUID_FEC_HAMMING74, "h74", 3;
+    Hamming84 => fec_scheme_LIQUID_FEC_HAMMING84, "h84", 4;
+    Hamming128 => fec_scheme_LIQUID_FEC_HAMMING128, "h128", 5;
+    Golay2412 => fec_scheme_LIQUID_FEC_GOLAY2412, "g2412", 6;
+    Secded2216 => fec_scheme_LIQUID_FEC_SECDED2216, "secded2216", 7;
+    Secded3932 => fec_scheme_LIQUID_FEC_SECDED3932, "secded3932", 8;
+    Secded7264 => fec_scheme_LIQUID_FEC_SECDED7264, "secded7264", 9;
+    ConvV27 => fec_scheme_LIQUID_FEC_CONV_V27, "v27", 10;
+    ConvV29 => fec_scheme_LIQUID_FEC_CONV_V29, "v29", 11;
+    ConvV39 => fec_scheme_LIQUID_FEC_CONV_V39, "v39", 12;
+    ConvV615 => fec_scheme_LIQUID_FEC_CONV_V615, "v615", 13;
+    ConvV27P23 => fec_scheme_LIQUID_FEC_CONV_V27P23, "v27p23", 14;
+    ConvV27P34 => fec_scheme_LIQUID_FEC_CONV_V27P34, "v27p34", 15;
+    ConvV27P45 => fec_scheme_LIQUID_FEC_CONV_V27P45, "v27p45", 16;
+    ConvV27P56 => fec_scheme_LIQUID_FEC_CONV_V27P56, "v27p56", 17;
+    ConvV27P67 => fec_scheme_LIQUID_FEC_CONV_V27P67, "v27p67", 18;
+    ConvV27P78 => fec_scheme_LIQUID_FEC_CONV_V27P78, "v27p78", 19;
+    ConvV29P23 => fec_scheme_LIQUID_FEC_CONV_V29P23, "v29p23", 20;
+    ConvV29P34 => fec_scheme_LIQUID_FEC_CONV_V29P34, "v29p34", 21;
+    ConvV29P45 => fec_scheme_LIQUID_FEC_CONV_V29P45, "v29p45", 22;
+    ConvV29P56 => fec_scheme_LIQUID_FEC_CONV_V29P56, "v29p56", 23;
+    ConvV29P67 => fec_scheme_LIQUID_FEC_CONV_V29P67, "v29p67", 24;
+    ConvV29P78 => fec_scheme_LIQUID_FEC_CONV_V29P78, "v29p78", 25;
+    ReedSolomonM8 => fec_scheme_LIQUID_FEC_RS_M8, "rs8", 26;
+}
+
+impl FecScheme {
+    /// convolutional and Reed-Solomon codes, only there when liquid has libfec
+    pub fn needs_libfec(&self) -> bool {
+        self.code() >= FecScheme::ConvV27.code()
+    }
+
+    /// encoded bytes for a `len` byte message
+    pub fn encoded_len(&self, len: usize) -> usize {
+        unsafe { ffi::fec_get_enc_msg_length(self.raw(), len as u32) as usize }
+    }
+}
+
+impl fmt::Display for FecScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for FecScheme {
+    type Err = ModemError;
+
+    fn from_str(s: &str) -> ModemResult<Self> {
+        FecScheme::ALL
+            .iter()
+            .find(|scheme| scheme.name().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| ModemError::InvalidParameter(format!("unknown fec scheme {s}")))
+    }
+}
+
+/// safe wrapper over a liquid forward error correction codec (`fec`)
+pub struct Fec {
+    fec: NonNull<ffi::fec_s>,
+    scheme: FecScheme,
+}
+
+// exclusively owned liquid object, see `DigitalModem`
+unsafe impl Send for Fec {}
+
+impl Fec {
+    pub fn new(scheme: FecScheme) -> ModemResult<Self> {
+        let fec = unsafe { ffi::fec_create(scheme.raw(), std::ptr::null_mut()) };
+        let fec = NonNull::new(fec).ok_or(ModemError::CreationError)?;
+        Ok(Self { fec, scheme })
+    }
+
+    pub fn scheme(&self) -> FecScheme {
+        self.scheme
+    }
+
+    pub fn encode(&mut self, message: &[u8]) -> ModemResult<Vec<u8>> {
+        let mut encoded = vec![0u8; self.scheme.encoded_len(message.len())];
+        // liquid takes the message as mutable but only reads it
+        let code = unsafe {
+            ffi::fec_encode(
+                self.fec.as_ptr(),
+                message.len() as u32,
+                message.as_ptr() as *mut u8,
+                encoded.as_mut_ptr(),
+            )
+        };
+        check(code, "fec_encode")?;
+        Ok(encoded)
+    }
+
+    /// correct and strip redundancy, `len` is the original message length
+    pub fn decode(&mut self, encoded: &[u8], len: usize) -> ModemResult<Vec<u8>> {
+        let expected = self.scheme.encoded_len(len);
+        if encoded.len() != expected {
+            return Err(ModemError::InvalidParameter(format!(
+                "{} bytes of {} encoding a {len} byte message, expected {expected}",
+                encoded.len(),
+                self.scheme
+            )));
+        }
+        let mut message = vec![0u8; len];
+        let code = unsafe {
+            ffi::fec_decode(
+                self.fec.as_ptr(),
+                len as u32,
+                encoded.as_ptr() as *mut u8,
+                message.as_mut_ptr(),
+            )
+        };
+        check(code, "fec_decode")?;
+        Ok(message)
+    }
+}
+
+impl Drop for Fec {
+    fn drop(&mut self) {
+        unsafe { ffi::fec_destroy(self.fec.as_ptr()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_wire_codes_round_trip() {
+        for &scheme in FecScheme::ALL {
+            assert_eq!(FecScheme::from_code(scheme.code()), Some(scheme));
+            assert_eq!(scheme.name().parse::<FecScheme>().unwrap(), scheme);
+        }
+        assert_eq!(FecScheme::from_code(200), None);
+    }
+
+    #[rstest]
+    #[case(FecScheme::Hamming74)]
+    #[case(FecScheme::Hamming128)]
+    #[case(FecScheme::Golay2412)]
+    #[case(FecScheme::Secded7264)]
+    #[case(FecScheme::ConvV27)]
+    #[case(FecScheme::ReedSolomonM8)]
+    fn test_corrects_single_bit_error(#[case] scheme: FecScheme) {
+        let message = b"over the air between laptops";
+        let mut fec = match Fec::new(scheme) {
+            Ok(fec) => fec,
+            // liquid built without libfec
+            Err(ModemError::CreationError) if scheme.needs_libfec() => return,
+            Err(err) => panic!("{scheme}: {err}"),
+        };
+        let mut encoded = fec.encode(message).unwrap();
+        assert_eq!(encoded.len(), scheme.encoded_len(message.len()));
+        encoded[3] ^= 0x10;
+        assert_eq!(fec.decode(&encoded, message.len()).unwrap(), message);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::liquid_modem::{
+    complex::Complex,
+    error::{ModemError, ModemResult, check},
+};
+use liquid_dsp_sys::ffi;
+use std::ptr::NonNull;
+
+/// root-raised-cosine interpolator (`firinterp_crcf`), one symbol in, `k` samples out
+pub struct Interpolator {
+    interp: NonNull<ffi::firinterp_crcf_s>,
+    k: u32,
+}
+
+// exclusively owned liquid object, see `DigitalModem`
+unsafe impl Send for Interpolator {}
+
+impl Interpolator {
+    /// `k` samples per symbol, `m` symbols of filter delay, `beta` excess bandwidth
+    pub fn rrc(k: u32, m: u32, beta: f32) -> ModemResult<Self> {
+        validate_rrc(k, m, beta)?;
+        let interp = unsafe {
+            ffi::firinterp_crcf_create_prototype(
+                ffi::liquid_firfilt_type_LIQUID_FIRFILT_RRC as i32,
+                k,
+                m,
+                beta,
+                0.0,
+            )
+        };
+        let interp = NonNull::new(interp).ok_or(ModemError::CreationError)?;
+        Ok(Self { interp, k })
+    }
+
+    pub fn samples_per_symbol(&self) -> u32 {
+        self.k
+    }
+
+    /// interpolate one symbol into `output`, which must hold `k` samples
+    pub fn execute(&mut self, symbol: Complex, output: &mut [Complex]) -> ModemResult<()> {
+        if output.len() < self.k as usize {
+            return Err(ModemError::InvalidParameter(format!(
+                "output holds {} of {} samples",
+                output.len(),
+                self.k
+            )));
+        }
+        let code = unsafe {
+            ffi::firinterp_crcf_execute(
+                self.interp.as_ptr(),
+                symbol.raw(),
+                output.as_mut_ptr() as *mut ffi::liquid_float_complex,
+            )
+        };
+        check(code, "firinterp_crcf_execute")
+    }
+
+    pub fn reset(&mut self) -> ModemResult<()> {
+        check(
+            unsafe { ffi::firinterp_crcf_reset(self.interp.as_ptr()) },
+            "firinterp_crcf_reset",
+        )
+    }
+}
+
+impl Drop for Interpolator {
+    fn drop(&mut self) {
+        unsafe { ffi::firinterp_crcf_destroy(self.interp.as_ptr()) };
+    }
+}
+
+/// root-raised-cosine matched filter and decimator (`firdecim_crcf`), `k` samples in, one out
+pub struct Decimator {
+    decim: NonNull<ffi::firdecim_crcf_s>,
+    k: u32,
+}
+
+// exclusively owned liquid object, see `DigitalModem`
+unsafe impl Send for Decimator {}
+
+impl Decimator {
+    /// `k` samples per symbol, `m` symbols of filter delay, `beta` excess bandwidth
+    pub fn rrc(k: u32, m: u32, beta: f32) -> ModemResult<Self> {
+        validate_rrc(k, m, beta)?;
+        let decim = unsafe {
+            ffi::firdecim_crcf_create_prototype(
+                ffi::liquid_firfilt_type_LIQUID_FIRFILT_RRC as i32,
+                k,
+                m,
+                beta,
+                0.0,
+            )
+        };
+        let decim = NonNull::new(decim).ok_or(ModemError::CreationError)?;
+        Ok(Self { decim, k })
+    }
+
+    pub fn samples_per_symbol(&self) -> u32 {
+        self.k
+    }
+
+    /// output gain, the matched pair sums `k` taps so `1/k` restores unit gain
+    pub fn set_scale(&mut self, scale: f32) -> ModemResult<()> {
+        check(
+            unsafe { ffi::firdecim_crcf_set_scale(self.decim.as_ptr(), scale) },
+            "firdecim_crcf_set_scale",
+        )
+    }
+
+    /// filter `k` input samples down to a single output sample
+    pub fn execute(&mut self, input: &mut [Complex]) -> ModemResult<Complex> {
+        if input.len() != self.k as usize {
+            return Err(ModemError::InvalidParameter(format!(
+                "input holds {} of {} samples",
+                input.len(),
+                self.k
+            )));
+        }
+        let mut output = Complex::default();
+        let code = unsafe {
+            ffi::firdecim_crcf_execute(
+                self.decim.as_ptr(),
+                input.as_mut_ptr() as *mut ffi::liquid_float_complex,
+                output.as_mut_ptr(),
+            )
+        };
+        check(code, "firdecim_crcf_execute")?;
+        Ok(output)
+    }
+
+    pub fn reset(&mut self) -> ModemResult<()> {
+        check(
+            unsafe { ffi::firdecim_crcf_reset(self.decim.as_ptr()) },
+            "firdecim_crcf_reset",
+        )
+    }
+}
+
+impl Drop for Decimator {
+    fn drop(&mut self) {
+        unsafe { ffi::firdecim_crcf_destroy(self.decim.as_ptr()) };
+    }
+}
+
+fn validate_rrc(k: u32, m: u32, beta: f32) -> ModemResult<()> {
+    if k < 2 {
+        return Err(ModemError::InvalidParameter(format!(
+            "samples per symbol must be at least 2, got {k}"
+        )));
+    }
+    if m == 0 {
+        return Err(ModemError::InvalidParameter(
+            "filter delay must be at least one symbol".into(),
+        ));
+    }
+    if !(beta > 0.0 && beta <= 1.0) {
+        return Err(ModemError::InvalidParameter(format!(
+            "excess bandwidth must be in (0, 1], got {beta}"
+        )));
+    }
+    Ok(())
+}
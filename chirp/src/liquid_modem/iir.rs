@@ -0,0 +1,65 @@
+use crate::liquid_modem::error::{ModemError, ModemResult, check};
+use liquid_dsp_sys::ffi;
+use std::ptr::NonNull;
+
+/// real infinite impulse response filter (`iirfilt_rrrf`)
+pub struct IirFilter {
+    filter: NonNull<ffi::iirfilt_rrrf_s>,
+}
+
+// exclusively owned liquid object, see `DigitalModem`
+unsafe impl Send for IirFilter {}
+
+impl IirFilter {
+    /// Butterworth lowpass of `order`, `cutoff` relative to the sample rate
+    pub fn butterworth_lowpass(order: u32, cutoff: f32) -> ModemResult<Self> {
+        if order == 0 || !(cutoff > 0.0 && cutoff < 0.5) {
+            return Err(ModemError::InvalidParameter(format!(
+                "order {order} filter with cutoff {cutoff} of the sample rate"
+            )));
+        }
+        // second-order sections stay stable at high orders
+        let filter = unsafe {
+            ffi::iirfilt_rrrf_create_prototype(
+                ffi::liquid_iirdes_filtertype_LIQUID_IIRDES_BUTTER,
+                ffi::liquid_iirdes_bandtype_LIQUID_IIRDES_LOWPASS,
+                ffi::liquid_iirdes_format_LIQUID_IIRDES_SOS,
+                order,
+                cutoff,
+                0.0,
+                1.0,
+                60.0,
+            )
+        };
+        let filter = NonNull::new(filter).ok_or(ModemError::CreationError)?;
+        Ok(Self { filter })
+    }
+
+    pub fn execute(&mut self, x: f32) -> ModemResult<f32> {
+        let mut y = 0.0;
+        let code = unsafe { ffi::iirfilt_rrrf_execute(self.filter.as_ptr(), x, &mut y) };
+        check(code, "iirfilt_rrrf_execute")?;
+        Ok(y)
+    }
+
+    /// filter a block in place
+    pub fn execute_block(&mut self, block: &mut [f32]) -> ModemResult<()> {
+        for sample in block.iter_mut() {
+            *sample = self.execute(*sample)?;
+        }
+        Ok(())
+    }
+
+    pub fn reset(&mut self) -> ModemResult<()> {
+        check(
+            unsafe { ffi::iirfilt_rrrf_reset(self.filter.as_ptr()) },
+            "iirfilt_rrrf_reset",
+        )
+    }
+}
+
+impl Drop for IirFilter {
+    fn drop(&mut self) {
+        unsafe { ffi::iirfilt_rrrf_destroy(self.filter.as_ptr()) };
+    }
+}
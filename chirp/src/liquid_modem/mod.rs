@@ -1,3 +1,9 @@
 pub mod complex;
 pub mod digital;
 pub mod error;
+pub mod fec;
+pub mod filter;
+pub mod iir;
+pub mod nco;
+pub mod passband;
+pub mod resamp;
@@ -0,0 +1,83 @@
+use crate::liquid_modem::{
+    complex::Complex,
+    error::{ModemError, ModemResult, check},
+};
+use liquid_dsp_sys::ffi;
+use std::{f32::consts::TAU, ptr::NonNull};
+
+/// numerically controlled oscillator (`nco_crcf`) for frequency translation
+pub struct Oscillator {
+    nco: NonNull<ffi::nco_crcf_s>,
+    step: f32, // radians per sample
+}
+
+// exclusively owned liquid object, see `DigitalModem`
+unsafe impl Send for Oscillator {}
+
+impl Oscillator {
+    /// oscillator at `freq` Hz for a stream sampled at `sample_rate` Hz
+    pub fn new(freq: f32, sample_rate: f32) -> ModemResult<Self> {
+        if !(0.0..sample_rate / 2.0).contains(&freq.abs()) {
+            return Err(ModemError::InvalidParameter(format!(
+                "oscillator {freq} Hz not below nyquist of {sample_rate} Hz"
+            )));
+        }
+        let nco = unsafe { ffi::nco_crcf_create(ffi::liquid_ncotype_LIQUID_NCO as i32) };
+        let nco = NonNull::new(nco).ok_or(ModemError::CreationError)?;
+        let mut oscillator = Self { nco, step: 0.0 };
+        oscillator.set_frequency(freq, sample_rate)?;
+        Ok(oscillator)
+    }
+
+    pub fn set_frequency(&mut self, freq: f32, sample_rate: f32) -> ModemResult<()> {
+        self.step = TAU * freq / sample_rate;
+        check(
+            unsafe { ffi::nco_crcf_set_frequency(self.nco.as_ptr(), self.step) },
+            "nco_crcf_set_frequency",
+        )
+    }
+
+    /// rotate `x` up by the current phase and advance one sample
+    pub fn mix_up(&mut self, x: Complex) -> ModemResult<Complex> {
+        let mut y = Complex::default();
+        let code = unsafe { ffi::nco_crcf_mix_up(self.nco.as_ptr(), x.raw(), y.as_mut_ptr()) };
+        check(code, "nco_crcf_mix_up")?;
+        self.advance()?;
+        Ok(y)
+    }
+
+    /// rotate `x` down by the current phase and advance one sample
+    pub fn mix_down(&mut self, x: Complex) -> ModemResult<Complex> {
+        let mut y = Complex::default();
+        let code = unsafe { ffi::nco_crcf_mix_down(self.nco.as_ptr(), x.raw(), y.as_mut_ptr()) };
+        check(code, "nco_crcf_mix_down")?;
+        self.advance()?;
+        Ok(y)
+    }
+
+    /// zero the phase, keeping the frequency
+    pub fn reset(&mut self) -> ModemResult<()> {
+        check(
+            unsafe { ffi::nco_crcf_reset(self.nco.as_ptr()) },
+            "nco_crcf_reset",
+        )?;
+        // liquid clears the frequency along with the phase
+        check(
+            unsafe { ffi::nco_crcf_set_frequency(self.nco.as_ptr(), self.step) },
+            "nco_crcf_set_frequency",
+        )
+    }
+
+    fn advance(&mut self) -> ModemResult<()> {
+        check(
+            unsafe { ffi::nco_crcf_step(self.nco.as_ptr()) },
+            "nco_crcf_step",
+        )
+    }
+}
+
+impl Drop for Oscillator {
+    fn drop(&mut self) {
+        unsafe { ffi::nco_crcf_destroy(self.nco.as_ptr()) };
+    }
+}
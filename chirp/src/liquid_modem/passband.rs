@@ -0,0 +1,220 @@
+use crate::liquid_modem::{
+    complex::Complex,
+    error::{ModemError, ModemResult},
+    filter::{Decimator, Interpolator},
+    nco::Oscillator,
+};
+
+/// shared parameters of the passband transmitter and receiver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PassbandConfig {
+    pub sample_rate: u32,        // audio samples per second
+    pub carrier_freq: u32,       // center of the occupied band
+    pub samples_per_symbol: u32, // interpolation / decimation factor
+    pub filter_delay: u32,       // RRC filter half-length in symbols
+    pub excess_bandwidth: f32,   // RRC roll-off factor
+    pub gain: f32,               // transmit amplitude
+}
+
+impl PassbandConfig {
+    pub fn symbol_rate(&self) -> f32 {
+        self.sample_rate as f32 / self.samples_per_symbol as f32
+    }
+
+    /// two-sided bandwidth occupied around the carrier
+    pub fn bandwidth(&self) -> f32 {
+        self.symbol_rate() * (1.0 + self.excess_bandwidth)
+    }
+
+    /// symbols between a symbol entering the transmitter and leaving the receiver
+    pub fn delay(&self) -> u32 {
+        2 * self.filter_delay
+    }
+
+    pub fn validate(&self) -> ModemResult<()> {
+        let nyquist = self.sample_rate as f32 / 2.0;
+        let upper = self.carrier_freq as f32 + self.bandwidth() / 2.0;
+        let lower = self.carrier_freq as f32 - self.bandwidth() / 2.0;
+        if upper >= nyquist || lower <= 0.0 {
+            return Err(ModemError::InvalidParameter(format!(
+                "band {lower}..{upper} Hz does not fit below nyquist of {nyquist} Hz"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for PassbandConfig {
+    fn default() -> Self {
+        // 3000 baud occupies roughly 17.5 - 21.5 kHz
+        Self {
+            sample_rate: 48_000,
+            carrier_freq: 19_500,
+            samples_per_symbol: 16,
+            filter_delay: 4,
+            excess_bandwidth: 0.35,
+            gain: 0.5,
+        }
+    }
+}
+
+/// pulse-shapes complex baseband symbols and mixes them onto a real audio carrier
+pub struct Upconverter {
+    config: PassbandConfig,
+    interp: Interpolator,
+    nco: Oscillator,
+    shaped: Vec<Complex>, // one symbol of interpolated baseband
+}
+
+impl Upconverter {
+    pub fn new(config: PassbandConfig) -> ModemResult<Self> {
+        config.validate()?;
+        Ok(Self {
+            interp: Interpolator::rrc(
+                config.samples_per_symbol,
+                config.filter_delay,
+                config.excess_bandwidth,
+            )?,
+            nco: Oscillator::new(config.carrier_freq as f32, config.sample_rate as f32)?,
+            shaped: vec![Complex::default(); config.samples_per_symbol as usize],
+            config,
+        })
+    }
+
+    pub fn config(&self) -> &PassbandConfig {
+        &self.config
+    }
+
+    /// append `samples_per_symbol` audio samples for one symbol
+    pub fn push(&mut self, symbol: Complex, output: &mut Vec<f32>) -> ModemResult<()> {
+        self.interp.execute(symbol, &mut self.shaped)?;
+        for &sample in self.shaped.iter() {
+            let mixed = self.nco.mix_up(sample)?;
+            output.push(self.config.gain * mixed.re());
+        }
+        Ok(())
+    }
+
+    /// flush the pulse-shaping filter tail so the last symbol is fully transmitted
+    pub fn flush(&mut self, output: &mut Vec<f32>) -> ModemResult<()> {
+        for _ in 0..self.config.filter_delay {
+            self.push(Complex::default(), output)?;
+        }
+        Ok(())
+    }
+
+    pub fn reset(&mut self) -> ModemResult<()> {
+        self.interp.reset()?;
+        self.nco.reset()
+    }
+}
+
+/// mixes real audio down to complex baseband and matched-filters it to one sample per symbol
+///
+/// Symbol timing is not recovered here, input must start on a symbol boundary
+/// as found by the frame synchronizer. Output lags input by `delay()` symbols.
+pub struct Downconverter {
+    config: PassbandConfig,
+    decim: Decimator,
+    nco: Oscillator,
+    pending: Vec<Complex>, // baseband samples of the current symbol
+}
+
+impl Downconverter {
+    pub fn new(config: PassbandConfig) -> ModemResult<Self> {
+        config.validate()?;
+        let mut decim = Decimator::rrc(
+            config.samples_per_symbol,
+            config.filter_delay,
+            config.excess_bandwidth,
+        )?;
+        // matched filter pair sums k taps, real mixing halves the amplitude
+        decim.set_scale(2.0 / (config.samples_per_symbol as f32 * config.gain))?;
+        Ok(Self {
+            decim,
+            nco: Oscillator::new(config.carrier_freq as f32, config.sample_rate as f32)?,
+            pending: Vec::with_capacity(config.samples_per_symbol as usize),
+            config,
+        })
+    }
+
+    pub fn config(&self) -> &PassbandConfig {
+        &self.config
+    }
+
+    /// feed one audio sample, returns a baseband symbol every `samples_per_symbol` samples
+    pub fn push(&mut self, sample: f32) -> ModemResult<Option<Complex>> {
+        let baseband = self.nco.mix_down(Complex::new(sample, 0.0))?;
+        self.pending.push(baseband);
+        if self.pending.len() < self.config.samples_per_symbol as usize {
+            return Ok(None);
+        }
+        let symbol = self.decim.execute(&mut self.pending)?;
+        self.pending.clear();
+        Ok(Some(symbol))
+    }
+
+    /// feed a block of audio, appending every completed symbol
+    pub fn push_block(&mut self, samples: &[f32], symbols: &mut Vec<Complex>) -> ModemResult<()> {
+        for &sample in samples {
+            if let Some(symbol) = self.push(sample)? {
+                symbols.push(symbol);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn delay(&self) -> u32 {
+        self.config.delay()
+    }
+
+    pub fn reset(&mut self) -> ModemResult<()> {
+        self.pending.clear();
+        self.decim.reset()?;
+        self.nco.reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::liquid_modem::digital::{DigitalModem, ModulationScheme};
+
+    #[test]
+    fn test_invalid_band() {
+        let config = PassbandConfig {
+            carrier_freq: 23_000,
+            ..PassbandConfig::default()
+        };
+        assert!(Upconverter::new(config).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config = PassbandConfig::default();
+        let mut tx_modem = DigitalModem::new(ModulationScheme::Qpsk).unwrap();
+        let mut rx_modem = DigitalModem::new(ModulationScheme::Qpsk).unwrap();
+        let mut upconverter = Upconverter::new(config).unwrap();
+        let mut downconverter = Downconverter::new(config).unwrap();
+
+        let sent: Vec<u32> = (0..64).map(|i| (i * 7 + 3) % 4).collect();
+        let mut audio = Vec::new();
+        for &symbol in sent.iter() {
+            let point = tx_modem.modulate(symbol).unwrap();
+            upconverter.push(point, &mut audio).unwrap();
+        }
+        upconverter.flush(&mut audio).unwrap();
+        upconverter.flush(&mut audio).unwrap();
+        assert!(audio.iter().all(|s| s.abs() <= 1.0));
+
+        let mut baseband = Vec::new();
+        downconverter.push_block(&audio, &mut baseband).unwrap();
+        let received: Vec<u32> = baseband
+            .into_iter()
+            .skip(downconverter.delay() as usize)
+            .take(sent.len())
+            .map(|point| rx_modem.demodulate(point).unwrap())
+            .collect();
+        assert_eq!(received, sent);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::liquid_modem::error::{ModemError, ModemResult, check};
+use liquid_dsp_sys::ffi;
+use std::ptr::NonNull;
+
+/// arbitrary-rate real resampler (`resamp_rrrf`)
+pub struct Resampler {
+    resamp: NonNull<ffi::resamp_rrrf_s>,
+    rate: f32,
+    output: Vec<f32>, // scratch for one input sample's worth of output
+}
+
+// exclusively owned liquid object, see `DigitalModem`
+unsafe impl Send for Resampler {}
+
+impl Resampler {
+    /// `rate` output samples per input sample
+    pub fn new(rate: f32) -> ModemResult<Self> {
+        check_rate(rate)?;
+        Self::from_raw(unsafe { ffi::resamp_rrrf_create_default(rate) }, rate)
+    }
+
+    /// `rate` output samples per input sample through a lowpass reaching
+    /// `semi_length` input samples either side and cutting off at `cutoff`,
+    /// relative to the input rate, e.g. to keep tones close to Nyquist
+    pub fn with_cutoff(rate: f32, cutoff: f32, semi_length: u32) -> ModemResult<Self> {
+        check_rate(rate)?;
+        if !(cutoff > 0.0 && cutoff < 0.5) || semi_length == 0 {
+            return Err(ModemError::InvalidParameter(format!(
+                "resampling cutoff {cutoff} over {semi_length} samples"
+            )));
+        }
+        let resamp =
+            unsafe { ffi::resamp_rrrf_create(rate, semi_length, cutoff, STOPBAND_DB, FILTER_BANK) };
+        Self::from_raw(resamp, rate)
+    }
+
+    fn from_raw(resamp: *mut ffi::resamp_rrrf_s, rate: f32) -> ModemResult<Self> {
+        let resamp = NonNull::new(resamp).ok_or(ModemError::CreationError)?;
+        Ok(Self {
+            resamp,
+            rate,
+            output: vec![0.0; rate.ceil() as usize + 1],
+        })
+    }
+
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    /// filter delay in output samples
+    pub fn delay(&self) -> usize {
+        unsafe { ffi::resamp_rrrf_get_delay(self.resamp.as_ptr()) as usize }
+    }
+
+    /// resample one input sample, appending whatever comes out to `output`
+    pub fn push(&mut self, x: f32, output: &mut Vec<f32>) -> ModemResult<()> {
+        let mut written = 0;
+        let code = unsafe {
+            ffi::resamp_rrrf_execute(
+                self.resamp.as_ptr(),
+                x,
+                self.output.as_mut_ptr(),
+                &mut written,
+            )
+        };
+        check(code, "resamp_rrrf_execute")?;
+        output.extend_from_slice(&self.output[..written as usize]);
+        Ok(())
+    }
+
+    pub fn push_block(&mut self, input: &[f32], output: &mut Vec<f32>) -> ModemResult<()> {
+        input.iter().try_for_each(|&x| self.push(x, output))
+    }
+
+    pub fn reset(&mut self) -> ModemResult<()> {
+        check(
+            unsafe { ffi::resamp_rrrf_reset(self.resamp.as_ptr()) },
+            "resamp_rrrf_reset",
+        )
+    }
+}
+
+// attenuation and polyphase filters of `Resampler::with_cutoff`
+const STOPBAND_DB: f32 = 60.0;
+const FILTER_BANK: u32 = 64;
+
+fn check_rate(rate: f32) -> ModemResult<()> {
+    if rate > 0.0 && rate.is_finite() {
+        Ok(())
+    } else {
+        Err(ModemError::InvalidParameter(format!(
+            "resampling rate {rate}"
+        )))
+    }
+}
+
+impl Drop for Resampler {
+    fn drop(&mut self) {
+        unsafe { ffi::resamp_rrrf_destroy(self.resamp.as_ptr()) };
+    }
+}
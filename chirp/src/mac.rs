@@ -0,0 +1,340 @@
+//! Carrier sense multiple access: hold frames back while anyone else is on
+//! the air, then contend for the channel after a random backoff.
+//!
+//! The channel counts as busy while the keying's tones carry energy
+//! `threshold_db` over the noise floor or the caller's receiver is inside a
+//! frame, e.g. `link::Receiver::is_receiving`, and for `hold` after both
+//! stopped. A queued frame then waits a number of idle slots drawn from a
+//! contention window, which doubles every time the channel turns busy again
+//! in the meantime. Past the backoff it goes out in each idle slot with
+//! probability `persistence`, so two stations done waiting at the same time
+//! still tend to miss each other.
+//!
+//! Like `arq::Arq` nothing here touches a device, the caller feeds what it
+//! captures while not playing and plays what `Mac::poll` hands out.
+
+use std::{collections::VecDeque, time::Duration};
+
+use chirp_modem::{Hz, detect::Goertzel, keying::Keying};
+
+use crate::sim::XorShift;
+
+// carrier sense analysis block
+const BLOCK_SECONDS: f32 = 0.005;
+// noise floor never tracked below this, digital silence would make any
+// dither look like a carrier
+const MIN_FLOOR: f32 = 1e-8;
+// share of each quiet block's power folded into the noise floor, once the
+// first few set it
+const FLOOR_RATE: f32 = 0.05;
+// blocks that only set the floor, nothing counts as a carrier before
+const PRIME_BLOCKS: u32 = 20;
+
+/// when to key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MacConfig {
+    pub threshold_db: f32, // tone energy over the noise floor that counts as busy
+    pub hold: Duration,    // busy for this long after the energy is gone
+    pub slot: Duration,    // backoff unit, covers detection and device latency
+    pub min_window: u32,   // contention window in slots, first try
+    pub max_window: u32,   // it stops doubling here
+    pub persistence: f32,  // chance to key in each idle slot after the backoff
+}
+
+impl Default for MacConfig {
+    fn default() -> Self {
+        Self {
+            threshold_db: 10.0,
+            hold: Duration::from_millis(60),
+            slot: Duration::from_millis(40),
+            min_window: 4,
+            max_window: 64,
+            persistence: 0.5,
+        }
+    }
+}
+
+impl MacConfig {
+    /// longest a frame is held back on a quiet channel, `hold` and the
+    /// widest backoff
+    pub fn max_wait(&self) -> Duration {
+        self.hold + self.slot * self.max_window.max(1)
+    }
+}
+
+/// energy on a keying's tones against a running noise floor
+pub struct CarrierSense {
+    filters: Vec<Goertzel>,
+    block: Vec<f32>, // analysis block being filled
+    block_len: usize,
+    threshold: f32,     // power ratio over the floor
+    floor: Option<f32>, // mean noise power on a tone
+    quiet_blocks: u32,  // averaged into the floor so far
+    active: bool,       // last full block was over the threshold
+}
+
+impl CarrierSense {
+    pub fn new(keying: Keying, threshold_db: f32) -> Self {
+        let sample_rate = keying.sample_rate();
+        let block_len = (BLOCK_SECONDS * sample_rate as f32).ceil() as usize;
+        Self {
+            filters: tones(keying)
+                .into_iter()
+                .map(|freq| Goertzel::new(freq, sample_rate))
+                .collect(),
+            block: Vec::with_capacity(block_len),
+            block_len: block_len.max(1),
+            threshold: 10f32.powf(threshold_db / 10.0),
+            floor: None,
+            quiet_blocks: 0,
+            active: false,
+        }
+    }
+
+    /// whether the last block analyzed held a carrier
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// noise floor in dB full scale, once a block was analyzed
+    pub fn floor_db(&self) -> Option<f32> {
+        self.floor.map(|floor| 10.0 * floor.log10())
+    }
+
+    /// whether any block completed in `samples` held a carrier, or the last
+    /// one did if none completed
+    pub fn push(&mut self, samples: &[f32]) -> bool {
+        let (mut heard, mut analyzed) = (false, false);
+        for &sample in samples {
+            self.block.push(sample);
+            if self.block.len() == self.block_len {
+                self.analyze();
+                self.block.clear();
+                heard |= self.active;
+                analyzed = true;
+            }
+        }
+        if analyzed { heard } else { self.active }
+    }
+
+    fn analyze(&mut self) {
+        let power = self
+            .filters
+            .iter()
+            .map(|filter| filter.power(self.block.iter().copied()))
+            .fold(0.0, f32::max);
+        let floor = self.floor.get_or_insert(power.max(MIN_FLOOR));
+        self.active = self.quiet_blocks >= PRIME_BLOCKS && power > *floor * self.threshold;
+        if !self.active {
+            // a plain mean of the first blocks, then a slow one, carriers
+            // never drag it along
+            self.quiet_blocks += 1;
+            let rate = FLOOR_RATE.max(1.0 / self.quiet_blocks as f32);
+            *floor = (*floor + rate * (power - *floor)).max(MIN_FLOOR);
+        }
+    }
+}
+
+// frequencies a keying puts on the air
+pub(crate) fn tones(keying: Keying) -> Vec<Hz> {
+    match keying {
+        Keying::Amplitude(config) => vec![config.carrier_freq().round() as Hz],
+        Keying::Frequency(tones) => vec![tones.mark, tones.space],
+        Keying::MultiFrequency(config) => (0..config.order).map(|i| config.tone(i)).collect(),
+    }
+}
+
+/// transmit queue behind carrier sense and backoff
+pub struct Mac {
+    config: MacConfig,
+    sense: CarrierSense,
+    queue: VecDeque<Vec<f32>>, // frames waiting for the channel, as samples
+    rng: XorShift,
+    slot: usize,            // in samples
+    hold: usize,            // in samples
+    quiet: usize,           // samples since the channel was last busy
+    window: u32,            // current contention window
+    backoff: Option<usize>, // idle slots to wait, drawn when first needed
+    tried: usize,           // idle slots already given a chance
+}
+
+impl Mac {
+    /// `seed` picks the backoffs, stations sharing a channel need different ones
+    pub fn new(keying: Keying, config: MacConfig, seed: u64) -> Self {
+        let sample_rate = keying.sample_rate() as f32;
+        let samples = |duration: Duration| (duration.as_secs_f32() * sample_rate) as usize;
+        Self {
+            sense: CarrierSense::new(keying, config.threshold_db),
+            queue: VecDeque::new(),
+            rng: XorShift::new(seed),
+            slot: samples(config.slot).max(1),
+            hold: samples(config.hold),
+            // listen for at least `hold` before the first frame
+            quiet: 0,
+            window: config.min_window.max(1),
+            backoff: None,
+            tried: 0,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &MacConfig {
+        &self.config
+    }
+
+    pub fn carrier_sense(&self) -> &CarrierSense {
+        &self.sense
+    }
+
+    /// queue a frame's samples, they go out in order
+    pub fn enqueue(&mut self, samples: Vec<f32>) {
+        self.queue.push_back(samples);
+    }
+
+    /// frames still waiting for the channel
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// someone is on the air or was until less than `hold` ago
+    pub fn is_busy(&self) -> bool {
+        self.quiet < self.hold
+    }
+
+    /// feed captured samples, `receiving` when a frame is being received
+    ///
+    /// Leave out what's captured while our own frames play, it's our echo.
+    pub fn push(&mut self, samples: &[f32], receiving: bool) {
+        if self.sense.push(samples) || receiving {
+            if !self.queue.is_empty() && self.backoff.is_some() && !self.is_busy() {
+                // lost the contention, back off further
+                self.window = (2 * self.window).min(self.config.max_window.max(1));
+                self.backoff = None;
+            }
+            self.quiet = 0;
+            self.tried = 0;
+        } else {
+            self.quiet = self.quiet.saturating_add(samples.len());
+        }
+    }
+
+    /// the next frame's samples once it may be keyed, to be played at once
+    pub fn poll(&mut self) -> Option<Vec<f32>> {
+        if self.queue.is_empty() || self.is_busy() {
+            return None;
+        }
+        let idle_slots = (self.quiet - self.hold) / self.slot;
+        let window = self.window as u64;
+        let backoff = *self
+            .backoff
+            .get_or_insert_with(|| (self.rng.next_u64() % window) as usize);
+        if idle_slots < backoff || idle_slots < self.tried {
+            return None;
+        }
+        // one chance per idle slot
+        self.tried = idle_slots + 1;
+        if self.rng.uniform() >= self.config.persistence {
+            return None;
+        }
+        self.window = self.config.min_window.max(1);
+        self.backoff = None;
+        self.tried = 0;
+        // our frame occupies the channel, the next one contends afresh
+        self.quiet = 0;
+        self.queue.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use super::*;
+
+    fn carrier(keying: Keying, seconds: f32) -> Vec<f32> {
+        let freq = tones(keying)[0] as f32;
+        let sample_rate = keying.sample_rate() as f32;
+        (0..(seconds * sample_rate) as usize)
+            .map(|n| 0.3 * (TAU * freq * n as f32 / sample_rate).sin())
+            .collect()
+    }
+
+    fn noise(seconds: f32, seed: u64) -> Vec<f32> {
+        let mut rng = XorShift::new(seed);
+        (0..(seconds * 48_000.0) as usize)
+            .map(|_| 0.001 * rng.gaussian())
+            .collect()
+    }
+
+    #[test]
+    fn test_carrier_sense_follows_tones_over_noise() {
+        for keying in [
+            Keying::Amplitude(Default::default()),
+            Keying::Frequency(Default::default()),
+            Keying::MultiFrequency(Default::default()),
+        ] {
+            let mut sense = CarrierSense::new(keying, 10.0);
+            sense.push(&noise(0.2, 1));
+            assert!(!sense.is_active());
+            let floor = sense.floor_db().unwrap();
+
+            let mut signal = noise(0.05, 2);
+            for (s, c) in signal.iter_mut().zip(carrier(keying, 0.05)) {
+                *s += c;
+            }
+            sense.push(&signal);
+            assert!(sense.is_active(), "{}", keying.name());
+            assert_eq!(sense.floor_db(), Some(floor), "floor stays put");
+
+            sense.push(&noise(0.05, 3));
+            assert!(!sense.is_active());
+        }
+    }
+
+    #[test]
+    fn test_waits_for_quiet_then_backoff() {
+        let keying = Keying::default();
+        let config = MacConfig {
+            persistence: 1.0,
+            ..MacConfig::default()
+        };
+        let mut mac = Mac::new(keying, config, 7);
+        mac.push(&noise(0.1, 1), false);
+        mac.enqueue(vec![1.0; 10]);
+        // someone else keys up before we could
+        mac.push(&carrier(keying, 0.02), false);
+        assert!(mac.is_busy());
+        assert_eq!(mac.poll(), None);
+
+        // no earlier than `hold` after, no later than the whole window
+        let block = noise(0.005, 2);
+        let mut waited = Duration::ZERO;
+        while mac.poll().is_none() {
+            mac.push(&block, false);
+            waited += Duration::from_millis(5);
+            assert!(waited <= config.max_wait());
+        }
+        assert!(waited >= config.hold);
+        assert_eq!(mac.queued(), 0);
+    }
+
+    #[test]
+    fn test_receiving_blocks_and_window_grows() {
+        let keying = Keying::default();
+        let config = MacConfig {
+            persistence: 0.0,
+            ..MacConfig::default()
+        };
+        let mut mac = Mac::new(keying, config, 11);
+        mac.enqueue(vec![0.5; 10]);
+        let quiet = noise(0.5, 1);
+        for _ in 0..4 {
+            mac.push(&quiet, false);
+            // never keys without persistence, but has drawn a backoff
+            assert_eq!(mac.poll(), None);
+            mac.push(&quiet[..100], true);
+            assert!(mac.is_busy());
+        }
+        assert_eq!(mac.window, config.max_window.min(config.min_window << 4));
+    }
+}
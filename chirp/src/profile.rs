@@ -0,0 +1,487 @@
+//! Named link settings, so both ends only have to agree on a name.
+//!
+//! Built-in profiles ship in `profiles.toml`. A user file in the same format
+//! adds profiles or overrides built-in ones:
+//!
+//! ```toml
+//! [profiles.ultrasonic-fast]
+//! fec = "h74"                  # only the error correction changes
+//!
+//! [profiles.office]
+//! base = "ultrasonic-robust"   # start from another profile
+//! description = "our meeting room"
+//!
+//! [profiles.office.modulation]
+//! keying = "ook"               # ook: carrier, bit_rate
+//! carrier = 18000              # fsk: carrier, bandwidth, bit_rate
+//! bit_rate = 1500              # mfsk: carrier, bandwidth, order
+//!
+//! [profiles.office.preamble]
+//! high_freq = 20000.0          # any field of `ChirpConfig` but the rate
+//! ```
+//!
+//! An override replaces the fields it sets. `modulation` is the exception,
+//! its fields only make sense together so it is replaced as a whole.
+//! Frequencies are in Hz and rates hold at `SAMPLE_RATE`, a sound card at
+//! another rate is resampled, see `audio::ResampledBackend`.
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use chirp_modem::{
+    Hz, SAMPLE_RATE, config::ModemConfig, fsk::FskTones, interleave::Interleaver, keying::Keying,
+    mfsk::MfskConfig,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{frame::Coding, link::LinkConfig, sync::ChirpConfig};
+
+const BUILT_IN: &str = include_str!("profiles.toml");
+
+// longest chain of `base` references followed, catches cycles
+const MAX_DEPTH: usize = 8;
+
+#[derive(Error, Debug)]
+pub enum ProfileError {
+    #[error("Reading {path} failed: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Parsing {origin} failed: {source}")]
+    Parse {
+        origin: String,
+        source: toml::de::Error,
+    },
+
+    #[error("Unknown profile {0}")]
+    Unknown(String),
+
+    #[error("Profile {profile} is invalid: {reason}")]
+    Invalid { profile: String, reason: String },
+}
+
+pub type ProfileResult<T> = Result<T, ProfileError>;
+
+/// a resolved profile, ready to use
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub description: String,
+    pub link: LinkConfig,
+}
+
+/// every profile known, built-in ones overridden by user files
+#[derive(Debug, Clone)]
+pub struct Profiles {
+    specs: BTreeMap<String, ProfileSpec>,
+}
+
+// one `[profiles.<name>]` table, everything left out comes from the base
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileSpec {
+    base: Option<String>,
+    description: Option<String>,
+    fec: Option<String>,
+    interleaver: Option<String>,
+    modulation: Option<Modulation>,
+    #[serde(default)]
+    preamble: PreambleSpec,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "keying", rename_all = "lowercase", deny_unknown_fields)]
+enum Modulation {
+    Ook {
+        carrier: Hz,
+        bit_rate: Hz,
+    },
+    /// mark and space `bandwidth` apart, centered on `carrier`
+    Fsk {
+        carrier: Hz,
+        bandwidth: Hz,
+        bit_rate: Hz,
+    },
+    /// `order` tones spread evenly over `bandwidth`, centered on `carrier`,
+    /// the symbol rate equals the tone spacing
+    Mfsk {
+        carrier: Hz,
+        bandwidth: Hz,
+        order: u8,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PreambleSpec {
+    low_freq: Option<f32>,
+    high_freq: Option<f32>,
+    sweep_len: Option<usize>,
+    taper_len: Option<usize>,
+    max_offset: Option<f32>,
+    threshold: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileFile {
+    #[serde(default)]
+    profiles: BTreeMap<String, ProfileSpec>,
+}
+
+impl Profiles {
+    pub fn built_in() -> Self {
+        Self::empty()
+            .with_overrides(BUILT_IN, "built-in profiles")
+            .expect("built-in profiles parse")
+    }
+
+    /// built-in profiles, overridden by the file at `path`
+    pub fn load(path: impl AsRef<Path>) -> ProfileResult<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|source| ProfileError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::built_in().with_overrides(&text, &path.display().to_string())
+    }
+
+    /// merge profiles from TOML `text`, `origin` names it in errors
+    pub fn with_overrides(mut self, text: &str, origin: &str) -> ProfileResult<Self> {
+        let file: ProfileFile = toml::from_str(text).map_err(|source| ProfileError::Parse {
+            origin: origin.to_string(),
+            source,
+        })?;
+        for (name, spec) in file.profiles {
+            let merged = match self.specs.remove(&name) {
+                Some(existing) => existing.overridden_by(spec),
+                None => spec,
+            };
+            self.specs.insert(name, merged);
+        }
+        Ok(self)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.specs.keys().map(String::as_str)
+    }
+
+    pub fn get(&self, name: &str) -> ProfileResult<Profile> {
+        let spec = self.flatten(name, 0)?;
+        let invalid = |reason: String| ProfileError::Invalid {
+            profile: name.to_string(),
+            reason,
+        };
+        Ok(Profile {
+            name: name.to_string(),
+            description: spec.description.clone().unwrap_or_default(),
+            link: spec.link().map_err(invalid)?,
+        })
+    }
+
+    fn empty() -> Self {
+        Self {
+            specs: BTreeMap::new(),
+        }
+    }
+
+    // `name` with its chain of bases applied underneath
+    fn flatten(&self, name: &str, depth: usize) -> ProfileResult<ProfileSpec> {
+        let spec = self
+            .specs
+            .get(name)
+            .ok_or_else(|| ProfileError::Unknown(name.to_string()))?;
+        let Some(base) = &spec.base else {
+            return Ok(spec.clone());
+        };
+        if depth == MAX_DEPTH {
+            return Err(ProfileError::Invalid {
+                profile: name.to_string(),
+                reason: format!("bases nest deeper than {MAX_DEPTH}, is there a cycle?"),
+            });
+        }
+        let inherited = self.flatten(base, depth + 1)?;
+        Ok(ProfileSpec {
+            base: None,
+            ..inherited.overridden_by(spec.clone())
+        })
+    }
+}
+
+/// where the user's profiles live, `profiles.toml` in `config_dir`
+pub fn user_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("profiles.toml"))
+}
+
+/// per-user chirp settings, `$XDG_CONFIG_HOME/chirp` falling back to
+/// `~/.config/chirp`
+pub fn config_dir() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("chirp"))
+}
+
+impl ProfileSpec {
+    fn overridden_by(self, other: ProfileSpec) -> ProfileSpec {
+        let preamble = PreambleSpec {
+            low_freq: other.preamble.low_freq.or(self.preamble.low_freq),
+            high_freq: other.preamble.high_freq.or(self.preamble.high_freq),
+            sweep_len: other.preamble.sweep_len.or(self.preamble.sweep_len),
+            taper_len: other.preamble.taper_len.or(self.preamble.taper_len),
+            max_offset: other.preamble.max_offset.or(self.preamble.max_offset),
+            threshold: other.preamble.threshold.or(self.preamble.threshold),
+        };
+        ProfileSpec {
+            base: other.base.or(self.base),
+            description: other.description.or(self.description),
+            fec: other.fec.or(self.fec),
+            interleaver: other.interleaver.or(self.interleaver),
+            modulation: other.modulation.or(self.modulation),
+            preamble,
+        }
+    }
+
+    fn link(&self) -> Result<LinkConfig, String> {
+        let defaults = LinkConfig::default();
+        let coding = match &self.fec {
+            Some(fec) => fec.parse::<Coding>().map_err(|err| err.to_string())?,
+            None => defaults.coding,
+        };
+        coding
+            .check()
+            .map_err(|err| format!("fec {coding} isn't available here: {err}"))?;
+        let interleaver = match &self.interleaver {
+            Some(interleaver) => interleaver
+                .parse::<Interleaver>()
+                .map_err(|err| err.to_string())?,
+            None => defaults.interleaver,
+        };
+        let keying = match self.modulation {
+            Some(modulation) => modulation.keying()?,
+            None => defaults.keying,
+        };
+        Ok(LinkConfig {
+            keying,
+            coding,
+            interleaver,
+            chirp: self.preamble.chirp()?,
+        })
+    }
+}
+
+impl Modulation {
+    fn keying(self) -> Result<Keying, String> {
+        let samples_per = |rate: Hz, what: &str| match rate {
+            0 => Err(format!("{what} must be positive")),
+            rate if SAMPLE_RATE.is_multiple_of(rate) => u16::try_from(SAMPLE_RATE / rate)
+                .map_err(|_| format!("{what} of {rate} is too slow")),
+            rate => Err(format!(
+                "{what} of {rate} doesn't divide {SAMPLE_RATE} Hz into whole samples"
+            )),
+        };
+        let low = |carrier: Hz, bandwidth: Hz| {
+            carrier
+                .checked_sub(bandwidth / 2)
+                .ok_or_else(|| format!("a {bandwidth} Hz band doesn't fit below {carrier} Hz"))
+        };
+        match self {
+            Modulation::Ook { carrier, bit_rate } => {
+                ModemConfig::new(SAMPLE_RATE, carrier, bit_rate)
+                    .map(Keying::Amplitude)
+                    .map_err(|err| err.to_string())
+            }
+            Modulation::Fsk {
+                carrier,
+                bandwidth,
+                bit_rate,
+            } => FskTones::new(
+                SAMPLE_RATE,
+                carrier + bandwidth / 2,
+                low(carrier, bandwidth)?,
+                samples_per(bit_rate, "bit rate")?,
+            )
+            .map(Keying::Frequency)
+            .map_err(|err| err.to_string()),
+            Modulation::Mfsk {
+                carrier,
+                bandwidth,
+                order,
+            } => {
+                let gaps = (order as Hz).saturating_sub(1).max(1);
+                if !bandwidth.is_multiple_of(gaps) {
+                    return Err(format!(
+                        "a {bandwidth} Hz band doesn't split evenly between {order} tones"
+                    ));
+                }
+                let spacing = bandwidth / gaps;
+                let base = low(carrier, bandwidth)?;
+                // the tones have to sit on the symbol's grid as given
+                if !base.is_multiple_of(spacing.max(1)) {
+                    return Err(format!(
+                        "lowest tone {base} Hz isn't a multiple of the {spacing} Hz spacing"
+                    ));
+                }
+                MfskConfig::new(
+                    SAMPLE_RATE,
+                    order,
+                    samples_per(spacing, "tone spacing")?,
+                    base,
+                )
+                .map(Keying::MultiFrequency)
+                .map_err(|err| err.to_string())
+            }
+        }
+    }
+}
+
+impl PreambleSpec {
+    fn chirp(&self) -> Result<ChirpConfig, String> {
+        let defaults = ChirpConfig::default();
+        let config = ChirpConfig {
+            low_freq: self.low_freq.unwrap_or(defaults.low_freq),
+            high_freq: self.high_freq.unwrap_or(defaults.high_freq),
+            sweep_len: self.sweep_len.unwrap_or(defaults.sweep_len),
+            taper_len: self.taper_len.unwrap_or(defaults.taper_len),
+            max_offset: self.max_offset.unwrap_or(defaults.max_offset),
+            threshold: self.threshold.unwrap_or(defaults.threshold),
+            ..defaults
+        };
+        // what `ChirpPreamble::new` would otherwise panic on
+        if !(0.0 < config.low_freq && config.low_freq < config.high_freq) {
+            return Err("preamble sweep band is empty".into());
+        }
+        if 2.0 * config.high_freq >= config.sample_rate as f32 {
+            return Err(format!(
+                "preamble sweep reaches past nyquist at {} Hz",
+                config.sample_rate
+            ));
+        }
+        if 2 * config.taper_len >= config.sweep_len {
+            return Err("preamble taper is longer than the sweep".into());
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in_profiles_resolve() {
+        let profiles = Profiles::built_in();
+        let names: Vec<&str> = profiles.names().collect();
+        assert_eq!(
+            names,
+            [
+                "audible-debug",
+                "cable-loopback",
+                "ultrasonic-fast",
+                "ultrasonic-robust"
+            ]
+        );
+        // the robust profile's v27/rs8 needs liquid with libfec
+        let libfec = "v27/rs8".parse::<Coding>().unwrap().check().is_ok();
+        for name in names {
+            if name == "ultrasonic-robust" && !libfec {
+                assert!(matches!(
+                    profiles.get(name),
+                    Err(ProfileError::Invalid { .. })
+                ));
+                continue;
+            }
+            let profile = profiles.get(name).unwrap();
+            assert!(!profile.description.is_empty());
+            assert!(profile.link.with_sample_rate(44_100).is_ok());
+        }
+        let fast = profiles.get("ultrasonic-fast").unwrap();
+        assert_eq!(fast.link.keying.bit_rate(), 3000.0);
+        assert!(matches!(
+            profiles.get("nonexistent"),
+            Err(ProfileError::Unknown(_))
+        ));
+    }
+
+    #[test]
+    fn test_user_overrides() {
+        let profiles = Profiles::built_in()
+            .with_overrides(
+                r#"
+                [profiles.ultrasonic-robust]
+                fec = "h128"
+                [profiles.ultrasonic-robust.preamble]
+                threshold = 0.6
+
+                [profiles.office]
+                base = "ultrasonic-robust"
+                [profiles.office.modulation]
+                keying = "ook"
+                carrier = 18000
+                bit_rate = 1500
+                "#,
+                "test",
+            )
+            .unwrap();
+        let robust = profiles.get("ultrasonic-robust").unwrap();
+        // everything but the coding, which needs libfec
+        let stock = Profiles::built_in()
+            .with_overrides("[profiles.ultrasonic-robust]\nfec = \"none\"", "test")
+            .unwrap()
+            .get("ultrasonic-robust")
+            .unwrap();
+        assert_eq!(robust.link.coding.to_string(), "h128/none");
+        assert_eq!(robust.link.keying, stock.link.keying);
+        assert_eq!(robust.link.chirp.threshold, 0.6);
+        assert_eq!(robust.link.chirp.sweep_len, stock.link.chirp.sweep_len);
+
+        let office = profiles.get("office").unwrap();
+        assert_eq!(office.description, stock.description);
+        assert_eq!(office.link.interleaver, stock.link.interleaver);
+        assert_eq!(office.link.keying.name(), "ook");
+        assert_eq!(office.link.keying.bit_rate(), 1500.0);
+    }
+
+    #[test]
+    fn test_invalid_profiles_are_reported() {
+        let with = |text: &str| Profiles::built_in().with_overrides(text, "test");
+        // unknown fields and fields of another keying
+        assert!(matches!(
+            with("[profiles.x]\nfecc = \"h128\""),
+            Err(ProfileError::Parse { .. })
+        ));
+        assert!(matches!(
+            with(
+                "[profiles.x.modulation]\nkeying = \"ook\"\ncarrier = 19500\nbit_rate = 3000\norder = 4"
+            ),
+            Err(ProfileError::Parse { .. })
+        ));
+
+        let profiles = with(
+            r#"
+            [profiles.above-nyquist.modulation]
+            keying = "fsk"
+            carrier = 23500
+            bandwidth = 2000
+            bit_rate = 1000
+            [profiles.loop-a]
+            base = "loop-b"
+            [profiles.loop-b]
+            base = "loop-a"
+            "#,
+        )
+        .unwrap();
+        for name in ["above-nyquist", "loop-a"] {
+            assert!(matches!(
+                profiles.get(name),
+                Err(ProfileError::Invalid { .. })
+            ));
+        }
+    }
+}
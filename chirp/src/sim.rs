@@ -0,0 +1,326 @@
+//! Acoustic channel simulator, for exercising a link without a speaker and a
+//! microphone in the loop.
+//!
+//! Samples pass through, in order: a random fixed gain, the speaker and
+//! microphone rolloff, multipath echoes, sample clock drift between the two
+//! sound cards, then impulse noise bursts and white noise. Every effect is
+//! off unless configured and all randomness comes from one seed, so a failing
+//! run replays exactly.
+//!
+//! The rolloff and drift run through liquid's `iirfilt_rrrf` and
+//! `resamp_rrrf`. Its `channel_cccf` models the same impairments, but on
+//! complex baseband, while the link keys a real passband carrier.
+
+use crate::liquid_modem::{
+    error::{ModemError, ModemResult},
+    iir::IirFilter,
+    resamp::Resampler,
+};
+
+/// lowpass standing in for speaker and microphone response
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rolloff {
+    pub cutoff: f32, // Hz
+    pub order: u32,
+}
+
+/// delayed, attenuated copy of the direct path
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Echo {
+    pub delay: usize, // samples behind the direct path
+    pub gain: f32,
+}
+
+/// short bursts of loud noise, like a door slam or keyboard clack
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImpulseNoise {
+    pub rate: f32,      // bursts per second on average
+    pub amplitude: f32, // peak of each burst
+    pub len: usize,     // samples per burst
+}
+
+/// impairments of one simulated channel, the default passes samples through
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelConfig {
+    /// white noise against a full-scale carrier after gain, `None` for none
+    pub snr_db: Option<f32>,
+    pub rolloff: Option<Rolloff>,
+    pub echoes: Vec<Echo>,
+    /// receive clock error, e.g. -104 for 47995 Hz against 48000 Hz
+    pub drift_ppm: f32,
+    /// gain drawn uniformly from this range once per channel
+    pub gain_db: (f32, f32),
+    pub impulses: Option<ImpulseNoise>,
+    pub seed: u64,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self {
+            snr_db: None,
+            rolloff: None,
+            echoes: Vec::new(),
+            drift_ppm: 0.0,
+            gain_db: (0.0, 0.0),
+            impulses: None,
+            seed: 1,
+        }
+    }
+}
+
+impl ChannelConfig {
+    /// laptop to laptop across a small room
+    pub fn room() -> Self {
+        Self {
+            snr_db: Some(20.0),
+            rolloff: Some(Rolloff {
+                cutoff: 18_000.0,
+                order: 2,
+            }),
+            echoes: vec![
+                Echo {
+                    delay: 240, // 1.7 m further at 343 m/s
+                    gain: 0.3,
+                },
+                Echo {
+                    delay: 720,
+                    gain: 0.1,
+                },
+            ],
+            drift_ppm: Self::drift(48_000.0, 47_995.0),
+            gain_db: (-12.0, -3.0),
+            impulses: Some(ImpulseNoise {
+                rate: 0.5,
+                amplitude: 0.8,
+                len: 48,
+            }),
+            ..Self::default()
+        }
+    }
+
+    /// clock error of a receiver sampling at `rx_rate` for a transmitter at `tx_rate`
+    pub fn drift(tx_rate: f32, rx_rate: f32) -> f32 {
+        (rx_rate / tx_rate - 1.0) * 1e6
+    }
+}
+
+/// streaming channel, samples go in as sent and come out as captured
+pub struct Channel {
+    gain: f32,
+    rolloff: Option<IirFilter>,
+    echoes: Vec<Echo>,
+    history: Vec<f32>, // ring of the most recent direct-path samples
+    cursor: usize,     // where the next direct-path sample goes
+    resampler: Option<Resampler>,
+    noise_sigma: f32,
+    impulses: Option<ImpulseNoise>,
+    burst: usize, // samples left in the current impulse burst
+    burst_chance: f32,
+    rng: XorShift,
+}
+
+impl Channel {
+    pub fn new(config: &ChannelConfig, sample_rate: u32) -> ModemResult<Self> {
+        let mut rng = XorShift::new(config.seed);
+        let (low, high) = config.gain_db;
+        if low > high {
+            return Err(ModemError::InvalidParameter(format!(
+                "gain range {low} dB to {high} dB"
+            )));
+        }
+        let gain = 10f32.powf((low + (high - low) * rng.uniform()) / 20.0);
+
+        let rolloff = config
+            .rolloff
+            .map(|r| IirFilter::butterworth_lowpass(r.order, r.cutoff / sample_rate as f32))
+            .transpose()?;
+        let resampler = (config.drift_ppm != 0.0)
+            .then(|| Resampler::new(1.0 + config.drift_ppm * 1e-6))
+            .transpose()?;
+
+        // full-scale sine has power 1/2, scaled along with the signal
+        let noise_sigma = config
+            .snr_db
+            .map_or(0.0, |snr| gain * (0.5 / 10f32.powf(snr / 10.0)).sqrt());
+        let longest = config.echoes.iter().map(|e| e.delay).max().unwrap_or(0);
+        Ok(Self {
+            gain,
+            rolloff,
+            echoes: config.echoes.clone(),
+            history: vec![0.0; longest + 1],
+            cursor: 0,
+            resampler,
+            noise_sigma,
+            impulses: config.impulses,
+            burst: 0,
+            burst_chance: config.impulses.map_or(0.0, |i| i.rate / sample_rate as f32),
+            rng,
+        })
+    }
+
+    /// linear gain drawn for this channel
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// pass a block through, drift makes the output slightly longer or shorter
+    pub fn process(&mut self, input: &[f32]) -> ModemResult<Vec<f32>> {
+        let mut output = Vec::with_capacity(input.len() + 1);
+        for &x in input {
+            let mut y = self.gain * x;
+            if let Some(rolloff) = &mut self.rolloff {
+                y = rolloff.execute(y)?;
+            }
+            y = self.multipath(y);
+            match &mut self.resampler {
+                Some(resampler) => resampler.push(y, &mut output)?,
+                None => output.push(y),
+            }
+        }
+        for y in &mut output {
+            *y += self.noise();
+        }
+        Ok(output)
+    }
+
+    /// pass a whole recording through, flushing what filters still hold
+    pub fn run(&mut self, input: &[f32]) -> ModemResult<Vec<f32>> {
+        let tail = self.resampler.as_ref().map_or(0, Resampler::delay);
+        let mut output = self.process(input)?;
+        output.extend(self.process(&vec![0.0; tail + self.history.len()])?);
+        Ok(output)
+    }
+
+    fn multipath(&mut self, direct: f32) -> f32 {
+        let len = self.history.len();
+        self.history[self.cursor] = direct;
+        let mut y = direct;
+        for echo in &self.echoes {
+            y += echo.gain * self.history[(self.cursor + len - echo.delay) % len];
+        }
+        self.cursor = (self.cursor + 1) % len;
+        y
+    }
+
+    fn noise(&mut self) -> f32 {
+        let mut n = 0.0;
+        if self.noise_sigma > 0.0 {
+            n += self.noise_sigma * self.rng.gaussian();
+        }
+        if let Some(impulses) = self.impulses {
+            if self.burst == 0 && self.rng.uniform() < self.burst_chance {
+                self.burst = impulses.len;
+            }
+            if self.burst > 0 {
+                self.burst -= 1;
+                n += impulses.amplitude * (2.0 * self.rng.uniform() - 1.0);
+            }
+        }
+        n
+    }
+}
+
+// xorshift64, plenty for noise and reproducible from a seed
+pub(crate) struct XorShift {
+    state: u64,
+    spare: Option<f32>, // second Box-Muller output
+}
+
+impl XorShift {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {
+            state: seed.max(1), // all zeros is a fixed point
+            spare: None,
+        }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// uniform in [0, 1)
+    pub(crate) fn uniform(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// standard normal
+    pub(crate) fn gaussian(&mut self) -> f32 {
+        if let Some(spare) = self.spare.take() {
+            return spare;
+        }
+        let radius = (-2.0 * (1.0 - self.uniform()).ln()).sqrt();
+        let angle = std::f32::consts::TAU * self.uniform();
+        self.spare = Some(radius * angle.sin());
+        radius * angle.cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        frame::Flags,
+        link::{LinkConfig, Receiver, Transmitter},
+    };
+    use chirp_modem::SAMPLE_RATE;
+
+    #[test]
+    fn test_ideal_channel_passes_through() {
+        let input: Vec<f32> = (0..1_000).map(|i| (i as f32 * 0.1).sin()).collect();
+        let mut channel = Channel::new(&ChannelConfig::default(), SAMPLE_RATE).unwrap();
+        assert_eq!(channel.process(&input).unwrap(), input);
+    }
+
+    #[test]
+    fn test_noise_power_matches_snr() {
+        let config = ChannelConfig {
+            snr_db: Some(10.0),
+            gain_db: (-6.0, -6.0),
+            ..Default::default()
+        };
+        let mut channel = Channel::new(&config, SAMPLE_RATE).unwrap();
+        let noise = channel.process(&vec![0.0; 100_000]).unwrap();
+        let power = noise.iter().map(|n| n * n).sum::<f32>() / noise.len() as f32;
+        let signal = 0.5 * channel.gain().powi(2);
+        let snr = 10.0 * (signal / power).log10();
+        assert!((snr - 10.0).abs() < 0.2, "measured {snr} dB");
+
+        // same seed, same noise
+        let mut again = Channel::new(&config, SAMPLE_RATE).unwrap();
+        assert_eq!(again.process(&vec![0.0; 100_000]).unwrap(), noise);
+    }
+
+    #[test]
+    fn test_link_survives_echoes_and_noise() {
+        let link = LinkConfig::default();
+        let mut samples = vec![0.0; 500];
+        samples.extend(
+            Transmitter::new(link)
+                .transmit(Flags::EMPTY, b"through the air")
+                .unwrap(),
+        );
+        let config = ChannelConfig {
+            snr_db: Some(25.0),
+            echoes: vec![Echo {
+                delay: 37,
+                gain: 0.05,
+            }],
+            gain_db: (-1.0, 0.0),
+            seed: 7,
+            ..Default::default()
+        };
+        let received = Channel::new(&config, SAMPLE_RATE)
+            .unwrap()
+            .run(&samples)
+            .unwrap();
+
+        let mut payloads = Vec::new();
+        Receiver::new(link).push(&received, |reception| {
+            payloads.push(reception.frame.unwrap().payload)
+        });
+        assert_eq!(payloads, [&b"through the air"[..]]);
+    }
+}
@@ -0,0 +1,203 @@
+//! Who is on the channel: station ids and presence beacons.
+//!
+//! A station id is 16 bits derived from a nickname or any other key, so a
+//! friend can be addressed by name alone. Every station broadcasts a beacon
+//! now and then, a frame flagged `Flags::BEACON` carrying its nickname, and
+//! keeps track of its neighbors from their beacons and every other frame
+//! it hears from them.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::sim::XorShift;
+
+/// address of a station on the channel
+pub type Station = u16;
+
+/// destination of frames for everyone
+pub const BROADCAST: Station = 0xFFFF;
+
+/// source of frames from tools that don't identify themselves
+pub const ANONYMOUS: Station = 0x0000;
+
+/// time between beacons on average, each one is jittered by a quarter
+pub const BEACON_INTERVAL: Duration = Duration::from_secs(30);
+
+/// beacons a neighbor may miss before it no longer counts as nearby
+pub const MISSED_BEACONS: u32 = 3;
+
+/// station id for a nickname or key, ignoring case and surrounding blanks,
+/// never `BROADCAST` or `ANONYMOUS`
+pub fn station_id(key: &str) -> Station {
+    // FNV-1a folded to 16 bits
+    let hash = key
+        .trim()
+        .to_lowercase()
+        .bytes()
+        .fold(0x811c_9dc5u32, |hash, b| {
+            (hash ^ b as u32).wrapping_mul(0x0100_0193)
+        });
+    match ((hash >> 16) ^ hash) as Station {
+        ANONYMOUS => 1,
+        BROADCAST => BROADCAST - 1,
+        id => id,
+    }
+}
+
+/// `#1a2b` as written by `format_station`, anything else as a nickname
+pub fn parse_station(s: &str) -> Option<Station> {
+    match s.strip_prefix('#') {
+        Some(hex) => Station::from_str_radix(hex, 16).ok(),
+        None if s.trim().is_empty() => None,
+        None => Some(station_id(s)),
+    }
+}
+
+pub fn format_station(station: Station) -> String {
+    format!("#{station:04x}")
+}
+
+/// another station heard recently
+#[derive(Debug, Clone, PartialEq)]
+pub struct Neighbor {
+    pub station: Station,
+    pub nick: Option<String>, // from its last beacon
+    pub last_heard: Duration, // on the clock passed to `Presence::heard`
+    pub snr_db: f32,          // of the last frame heard
+}
+
+/// neighbor list and beacon schedule of one station
+pub struct Presence {
+    station: Station,
+    interval: Duration,
+    next_beacon: Duration,
+    neighbors: BTreeMap<Station, Neighbor>,
+    rng: XorShift,
+}
+
+impl Presence {
+    /// the first beacon goes out within a few seconds, then one every
+    /// `interval` or so, `seed` keeps stations started together apart
+    pub fn new(station: Station, interval: Duration, seed: u64) -> Self {
+        let mut rng = XorShift::new(seed);
+        let first = Duration::from_secs(5).min(interval).mul_f32(rng.uniform());
+        Self {
+            station,
+            interval,
+            next_beacon: first,
+            neighbors: BTreeMap::new(),
+            rng,
+        }
+    }
+
+    pub fn station(&self) -> Station {
+        self.station
+    }
+
+    /// a frame from `from` arrived, `nick` is set when it was a beacon
+    pub fn heard(&mut self, from: Station, nick: Option<&str>, snr_db: f32, now: Duration) {
+        if from == self.station || from == ANONYMOUS || from == BROADCAST {
+            return;
+        }
+        let neighbor = self.neighbors.entry(from).or_insert_with(|| Neighbor {
+            station: from,
+            nick: None,
+            last_heard: now,
+            snr_db,
+        });
+        if let Some(nick) = nick {
+            neighbor.nick = Some(nick.to_string());
+        }
+        neighbor.last_heard = now;
+        neighbor.snr_db = snr_db;
+    }
+
+    /// whether a beacon is due, each `true` schedules the next one
+    pub fn poll_beacon(&mut self, now: Duration) -> bool {
+        if now < self.next_beacon {
+            return false;
+        }
+        let jitter = 0.75 + 0.5 * self.rng.uniform();
+        self.next_beacon = now + self.interval.mul_f32(jitter);
+        true
+    }
+
+    pub fn neighbor(&self, station: Station) -> Option<&Neighbor> {
+        self.neighbors.get(&station)
+    }
+
+    /// neighbors heard within the last `MISSED_BEACONS` intervals, the most
+    /// recently heard first
+    pub fn nearby(&self, now: Duration) -> Vec<&Neighbor> {
+        let horizon = self.interval * MISSED_BEACONS;
+        let mut nearby: Vec<_> = self
+            .neighbors
+            .values()
+            .filter(|neighbor| now.saturating_sub(neighbor.last_heard) <= horizon)
+            .collect();
+        nearby.sort_by_key(|neighbor| std::cmp::Reverse(neighbor.last_heard));
+        nearby
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_station_ids() {
+        assert_eq!(station_id("wren"), station_id(" Wren "));
+        assert_ne!(station_id("wren"), station_id("robin"));
+        assert_eq!(parse_station("wren"), Some(station_id("wren")));
+        assert_eq!(parse_station(&format_station(0x1a2b)), Some(0x1a2b));
+        assert_eq!(parse_station("#zz"), None);
+        assert_eq!(parse_station(" "), None);
+        for i in 0..10_000 {
+            let id = station_id(&i.to_string());
+            assert!(id != ANONYMOUS && id != BROADCAST);
+        }
+    }
+
+    #[test]
+    fn test_neighbors_come_and_go() {
+        let interval = Duration::from_secs(10);
+        let mut presence = Presence::new(1, interval, 5);
+        presence.heard(1, Some("me"), 30.0, Duration::ZERO);
+        presence.heard(2, Some("wren"), 20.0, Duration::ZERO);
+        presence.heard(3, None, 12.0, Duration::from_secs(5));
+        // other frames keep the nickname from the beacon
+        presence.heard(2, None, 18.0, Duration::from_secs(20));
+
+        let now = Duration::from_secs(30);
+        let nearby = presence.nearby(now);
+        assert_eq!(nearby.len(), 2);
+        assert_eq!(nearby[0].station, 2);
+        assert_eq!(nearby[0].nick.as_deref(), Some("wren"));
+        assert_eq!(nearby[0].snr_db, 18.0);
+        assert_eq!(nearby[1].station, 3);
+
+        let later = Duration::from_secs(45);
+        let nearby: Vec<_> = presence.nearby(later).iter().map(|n| n.station).collect();
+        assert_eq!(nearby, [2]);
+    }
+
+    #[test]
+    fn test_beacons_are_jittered_around_interval() {
+        let interval = Duration::from_secs(30);
+        let mut presence = Presence::new(1, interval, 9);
+        let mut now = Duration::ZERO;
+        let mut sent = Vec::new();
+        while sent.len() < 20 {
+            if presence.poll_beacon(now) {
+                sent.push(now);
+            }
+            now += Duration::from_millis(100);
+        }
+        assert!(sent[0] <= Duration::from_secs(5));
+        for gap in sent.windows(2).map(|pair| pair[1] - pair[0]) {
+            assert!(
+                gap >= interval.mul_f32(0.75)
+                    && gap <= interval.mul_f32(1.25) + Duration::from_millis(100)
+            );
+        }
+    }
+}
@@ -0,0 +1,466 @@
+//! Linear frequency sweep preamble and matched-filter frame synchronizer.
+//!
+//! The preamble is an up-sweep immediately followed by a down-sweep across the
+//! same band. A sweep correlates sharply against itself, and a carrier offset
+//! between the two sound cards moves the up-sweep peak earlier and the
+//! down-sweep peak later by the same amount. Averaging the two peaks gives the
+//! exact start of the preamble, their spread gives the frequency offset.
+
+use std::f32::consts::{PI, TAU};
+
+use chirp_modem::Hz;
+
+/// shape of the sweep preamble
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChirpConfig {
+    pub sample_rate: Hz,
+    pub low_freq: f32,    // sweep start of the up-chirp, end of the down-chirp
+    pub high_freq: f32,   // sweep end of the up-chirp, start of the down-chirp
+    pub sweep_len: usize, // samples per sweep, the preamble holds two
+    pub taper_len: usize, // raised-cosine fade at both ends of each sweep
+    pub max_offset: f32,  // largest frequency offset searched for, Hz
+    pub threshold: f32,   // normalized correlation needed to detect a sweep
+}
+
+impl ChirpConfig {
+    /// sweep rate in Hz per second
+    pub fn sweep_rate(&self) -> f32 {
+        (self.high_freq - self.low_freq) * self.sample_rate as f32 / self.sweep_len as f32
+    }
+
+    /// the same sweep for a device running at `sample_rate`, lasting as long
+    pub fn with_sample_rate(&self, sample_rate: Hz) -> Self {
+        let scale = |samples: usize| {
+            (samples as u64 * sample_rate as u64).div_ceil(self.sample_rate as u64) as usize
+        };
+        Self {
+            sample_rate,
+            sweep_len: scale(self.sweep_len),
+            taper_len: scale(self.taper_len),
+            ..*self
+        }
+    }
+
+    /// samples the peaks move apart per Hz of frequency offset, each way
+    fn lag_per_hz(&self) -> f32 {
+        self.sample_rate as f32 / self.sweep_rate()
+    }
+}
+
+impl Default for ChirpConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 48_000,
+            low_freq: 18_000.0,
+            high_freq: 21_000.0,
+            sweep_len: 512,
+            taper_len: 32,
+            max_offset: 200.0,
+            threshold: 0.5,
+        }
+    }
+}
+
+/// up- and down-sweep templates for one configuration
+#[derive(Debug, Clone)]
+pub struct ChirpPreamble {
+    config: ChirpConfig,
+    up: Sweep,
+    down: Sweep,
+}
+
+// in-phase and quadrature copies of one tapered sweep
+#[derive(Debug, Clone)]
+struct Sweep {
+    i: Vec<f32>,
+    q: Vec<f32>,
+    energy: f32, // of the in-phase template
+}
+
+impl Sweep {
+    fn new(config: &ChirpConfig, start: f32, end: f32) -> Self {
+        let n = config.sweep_len;
+        let fs = config.sample_rate as f32;
+        let rate = (end - start) * fs / n as f32;
+        let (mut i, mut q) = (Vec::with_capacity(n), Vec::with_capacity(n));
+        for k in 0..n {
+            let t = k as f32 / fs;
+            let phase = TAU * (start * t + 0.5 * rate * t * t);
+            let gain = taper(k, n, config.taper_len);
+            i.push(gain * phase.cos());
+            q.push(gain * phase.sin());
+        }
+        let energy = i.iter().map(|x| x * x).sum();
+        Self { i, q, energy }
+    }
+
+    // normalized correlation magnitude against a window with energy `window_energy`
+    fn correlate(&self, window: impl Iterator<Item = f32> + Clone, window_energy: f32) -> f32 {
+        if window_energy <= f32::EPSILON {
+            return 0.0;
+        }
+        let (mut re, mut im) = (0.0f32, 0.0f32);
+        for ((x, ti), tq) in window.zip(self.i.iter()).zip(self.q.iter()) {
+            re += x * ti;
+            im += x * tq;
+        }
+        ((re * re + im * im) / (window_energy * self.energy)).sqrt()
+    }
+}
+
+// raised-cosine fade in/out, keeps the preamble from clicking
+fn taper(k: usize, n: usize, len: usize) -> f32 {
+    let edge = k.min(n - 1 - k);
+    if edge >= len {
+        1.0
+    } else {
+        0.5 - 0.5 * (PI * edge as f32 / len as f32).cos()
+    }
+}
+
+impl ChirpPreamble {
+    pub fn new(config: ChirpConfig) -> Self {
+        assert!(
+            2.0 * config.high_freq < config.sample_rate as f32,
+            "sweep must stay below nyquist"
+        );
+        assert!(config.low_freq < config.high_freq, "sweep band is empty");
+        assert!(
+            2 * config.taper_len < config.sweep_len,
+            "taper longer than the sweep"
+        );
+        Self {
+            up: Sweep::new(&config, config.low_freq, config.high_freq),
+            down: Sweep::new(&config, config.high_freq, config.low_freq),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &ChirpConfig {
+        &self.config
+    }
+
+    /// samples in the full preamble
+    pub fn len(&self) -> usize {
+        2 * self.config.sweep_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// transmit waveform, unit amplitude
+    pub fn samples(&self) -> impl Iterator<Item = f32> + '_ {
+        self.up.i.iter().chain(self.down.i.iter()).copied()
+    }
+}
+
+impl Default for ChirpPreamble {
+    fn default() -> Self {
+        Self::new(ChirpConfig::default())
+    }
+}
+
+/// a preamble found in the input stream
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncEvent {
+    pub offset: u64,      // stream sample index of the first preamble sample
+    pub quality: f32,     // mean normalized correlation of both sweeps, 0 to 1
+    pub freq_offset: f32, // received minus transmitted frequency, Hz
+}
+
+impl SyncEvent {
+    /// stream sample index right after the preamble, where the payload starts
+    pub fn payload_offset(&self, preamble: &ChirpPreamble) -> u64 {
+        self.offset + preamble.len() as u64
+    }
+
+    /// signal to noise ratio over the preamble, estimated from `quality`
+    ///
+    /// Noise uncorrelated with the sweeps leaves a normalized correlation of
+    /// `sqrt(S / (S + N))`. Capped at 40 dB, beyond that the estimate is noise.
+    pub fn snr_db(&self) -> f32 {
+        let rho2 = self.quality.clamp(0.0, 0.99995).powi(2);
+        10.0 * (rho2 / (1.0 - rho2)).log10()
+    }
+}
+
+// best correlation seen while tracking a peak
+#[derive(Debug, Clone, Copy)]
+struct Peak {
+    start: u64, // stream index of the window start
+    quality: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Hunting,
+    // up-sweep crossed the threshold, following it to its maximum
+    RisingUp {
+        best: Peak,
+        until: u64,
+    },
+    // up-sweep peak found, searching the expected down-sweep window
+    SeekingDown {
+        up: Peak,
+        from: u64,
+        until: u64,
+        best: Option<Peak>,
+    },
+}
+
+/// streaming matched-filter synchronizer for `ChirpPreamble`
+///
+/// Feed captured blocks as they arrive, e.g. straight from an audio callback;
+/// sample positions are counted from the first sample ever pushed, so the
+/// unknown delay between playback start and microphone arrival needs no
+/// special handling.
+pub struct ChirpSynchronizer {
+    preamble: ChirpPreamble,
+    history: Vec<f32>, // ring buffer of the last sweep_len samples
+    head: usize,
+    filled: usize,
+    energy: f64, // running energy of history
+    position: u64,
+    state: State,
+    max_lag: u64, // samples a peak may move at the largest offset
+}
+
+impl ChirpSynchronizer {
+    pub fn new(preamble: ChirpPreamble) -> Self {
+        let n = preamble.config.sweep_len;
+        let max_lag = (preamble.config.max_offset * preamble.config.lag_per_hz()).ceil() as u64;
+        Self {
+            history: vec![0.0; n],
+            head: 0,
+            filled: 0,
+            energy: 0.0,
+            position: 0,
+            state: State::Hunting,
+            max_lag,
+            preamble,
+        }
+    }
+
+    pub fn preamble(&self) -> &ChirpPreamble {
+        &self.preamble
+    }
+
+    /// samples consumed so far
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// most samples an event can trail the end of its preamble, i.e. how much
+    /// recent input a receiver has to keep to rewind to the payload start
+    pub fn latency(&self) -> usize {
+        3 * self.max_lag as usize + 1
+    }
+
+    /// consume a block, calling `on_sync` for every preamble found
+    pub fn push(&mut self, samples: &[f32], mut on_sync: impl FnMut(SyncEvent)) {
+        for &sample in samples {
+            if let Some(event) = self.push_sample(sample) {
+                on_sync(event);
+            }
+        }
+    }
+
+    pub fn push_sample(&mut self, sample: f32) -> Option<SyncEvent> {
+        let n = self.preamble.config.sweep_len;
+        let old = self.history[self.head];
+        self.history[self.head] = sample;
+        self.head = (self.head + 1) % n;
+        self.energy += (sample * sample) as f64 - (old * old) as f64;
+        self.position += 1;
+        if self.filled < n {
+            self.filled += 1;
+            if self.filled < n {
+                return None;
+            }
+        }
+        if self.head == 0 {
+            // rounding drift in the running sum, resync once per sweep length
+            self.energy = self.history.iter().map(|x| (x * x) as f64).sum();
+        }
+        let start = self.position - n as u64;
+        self.step(start)
+    }
+
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.head = 0;
+        self.filled = 0;
+        self.energy = 0.0;
+        self.state = State::Hunting;
+    }
+
+    // advance the peak search with the window starting at `start`
+    fn step(&mut self, start: u64) -> Option<SyncEvent> {
+        let threshold = self.preamble.config.threshold;
+        let n = self.preamble.config.sweep_len as u64;
+        match self.state {
+            State::Hunting => {
+                let quality = self.correlate_up();
+                if quality >= threshold {
+                    self.state = State::RisingUp {
+                        best: Peak { start, quality },
+                        until: start + self.max_lag,
+                    };
+                }
+                None
+            }
+            State::RisingUp { mut best, until } => {
+                let quality = self.correlate_up();
+                if quality > best.quality {
+                    best = Peak { start, quality };
+                }
+                if start < until {
+                    self.state = State::RisingUp { best, until };
+                } else {
+                    // an offset moves the down peak twice as far from the up peak as either moved
+                    self.state = State::SeekingDown {
+                        up: best,
+                        from: (best.start + n).saturating_sub(2 * self.max_lag),
+                        until: best.start + n + 2 * self.max_lag,
+                        best: None,
+                    };
+                }
+                None
+            }
+            State::SeekingDown {
+                up,
+                from,
+                until,
+                mut best,
+            } => {
+                if start < from {
+                    return None;
+                }
+                let quality = self.correlate_down();
+                if best.is_none_or(|peak| quality > peak.quality) {
+                    best = Some(Peak { start, quality });
+                }
+                if start < until {
+                    self.state = State::SeekingDown {
+                        up,
+                        from,
+                        until,
+                        best,
+                    };
+                    return None;
+                }
+                self.state = State::Hunting;
+                let down = best.filter(|peak| peak.quality >= threshold)?;
+                Some(self.event(up, down))
+            }
+        }
+    }
+
+    fn event(&self, up: Peak, down: Peak) -> SyncEvent {
+        let n = self.preamble.config.sweep_len as i64;
+        // up peak lands early and down peak late by the same offset-induced lag
+        let spread = down.start as i64 - up.start as i64 - n;
+        let offset = (up.start as i64 + down.start as i64 - n).div_euclid(2);
+        SyncEvent {
+            offset: offset.max(0) as u64,
+            quality: 0.5 * (up.quality + down.quality),
+            freq_offset: spread as f32 / (2.0 * self.preamble.config.lag_per_hz()),
+        }
+    }
+
+    fn window(&self) -> impl Iterator<Item = f32> + Clone + '_ {
+        // oldest sample sits at head once the ring is full
+        let (newer, older) = self.history.split_at(self.head);
+        older.iter().chain(newer).copied()
+    }
+
+    fn correlate_up(&self) -> f32 {
+        self.preamble
+            .up
+            .correlate(self.window(), self.energy as f32)
+    }
+
+    fn correlate_down(&self) -> f32 {
+        self.preamble
+            .down
+            .correlate(self.window(), self.energy as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // deterministic white-ish noise
+    fn noise(len: usize, amplitude: f32) -> impl Iterator<Item = f32> {
+        let mut state = 0x2545_F491u32;
+        (0..len).map(move |_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            amplitude * (state as f32 / u32::MAX as f32 - 0.5)
+        })
+    }
+
+    fn detect(config: ChirpConfig, signal: &[f32]) -> Vec<SyncEvent> {
+        let mut sync = ChirpSynchronizer::new(ChirpPreamble::new(config));
+        let mut events = Vec::new();
+        // uneven blocks like an audio callback would deliver
+        for block in signal.chunks(333) {
+            sync.push(block, |event| events.push(event));
+        }
+        events
+    }
+
+    #[test]
+    fn test_exact_offset_in_noise() {
+        let preamble = ChirpPreamble::default();
+        let delay = 1_234;
+        let mut signal: Vec<f32> = noise(delay, 0.2).collect();
+        signal.extend(
+            preamble
+                .samples()
+                .zip(noise(preamble.len(), 0.2))
+                .map(|(s, n)| 0.3 * s + n),
+        );
+        signal.extend(noise(2_000, 0.2));
+
+        let events = detect(*preamble.config(), &signal);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].offset, delay as u64);
+        assert!(events[0].quality > 0.5);
+        assert!(events[0].freq_offset.abs() < 5.0);
+        // sweep power 0.3^2 / 2 against uniform noise 0.2^2 / 12, about 11.3 dB
+        assert!(
+            (events[0].snr_db() - 11.3).abs() < 1.5,
+            "{}",
+            events[0].snr_db()
+        );
+    }
+
+    #[test]
+    fn test_frequency_offset_estimate() {
+        let config = ChirpConfig::default();
+        for shift in [60.0, -60.0] {
+            // same sweep played sharp or flat
+            let shifted = ChirpPreamble::new(ChirpConfig {
+                low_freq: config.low_freq + shift,
+                high_freq: config.high_freq + shift,
+                ..config
+            });
+            let mut signal: Vec<f32> = noise(500, 0.01).collect();
+            signal.extend(shifted.samples());
+            signal.extend(noise(2_000, 0.01));
+
+            let events = detect(config, &signal);
+            assert_eq!(events.len(), 1);
+            assert!((events[0].offset as i64 - 500).abs() <= 1);
+            assert!(
+                (events[0].freq_offset - shift).abs() < 6.0,
+                "estimated {} for {shift}",
+                events[0].freq_offset
+            );
+        }
+    }
+}
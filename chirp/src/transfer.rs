@@ -0,0 +1,899 @@
+//! File transfer between two stations, on top of `arq` messages.
+//!
+//! The sender offers a file with its name, size and SHA-256. The receiver
+//! accepts it at the offset it already has from an earlier attempt, 0 for a
+//! fresh one, and the rest follows in chunks of one `arq` message each.
+//! Once all of it arrived the receiver checks the hash and tells the sender.
+//!
+//! ```text
+//! offer   | kind | id | size | sha-256 | name     |
+//!         | 1    | 4  | 4    | 32      | the rest |
+//!
+//! accept  | kind | id | offset |
+//!         | 1    | 4  | 4      |
+//!
+//! decline | kind | id |
+//!         | 1    | 4  |
+//!
+//! chunk   | kind | id | offset | data     |
+//!         | 1    | 4  | 4      | the rest |
+//!
+//! done    | kind | id | verified |
+//!         | 1    | 4  | 1        |
+//! ```
+//!
+//! Kinds start at 0x80, so packets can share a stream with messages that
+//! start with a smaller byte, like chat lines with their nickname length.
+//!
+//! An interrupted transfer isn't retried, offering the same file again
+//! resumes it once the receiver accepts with what it kept. `Transfers`
+//! touches no files, like `arq::Arq` it only passes messages: the caller
+//! reads what it offers, stores what arrives and keeps partial downloads
+//! for later, see `Transfers::partial`.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+    time::Duration,
+};
+
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::{
+    arq::{Arq, ArqError, Delivery, DeliveryStatus, MessageId},
+    station::{BROADCAST, Station, format_station},
+};
+
+/// picked by the offering station, unique among its transfers
+pub type TransferId = u32;
+
+/// file bytes per chunk
+pub const CHUNK_SIZE: usize = 1024;
+
+/// largest file offered or accepted, it takes a while at these rates
+pub const MAX_FILE: usize = 1 << 20;
+
+/// longest file name in bytes, it has to fit an offer
+pub const MAX_NAME: usize = 255;
+
+/// an accepted download with nothing from its sender for this long is
+/// given up on
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(120);
+
+// chunks handed to `Arq` ahead of their acknowledgement, per upload
+const AHEAD: usize = 2;
+
+const OFFER: u8 = 0x80;
+const ACCEPT: u8 = 0x81;
+const DECLINE: u8 = 0x82;
+const CHUNK: u8 = 0x83;
+const DONE: u8 = 0x84;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TransferError {
+    #[error("Transfer packet truncated: needed {needed} bytes, got {available}.")]
+    Truncated { needed: usize, available: usize },
+
+    #[error("Unknown transfer packet kind: {0:#04x}")]
+    UnknownKind(u8),
+
+    #[error("File too large: {0} bytes, at most {MAX_FILE}")]
+    TooLarge(usize),
+
+    #[error("Bad file name: {0:?}")]
+    BadName(String),
+
+    #[error("Files go to a single station, not to everyone")]
+    Broadcast,
+
+    #[error("No transfer {0}")]
+    Unknown(Key),
+
+    #[error(transparent)]
+    Arq(#[from] ArqError),
+}
+
+pub type TransferResult<T> = Result<T, TransferError>;
+
+/// a transfer, by the station offering the file and its id there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Key {
+    pub from: Station,
+    pub id: TransferId,
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", format_station(self.from), self.id)
+    }
+}
+
+/// one `arq` message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    Offer {
+        id: TransferId,
+        size: u32,
+        hash: [u8; 32],
+        name: String,
+    },
+    Accept {
+        id: TransferId,
+        offset: u32, // bytes the receiver has already
+    },
+    Decline {
+        id: TransferId,
+    },
+    Chunk {
+        id: TransferId,
+        offset: u32,
+        data: Vec<u8>,
+    },
+    Done {
+        id: TransferId,
+        verified: bool, // the hash matched
+    },
+}
+
+impl Packet {
+    pub fn encode(&self) -> Vec<u8> {
+        let (kind, id) = match self {
+            Packet::Offer { id, .. } => (OFFER, id),
+            Packet::Accept { id, .. } => (ACCEPT, id),
+            Packet::Decline { id } => (DECLINE, id),
+            Packet::Chunk { id, .. } => (CHUNK, id),
+            Packet::Done { id, .. } => (DONE, id),
+        };
+        let mut bytes = vec![kind];
+        bytes.extend_from_slice(&id.to_be_bytes());
+        match self {
+            Packet::Offer {
+                size, hash, name, ..
+            } => {
+                bytes.extend_from_slice(&size.to_be_bytes());
+                bytes.extend_from_slice(hash);
+                bytes.extend_from_slice(name.as_bytes());
+            }
+            Packet::Accept { offset, .. } => bytes.extend_from_slice(&offset.to_be_bytes()),
+            Packet::Decline { .. } => {}
+            Packet::Chunk { offset, data, .. } => {
+                bytes.extend_from_slice(&offset.to_be_bytes());
+                bytes.extend_from_slice(data);
+            }
+            Packet::Done { verified, .. } => bytes.push(*verified as u8),
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> TransferResult<Self> {
+        let &kind = bytes.first().ok_or(TransferError::Truncated {
+            needed: 1,
+            available: 0,
+        })?;
+        let needed = match kind {
+            OFFER => 41,
+            ACCEPT | CHUNK => 9,
+            DECLINE => 5,
+            DONE => 6,
+            _ => return Err(TransferError::UnknownKind(kind)),
+        };
+        if bytes.len() < needed {
+            return Err(TransferError::Truncated {
+                needed,
+                available: bytes.len(),
+            });
+        }
+        let u32_at =
+            |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let id = u32_at(1);
+        Ok(match kind {
+            OFFER => {
+                let mut hash = [0; 32];
+                hash.copy_from_slice(&bytes[9..41]);
+                let name = String::from_utf8_lossy(&bytes[41..]).into_owned();
+                Packet::Offer {
+                    id,
+                    size: u32_at(5),
+                    hash,
+                    name: check_name(name)?,
+                }
+            }
+            ACCEPT => Packet::Accept {
+                id,
+                offset: u32_at(5),
+            },
+            DECLINE => Packet::Decline { id },
+            CHUNK => Packet::Chunk {
+                id,
+                offset: u32_at(5),
+                data: bytes[9..].to_vec(),
+            },
+            _ => Packet::Done {
+                id,
+                verified: bytes[5] != 0,
+            },
+        })
+    }
+}
+
+/// whether an `arq` message is a transfer packet
+pub fn is_packet(data: &[u8]) -> bool {
+    data.first().is_some_and(|&kind| kind >= OFFER)
+}
+
+// a plain file name, nothing a receiver could be tricked into writing
+// elsewhere with
+fn check_name(name: String) -> TransferResult<String> {
+    let bad = name.is_empty()
+        || name.len() > MAX_NAME
+        || name == "."
+        || name == ".."
+        || name.contains(['/', '\\', '\0']);
+    if bad {
+        return Err(TransferError::BadName(name));
+    }
+    Ok(name)
+}
+
+/// a file offered to us
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    pub key: Key,
+    pub name: String,
+    pub size: usize,
+    pub hash: [u8; 32], // SHA-256 of the whole file
+}
+
+/// why a transfer ended early
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    #[error("not acknowledged")]
+    Unacknowledged,
+
+    #[error("hash mismatch")]
+    HashMismatch,
+
+    #[error("sender gone quiet")]
+    Stalled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferEvent {
+    /// someone offers us a file, answer with `accept` or `decline`
+    Offered(Offer),
+    /// our offer was taken, from `offset` on
+    Accepted {
+        key: Key,
+        offset: usize,
+    },
+    Declined {
+        key: Key,
+    },
+    /// bytes acknowledged of an upload or received of a download
+    Progress {
+        key: Key,
+        done: usize,
+        size: usize,
+    },
+    /// a download is complete and matches its hash
+    Received {
+        offer: Offer,
+        data: Vec<u8>,
+    },
+    /// the receiver has all of an upload and its hash matched
+    Delivered {
+        key: Key,
+    },
+    /// given up on, what a receiver kept can be resumed with a new offer
+    Failed {
+        key: Key,
+        failure: Failure,
+    },
+}
+
+/// uploads and downloads of one station
+pub struct Transfers {
+    station: Station,
+    next_id: TransferId,
+    uploads: BTreeMap<TransferId, Upload>,
+    downloads: BTreeMap<Key, Download>,
+    outbox: VecDeque<(Station, Key, Packet)>, // not handed to `Arq` yet
+    sent: HashMap<MessageId, (Key, Option<usize>)>, // with the offset a chunk ends at
+}
+
+struct Upload {
+    to: Station,
+    data: Vec<u8>,
+    accepted: bool,
+    next: usize,    // offset of the next chunk to hand out
+    acked: usize,   // bytes acknowledged
+    pending: usize, // chunks handed out and not acknowledged
+}
+
+struct Download {
+    offer: Offer,
+    data: Vec<u8>,
+    accepted: bool,
+    heard: Option<Duration>, // last chunk, `None` until the next poll
+}
+
+impl Transfers {
+    /// ids count up from one picked by `seed`, so a restart doesn't reuse
+    /// any a receiver may remember
+    pub fn new(station: Station, seed: u64) -> Self {
+        Self {
+            station,
+            next_id: seed as TransferId,
+            uploads: BTreeMap::new(),
+            downloads: BTreeMap::new(),
+            outbox: VecDeque::new(),
+            sent: HashMap::new(),
+        }
+    }
+
+    pub fn station(&self) -> Station {
+        self.station
+    }
+
+    /// offer `data` to `to` as `name`
+    pub fn offer(&mut self, to: Station, name: &str, data: Vec<u8>) -> TransferResult<Key> {
+        if to == BROADCAST {
+            return Err(TransferError::Broadcast);
+        }
+        if data.len() > MAX_FILE {
+            return Err(TransferError::TooLarge(data.len()));
+        }
+        let name = check_name(name.to_string())?;
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let key = Key {
+            from: self.station,
+            id,
+        };
+        let packet = Packet::Offer {
+            id,
+            size: data.len() as u32,
+            hash: sha256(&data),
+            name,
+        };
+        self.outbox.push_back((to, key, packet));
+        self.uploads.insert(
+            id,
+            Upload {
+                to,
+                data,
+                accepted: false,
+                next: 0,
+                acked: 0,
+                pending: 0,
+            },
+        );
+        Ok(key)
+    }
+
+    /// take an offered file, `kept` is what's left of an earlier attempt at
+    /// it, if anything
+    pub fn accept(&mut self, key: Key, kept: Vec<u8>) -> TransferResult<Vec<TransferEvent>> {
+        let download = self
+            .downloads
+            .get_mut(&key)
+            .ok_or(TransferError::Unknown(key))?;
+        download.accepted = true;
+        download.heard = None;
+        download.data = kept;
+        download.data.truncate(download.offer.size);
+        let offset = download.data.len() as u32;
+        self.outbox
+            .push_back((key.from, key, Packet::Accept { id: key.id, offset }));
+        // nothing left to send, an empty file or one kept whole
+        Ok(self.check(key).into_iter().collect())
+    }
+
+    pub fn decline(&mut self, key: Key) -> TransferResult<()> {
+        self.downloads
+            .remove(&key)
+            .ok_or(TransferError::Unknown(key))?;
+        self.outbox
+            .push_back((key.from, key, Packet::Decline { id: key.id }));
+        Ok(())
+    }
+
+    /// a download received so far, to keep for a resume if it's cut short
+    pub fn partial(&self, key: Key) -> Option<&[u8]> {
+        Some(&self.downloads.get(&key)?.data)
+    }
+
+    /// take a transfer packet from `from`, see `is_packet`
+    pub fn receive(
+        &mut self,
+        from: Station,
+        data: &[u8],
+        now: Duration,
+    ) -> TransferResult<Vec<TransferEvent>> {
+        let mut events = Vec::new();
+        match Packet::decode(data)? {
+            Packet::Offer {
+                id,
+                size,
+                hash,
+                name,
+            } => {
+                let size = size as usize;
+                if size > MAX_FILE {
+                    return Err(TransferError::TooLarge(size));
+                }
+                let offer = Offer {
+                    key: Key { from, id },
+                    name,
+                    size,
+                    hash,
+                };
+                self.downloads.insert(
+                    offer.key,
+                    Download {
+                        offer: offer.clone(),
+                        data: Vec::new(),
+                        accepted: false,
+                        heard: None,
+                    },
+                );
+                events.push(TransferEvent::Offered(offer));
+            }
+            Packet::Chunk { id, offset, data } => {
+                let key = Key { from, id };
+                if let Some(download) = self.downloads.get_mut(&key) {
+                    // in order by `arq`, anything else is left from an
+                    // earlier attempt
+                    if download.accepted && offset as usize == download.data.len() {
+                        download.heard = Some(now);
+                        download.data.extend(data);
+                        download.data.truncate(download.offer.size);
+                        events.push(TransferEvent::Progress {
+                            key,
+                            done: download.data.len(),
+                            size: download.offer.size,
+                        });
+                        events.extend(self.check(key));
+                    }
+                }
+            }
+            Packet::Accept { id, offset } => {
+                let key = self.key(id);
+                if let Some(upload) = self.upload(id, from) {
+                    upload.accepted = true;
+                    upload.next = (offset as usize).min(upload.data.len());
+                    upload.acked = upload.next;
+                    events.push(TransferEvent::Accepted {
+                        key,
+                        offset: upload.next,
+                    });
+                }
+            }
+            Packet::Decline { id } => {
+                if self.upload(id, from).is_some() {
+                    self.uploads.remove(&id);
+                    events.push(TransferEvent::Declined { key: self.key(id) });
+                }
+            }
+            Packet::Done { id, verified } => {
+                if let Some(upload) = self.upload(id, from) {
+                    let size = upload.data.len();
+                    // done can overtake the acknowledgement of the last chunks
+                    if upload.acked < size {
+                        events.push(TransferEvent::Progress {
+                            key: self.key(id),
+                            done: size,
+                            size,
+                        });
+                    }
+                    self.uploads.remove(&id);
+                    let key = self.key(id);
+                    events.push(match verified {
+                        true => TransferEvent::Delivered { key },
+                        false => TransferEvent::Failed {
+                            key,
+                            failure: Failure::HashMismatch,
+                        },
+                    });
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// hand answers and the next chunks of accepted uploads to `arq`, and
+    /// give up on stalled downloads
+    pub fn poll(&mut self, arq: &mut Arq, now: Duration) -> TransferResult<Vec<TransferEvent>> {
+        let mut events = Vec::new();
+        self.downloads.retain(|&key, download| {
+            if !download.accepted {
+                return true;
+            }
+            let heard = *download.heard.get_or_insert(now);
+            let stalled = now.saturating_sub(heard) >= STALL_TIMEOUT;
+            if stalled {
+                events.push(TransferEvent::Failed {
+                    key,
+                    failure: Failure::Stalled,
+                });
+            }
+            !stalled
+        });
+        while let Some((to, key, packet)) = self.outbox.pop_front() {
+            let message = arq.send(to, packet.encode())?;
+            self.sent.insert(message, (key, None));
+        }
+        for (&id, upload) in &mut self.uploads {
+            while upload.accepted && upload.pending < AHEAD && upload.next < upload.data.len() {
+                let end = (upload.next + CHUNK_SIZE).min(upload.data.len());
+                let packet = Packet::Chunk {
+                    id,
+                    offset: upload.next as u32,
+                    data: upload.data[upload.next..end].to_vec(),
+                };
+                let message = arq.send(upload.to, packet.encode())?;
+                let key = Key {
+                    from: self.station,
+                    id,
+                };
+                self.sent.insert(message, (key, Some(end)));
+                upload.next = end;
+                upload.pending += 1;
+            }
+        }
+        Ok(events)
+    }
+
+    /// pass on each `Delivery` of messages `poll` handed to `arq`
+    pub fn delivered(&mut self, delivery: &Delivery) -> Option<TransferEvent> {
+        let (key, end) = match delivery.status {
+            DeliveryStatus::Sent => return None,
+            _ => self.sent.remove(&delivery.id)?,
+        };
+        if delivery.status == DeliveryStatus::Failed {
+            let upload = key.from == self.station && self.uploads.remove(&key.id).is_some();
+            let download = self.downloads.remove(&key).is_some();
+            return (upload || download).then_some(TransferEvent::Failed {
+                key,
+                failure: Failure::Unacknowledged,
+            });
+        }
+        let upload = self.uploads.get_mut(&key.id)?;
+        let end = end?;
+        upload.pending -= 1;
+        upload.acked = upload.acked.max(end);
+        Some(TransferEvent::Progress {
+            key,
+            done: upload.acked,
+            size: upload.data.len(),
+        })
+    }
+
+    /// nothing offered, accepted or waiting to be handed out
+    pub fn is_idle(&self) -> bool {
+        self.uploads.is_empty()
+            && self.downloads.values().all(|download| !download.accepted)
+            && self.outbox.is_empty()
+    }
+
+    fn key(&self, id: TransferId) -> Key {
+        Key {
+            from: self.station,
+            id,
+        }
+    }
+
+    // our upload `id`, if it went to `from`
+    fn upload(&mut self, id: TransferId, from: Station) -> Option<&mut Upload> {
+        self.uploads.get_mut(&id).filter(|upload| upload.to == from)
+    }
+
+    // finish an accepted download once it has every byte, and tell the
+    // sender how it went
+    fn check(&mut self, key: Key) -> Option<TransferEvent> {
+        let download = self.downloads.get(&key)?;
+        if !download.accepted || download.data.len() < download.offer.size {
+            return None;
+        }
+        let Download { offer, data, .. } = self.downloads.remove(&key)?;
+        let verified = sha256(&data) == offer.hash;
+        self.outbox.push_back((
+            key.from,
+            key,
+            Packet::Done {
+                id: key.id,
+                verified,
+            },
+        ));
+        Some(match verified {
+            true => TransferEvent::Received { offer, data },
+            false => TransferEvent::Failed {
+                key,
+                failure: Failure::HashMismatch,
+            },
+        })
+    }
+}
+
+/// SHA-256 digest, as offered files are checked against
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{
+        arq::ArqConfig,
+        frame::{Coding, Flags, Header},
+        link::LinkConfig,
+    };
+
+    const STEP: Duration = Duration::from_millis(100);
+
+    // one end of a transfer with everything it was told
+    struct Node {
+        arq: Arq,
+        transfers: Transfers,
+        deliveries: Arc<Mutex<Vec<Delivery>>>,
+        events: Vec<TransferEvent>,
+    }
+
+    impl Node {
+        fn new(station: Station) -> Self {
+            let deliveries = Arc::new(Mutex::new(Vec::new()));
+            let log = deliveries.clone();
+            let config = ArqConfig::sliding_window(8);
+            Self {
+                arq: Arq::new(station, LinkConfig::default(), config)
+                    .with_delivery_callback(move |delivery| log.lock().unwrap().push(delivery)),
+                transfers: Transfers::new(station, station as u64 * 1000),
+                deliveries,
+                events: Vec::new(),
+            }
+        }
+
+        // send what's due to `other`, unless `heard` is false
+        fn step(&mut self, other: &mut Node, now: Duration, heard: bool) {
+            let events = self.transfers.poll(&mut self.arq, now).unwrap();
+            self.events.extend(events);
+            while let Some(out) = self.arq.poll_transmit(now) {
+                if !heard {
+                    continue;
+                }
+                let header = Header {
+                    flags: Flags::EMPTY,
+                    coding: Coding::NONE,
+                    source: self.arq.station(),
+                    destination: out.to,
+                    sequence: 0,
+                    length: out.payload.len() as u16,
+                    fragment: out.fragment,
+                };
+                for received in other.arq.receive(&header, &out.payload, now).unwrap() {
+                    assert!(is_packet(&received.data));
+                    let events = other.transfers.receive(received.from, &received.data, now);
+                    other.events.extend(events.unwrap());
+                }
+            }
+            let deliveries = std::mem::take(&mut *self.deliveries.lock().unwrap());
+            for delivery in deliveries {
+                self.events.extend(self.transfers.delivered(&delivery));
+            }
+        }
+
+        fn offered(&self) -> Offer {
+            self.events
+                .iter()
+                .find_map(|event| match event {
+                    TransferEvent::Offered(offer) => Some(offer.clone()),
+                    _ => None,
+                })
+                .unwrap()
+        }
+    }
+
+    // both ends take turns for `seconds`, frames only arrive while `heard`
+    // says so
+    fn run(
+        a: &mut Node,
+        b: &mut Node,
+        start: Duration,
+        seconds: u64,
+        heard: impl Fn(Duration) -> bool,
+    ) {
+        let mut now = start;
+        while now < start + Duration::from_secs(seconds) {
+            a.step(b, now, heard(now));
+            b.step(a, now, heard(now));
+            now += STEP;
+        }
+    }
+
+    fn file(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_packet_round_trip_and_hash() {
+        for packet in [
+            Packet::Offer {
+                id: 7,
+                size: 5000,
+                hash: sha256(b"abc"),
+                name: "notes.txt".into(),
+            },
+            Packet::Accept {
+                id: 7,
+                offset: 2048,
+            },
+            Packet::Decline { id: 7 },
+            Packet::Chunk {
+                id: u32::MAX,
+                offset: 1024,
+                data: vec![1, 2, 3],
+            },
+            Packet::Done {
+                id: 7,
+                verified: true,
+            },
+        ] {
+            let bytes = packet.encode();
+            assert!(is_packet(&bytes));
+            assert_eq!(Packet::decode(&bytes), Ok(packet));
+        }
+        assert_eq!(
+            Packet::decode(&[0x85]),
+            Err(TransferError::UnknownKind(0x85))
+        );
+        assert_eq!(
+            Packet::decode(&[ACCEPT, 0, 0]),
+            Err(TransferError::Truncated {
+                needed: 9,
+                available: 3
+            })
+        );
+        let mut sneaky = Packet::Offer {
+            id: 1,
+            size: 1,
+            hash: [0; 32],
+            name: "x".into(),
+        }
+        .encode();
+        sneaky.pop();
+        sneaky.extend_from_slice(b"../.bashrc");
+        assert!(matches!(
+            Packet::decode(&sneaky),
+            Err(TransferError::BadName(_))
+        ));
+    }
+
+    #[test]
+    fn test_offer_accept_and_deliver() {
+        let (mut a, mut b) = (Node::new(1), Node::new(2));
+        let data = file(3 * CHUNK_SIZE + 100);
+        let key = a.transfers.offer(2, "notes.txt", data.clone()).unwrap();
+        assert_eq!(
+            a.transfers.offer(BROADCAST, "notes.txt", vec![]),
+            Err(TransferError::Broadcast)
+        );
+        assert!(a.transfers.offer(2, "a/b", vec![]).is_err());
+        run(&mut a, &mut b, Duration::ZERO, 5, |_| true);
+
+        let offer = b.offered();
+        assert_eq!((offer.key, offer.size), (key, data.len()));
+        assert_eq!(offer.name, "notes.txt");
+        assert!(
+            b.transfers
+                .accept(offer.key, Vec::new())
+                .unwrap()
+                .is_empty()
+        );
+        run(&mut a, &mut b, Duration::from_secs(5), 120, |_| true);
+
+        assert!(b.events.contains(&TransferEvent::Received {
+            offer,
+            data: data.clone()
+        }));
+        assert!(
+            a.events
+                .contains(&TransferEvent::Accepted { key, offset: 0 })
+        );
+        assert_eq!(a.events.last(), Some(&TransferEvent::Delivered { key }));
+        let progress: Vec<_> = a
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                TransferEvent::Progress { done, size, .. } => Some((*done, *size)),
+                _ => None,
+            })
+            .collect();
+        assert!(progress.len() >= 2);
+        assert!(progress.is_sorted());
+        assert_eq!(progress.last(), Some(&(data.len(), data.len())));
+        assert!(a.transfers.is_idle() && b.transfers.is_idle());
+    }
+
+    #[test]
+    fn test_interrupted_transfer_resumes() {
+        let (mut a, mut b) = (Node::new(1), Node::new(2));
+        let data = file(6 * CHUNK_SIZE);
+        a.transfers.offer(2, "song.ogg", data.clone()).unwrap();
+        run(&mut a, &mut b, Duration::ZERO, 5, |_| true);
+        let first = b.offered();
+        b.transfers.accept(first.key, Vec::new()).unwrap();
+
+        // the link drops out partway, both ends give up
+        let mut kept = Vec::new();
+        let mut now = Duration::from_secs(5);
+        while now < Duration::from_secs(600) {
+            let heard = kept.len() < 2 * CHUNK_SIZE;
+            a.step(&mut b, now, heard);
+            b.step(&mut a, now, heard);
+            if let Some(partial) = b.transfers.partial(first.key) {
+                kept = partial.to_vec();
+            }
+            now += STEP;
+        }
+        let failed = |node: &Node, failure| {
+            node.events.iter().any(
+                |event| matches!(event, TransferEvent::Failed { failure: f, .. } if *f == failure),
+            )
+        };
+        assert!(failed(&a, Failure::Unacknowledged));
+        assert!(failed(&b, Failure::Stalled));
+        assert!(kept.len() >= 2 * CHUNK_SIZE && kept.len() < data.len());
+        assert_eq!(kept, data[..kept.len()]);
+
+        // offered again, the receiver picks up where it left off
+        a.events.clear();
+        b.events.clear();
+        let key = a.transfers.offer(2, "song.ogg", data.clone()).unwrap();
+        run(&mut a, &mut b, now, 5, |_| true);
+        let second = b.offered();
+        assert_eq!(second.hash, first.hash);
+        b.transfers.accept(second.key, kept.clone()).unwrap();
+        run(&mut a, &mut b, now + Duration::from_secs(5), 120, |_| true);
+
+        assert!(a.events.contains(&TransferEvent::Accepted {
+            key,
+            offset: kept.len()
+        }));
+        assert!(b.events.contains(&TransferEvent::Received {
+            offer: second,
+            data
+        }));
+        assert_eq!(a.events.last(), Some(&TransferEvent::Delivered { key }));
+    }
+
+    #[test]
+    fn test_corrupt_partial_fails_the_hash_check() {
+        let (mut a, mut b) = (Node::new(1), Node::new(2));
+        let key = a.transfers.offer(2, "key.pub", file(1500)).unwrap();
+        run(&mut a, &mut b, Duration::ZERO, 5, |_| true);
+        b.transfers.accept(key, vec![0xff; 1000]).unwrap();
+        run(&mut a, &mut b, Duration::from_secs(5), 60, |_| true);
+
+        let mismatch = TransferEvent::Failed {
+            key,
+            failure: Failure::HashMismatch,
+        };
+        assert!(b.events.contains(&mismatch));
+        assert_eq!(a.events.last(), Some(&mismatch));
+
+        // declined offers end there
+        let key = a.transfers.offer(2, "key.pub", file(1500)).unwrap();
+        run(&mut a, &mut b, Duration::from_secs(65), 5, |_| true);
+        b.transfers.decline(key).unwrap();
+        assert!(b.transfers.decline(key).is_err());
+        run(&mut a, &mut b, Duration::from_secs(70), 5, |_| true);
+        assert_eq!(a.events.last(), Some(&TransferEvent::Declined { key }));
+        assert!(a.transfers.is_idle() && b.transfers.is_idle());
+    }
+}